@@ -1,12 +1,12 @@
 use std::{thread, time::Duration};
 
-use arma_rs::{Context, Group};
+use arma_rs::{pool::SpawnError, Context, Group};
 
-pub fn sleep(ctx: Context, duration: u64, id: String) {
-    thread::spawn(move || {
+pub fn sleep(ctx: Context, duration: u64, id: String) -> Result<(), SpawnError> {
+    ctx.spawn(move |ctx| {
         thread::sleep(Duration::from_secs(duration));
         ctx.callback_data("timer:sleep", "done", Some(id)).unwrap();
-    });
+    })
 }
 
 pub fn group() -> Group {
@@ -21,7 +21,7 @@ pub enum Data {
 pub enum StructData {
     Map(Vec<FieldNamed>),
     Tuple(Vec<FieldUnnamed>),
-    NewType(FieldUnnamed),
+    NewType(Box<FieldUnnamed>),
 }
 
 impl ContainerData {
@@ -55,9 +55,9 @@ impl ContainerData {
         }
     }
 
-    pub fn impl_from_arma(&self) -> TokenStream {
+    pub fn impl_from_arma(&self, conversion: r#struct::Conversion) -> TokenStream {
         match self.data {
-            Data::Struct(ref data) => r#struct::impl_from_arma(&self.attributes, data),
+            Data::Struct(ref data) => r#struct::impl_from_arma(&self.attributes, data, conversion),
         }
     }
 }
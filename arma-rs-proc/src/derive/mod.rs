@@ -2,15 +2,25 @@ mod attributes;
 mod data;
 mod r#struct;
 
-use proc_macro2::TokenStream;
+use proc_macro2::{Span, TokenStream};
 use quote::quote;
-use syn::{DeriveInput, Result};
+use syn::{DeriveInput, Error, GenericParam, Lifetime, LifetimeParam, Result};
 
 use data::ContainerData;
+use r#struct::Conversion;
 
 pub fn generate_from_arma(input: DeriveInput) -> Result<TokenStream> {
     let container = parse_container_data(input)?;
-    let body = container.impl_from_arma();
+    let body = container.impl_from_arma(Conversion::Owned);
+
+    // Owned structs can also be used where borrowing is possible, such as fields of a `FromArmaRef` struct
+    let input_lifetime = Lifetime::new("'arma", Span::call_site());
+    let mut ref_generics = container.generics.clone();
+    ref_generics.params.insert(
+        0,
+        GenericParam::Lifetime(LifetimeParam::new(input_lifetime.clone())),
+    );
+    let (ref_impl_generics, _, _) = ref_generics.split_for_impl();
 
     let ident = container.ident;
     let (impl_generics, ty_generics, where_clause) = container.generics.split_for_impl();
@@ -18,6 +28,54 @@ pub fn generate_from_arma(input: DeriveInput) -> Result<TokenStream> {
         #[automatically_derived]
         impl #impl_generics arma_rs::FromArma for #ident #ty_generics #where_clause {
             fn from_arma(func_input: String) -> Result<Self, arma_rs::FromArmaError> {
+                <Self as arma_rs::FromArma>::from_arma_str(&func_input)
+            }
+
+            fn from_arma_str(func_input: &str) -> Result<Self, arma_rs::FromArmaError> {
+                #body
+            }
+        }
+
+        #[automatically_derived]
+        impl #ref_impl_generics arma_rs::FromArmaRef<#input_lifetime> for #ident #ty_generics #where_clause {
+            fn from_arma_ref(func_input: &#input_lifetime str) -> Result<Self, arma_rs::FromArmaError> {
+                <Self as arma_rs::FromArma>::from_arma_str(func_input)
+            }
+        }
+    })
+}
+
+pub fn generate_from_arma_ref(input: DeriveInput) -> Result<TokenStream> {
+    let container = parse_container_data(input)?;
+    let body = container.impl_from_arma(Conversion::Borrowed);
+
+    // The input lives as long as the struct's lifetime, or any lifetime if it has none
+    let mut lifetimes = container.generics.lifetimes();
+    let input_lifetime = match (lifetimes.next(), lifetimes.next()) {
+        (Some(lifetime), None) => lifetime.lifetime.clone(),
+        (None, _) => Lifetime::new("'arma", Span::call_site()),
+        (Some(_), Some(lifetime)) => {
+            return Err(Error::new_spanned(
+                lifetime,
+                "FromArmaRef can only be derived for structs with at most one lifetime",
+            ));
+        }
+    };
+    let mut generics = container.generics.clone();
+    if container.generics.lifetimes().next().is_none() {
+        generics.params.insert(
+            0,
+            GenericParam::Lifetime(LifetimeParam::new(input_lifetime.clone())),
+        );
+    }
+    let (impl_generics, _, _) = generics.split_for_impl();
+
+    let ident = container.ident;
+    let (_, ty_generics, where_clause) = container.generics.split_for_impl();
+    Ok(quote! {
+        #[automatically_derived]
+        impl #impl_generics arma_rs::FromArmaRef<#input_lifetime> for #ident #ty_generics #where_clause {
+            fn from_arma_ref(func_input: &#input_lifetime str) -> Result<Self, arma_rs::FromArmaError> {
                 #body
             }
         }
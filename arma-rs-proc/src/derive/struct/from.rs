@@ -6,29 +6,63 @@ use crate::derive::{
     data::{Field, FieldNamed, FieldUnnamed, StructData},
 };
 
-pub fn impl_from_arma(attributes: &ContainerAttributes, data: &StructData) -> TokenStream {
+/// Trait used to convert the fields, `func_input` is always a `&str`
+#[derive(Clone, Copy)]
+pub enum Conversion {
+    /// `FromArma::from_arma_str`, fields are copied out of the input where needed
+    Owned,
+    /// `FromArmaRef::from_arma_ref`, fields can borrow from the input
+    Borrowed,
+}
+
+impl Conversion {
+    fn convert(self, input: TokenStream) -> TokenStream {
+        match self {
+            Self::Owned => quote!(arma_rs::FromArma::from_arma_str(#input)?),
+            Self::Borrowed => quote!(arma_rs::FromArmaRef::from_arma_ref(#input)?),
+        }
+    }
+}
+
+pub fn impl_from_arma(
+    attributes: &ContainerAttributes,
+    data: &StructData,
+    conversion: Conversion,
+) -> TokenStream {
     // For simplicity sake we assume that theres no conflicts and everything has already been validated
     match &data {
-        StructData::Map(fields) => map_struct(attributes, fields),
-        StructData::Tuple(fields) => tuple_struct(attributes, fields),
-        StructData::NewType(field) => newtype_struct(attributes, field),
+        StructData::Map(fields) => map_struct(attributes, fields, conversion),
+        StructData::Tuple(fields) => tuple_struct(attributes, fields, conversion),
+        StructData::NewType(field) => newtype_struct(attributes, field.as_ref(), conversion),
     }
 }
 
-fn map_struct(attributes: &ContainerAttributes, fields: &[FieldNamed]) -> TokenStream {
+fn map_struct(
+    attributes: &ContainerAttributes,
+    fields: &[FieldNamed],
+    conversion: Conversion,
+) -> TokenStream {
     if *attributes.transparent.value() {
-        return newtype_struct(attributes, fields.first().unwrap());
+        return newtype_struct(attributes, fields.first().unwrap(), conversion);
     }
 
     let mut setup = TokenStream::new();
     setup.extend(quote! {
-        let mut input_as_values = std::collections::HashMap::<String, arma_rs::Value>::default();
-
-        let input_pairs: Vec<(String, arma_rs::Value)> = FromArma::from_arma(func_input)?;
-        for (k, v) in input_pairs {
-            if input_as_values.insert(k.clone(), v).is_some() {
-                return Err(arma_rs::FromArmaError::DuplicateField(k));
+        let mut input_as_values =
+            std::collections::HashMap::<std::borrow::Cow<str>, &str>::default();
+
+        for pair in arma_rs::array_elements(func_input)? {
+            let [k, v]: [&str; 2] = arma_rs::array_elements(pair)?.try_into().map_err(
+                |pair: Vec<&str>| arma_rs::FromArmaError::InvalidLength {
+                    expected: 2,
+                    actual: pair.len(),
+                },
+            )?;
+            let k: std::borrow::Cow<str> = arma_rs::FromArmaRef::from_arma_ref(k)?;
+            if input_as_values.contains_key(&k) {
+                return Err(arma_rs::FromArmaError::DuplicateField(k.into_owned()));
             }
+            input_as_values.insert(k, v);
         }
     });
     if *attributes.default.value() {
@@ -42,11 +76,10 @@ fn map_struct(attributes: &ContainerAttributes, fields: &[FieldNamed]) -> TokenS
 
         let some_match = if *field.attributes.from_str.value() {
             quote!(input_value
-                .to_string()
                 .parse()
                 .map_err(arma_rs::FromArmaError::custom)?)
         } else {
-            quote!(arma_rs::FromArma::from_arma(input_value.to_string())?)
+            conversion.convert(quote!(input_value))
         };
 
         let none_match = if *field.attributes.default.value() {
@@ -67,7 +100,7 @@ fn map_struct(attributes: &ContainerAttributes, fields: &[FieldNamed]) -> TokenS
 
     let check_unknown = quote! {
         if let Some(unknown) = input_as_values.keys().next() {
-            return Err(arma_rs::FromArmaError::UnknownField(unknown.clone()));
+            return Err(arma_rs::FromArmaError::UnknownField(unknown.to_string()));
         }
     };
     quote! {
@@ -81,11 +114,14 @@ fn map_struct(attributes: &ContainerAttributes, fields: &[FieldNamed]) -> TokenS
     }
 }
 
-fn tuple_struct(attributes: &ContainerAttributes, fields: &[FieldUnnamed]) -> TokenStream {
+fn tuple_struct(
+    attributes: &ContainerAttributes,
+    fields: &[FieldUnnamed],
+    conversion: Conversion,
+) -> TokenStream {
     let mut setup = TokenStream::new();
     setup.extend(quote! {
-        let input_as_values: Vec<arma_rs::Value> = arma_rs::FromArma::from_arma(func_input)?;
-        let mut input_as_values = input_as_values.into_iter();
+        let mut input_as_values = arma_rs::array_elements(func_input)?.into_iter();
     });
     if *attributes.default.value() {
         setup.extend(quote! {
@@ -99,11 +135,10 @@ fn tuple_struct(attributes: &ContainerAttributes, fields: &[FieldUnnamed]) -> To
 
         let some_match = if *field.attributes.from_str.value() {
             quote!(input_value
-                .to_string()
                 .parse()
                 .map_err(arma_rs::FromArmaError::custom)?)
         } else {
-            quote!(arma_rs::FromArma::from_arma(input_value.to_string())?)
+            conversion.convert(quote!(input_value))
         };
 
         let none_match = if *field.attributes.default.value() {
@@ -145,13 +180,17 @@ fn tuple_struct(attributes: &ContainerAttributes, fields: &[FieldUnnamed]) -> To
     }
 }
 
-fn newtype_struct(_attributes: &ContainerAttributes, field: &impl Field) -> TokenStream {
+fn newtype_struct(
+    _attributes: &ContainerAttributes,
+    field: &impl Field,
+    conversion: Conversion,
+) -> TokenStream {
     let token = field.token();
 
     let field_body = if *field.attributes().from_str.value() {
         quote!(func_input.parse().map_err(arma_rs::FromArmaError::custom)?)
     } else {
-        quote!(arma_rs::FromArma::from_arma(func_input)?)
+        conversion.convert(quote!(func_input))
     };
 
     quote! {
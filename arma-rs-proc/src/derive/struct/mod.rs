@@ -5,7 +5,7 @@ mod validate;
 use proc_macro2::Span;
 use syn::{Error, Result};
 
-pub use from::impl_from_arma;
+pub use from::{impl_from_arma, Conversion};
 pub use into::impl_into_arma;
 pub use validate::validate_attributes;
 
@@ -46,7 +46,7 @@ impl StructData {
 
                 if fields.unnamed.len() == 1 {
                     let field = FieldUnnamed::new(errors, fields.unnamed[0].clone(), 0);
-                    Ok(Self::NewType(field))
+                    Ok(Self::NewType(Box::new(field)))
                 } else {
                     let fields = fields
                         .unnamed
@@ -0,0 +1,68 @@
+use proc_macro2::TokenStream;
+use quote::quote;
+use syn::{DeriveInput, Error, LitStr, Result};
+
+pub fn generate_arma_event(input: DeriveInput) -> Result<TokenStream> {
+    let mut name = None;
+    let mut func = None;
+    for attr in &input.attrs {
+        if !attr.path().is_ident("arma_event") {
+            continue;
+        }
+        attr.parse_nested_meta(|meta| {
+            let target = if meta.path.is_ident("name") {
+                &mut name
+            } else if meta.path.is_ident("func") {
+                &mut func
+            } else {
+                return Err(meta.error("unknown arma_event attribute, expected `name` or `func`"));
+            };
+            if target.is_some() {
+                return Err(meta.error("duplicate arma_event attribute"));
+            }
+            let value: LitStr = meta.value()?.parse()?;
+            if value.value().is_empty() {
+                return Err(Error::new_spanned(
+                    value,
+                    "arma_event values can not be empty",
+                ));
+            }
+            *target = Some(value);
+            Ok(())
+        })?;
+    }
+
+    let name = name.map_or_else(
+        || quote! { env!("CARGO_PKG_NAME") },
+        |name| quote! { #name },
+    );
+    let func = func
+        .unwrap_or_else(|| LitStr::new(&snake_case(&input.ident.to_string()), input.ident.span()));
+
+    let ident = input.ident;
+    let (impl_generics, ty_generics, where_clause) = input.generics.split_for_impl();
+    Ok(quote! {
+        #[automatically_derived]
+        impl #impl_generics arma_rs::ArmaEvent for #ident #ty_generics #where_clause {
+            const NAME: &'static str = #name;
+            const FUNC: &'static str = #func;
+        }
+    })
+}
+
+/// `PlayerJoined` to `player_joined`, `HTTPRequest` to `http_request`
+fn snake_case(ident: &str) -> String {
+    let chars = ident.chars().collect::<Vec<_>>();
+    let mut out = String::new();
+    for (i, c) in chars.iter().enumerate() {
+        if c.is_uppercase() && i != 0 {
+            let prev = chars[i - 1];
+            let next_lower = chars.get(i + 1).is_some_and(|n| n.is_lowercase());
+            if prev.is_lowercase() || prev.is_ascii_digit() || (prev.is_uppercase() && next_lower) {
+                out.push('_');
+            }
+        }
+        out.extend(c.to_lowercase());
+    }
+    out
+}
@@ -0,0 +1,237 @@
+use proc_macro2::{Ident, Span, TokenStream};
+use quote::quote;
+use syn::{
+    meta::ParseNestedMeta, Error, Expr, ExprLit, FnArg, ItemFn, Lit, LitBool, Result, ReturnType,
+    Type,
+};
+
+#[derive(Default)]
+/// Options given to the `#[arma]` attribute
+pub struct Options {
+    version: Option<Expr>,
+    name: Option<Expr>,
+    no_args: Option<LitBool>,
+}
+
+impl Options {
+    pub fn parse(&mut self, meta: &ParseNestedMeta) -> Result<()> {
+        if meta.path.is_ident("version") {
+            Self::set(meta, &mut self.version, "version")
+        } else if meta.path.is_ident("name") {
+            Self::set(meta, &mut self.name, "name")
+        } else if meta.path.is_ident("no_args") {
+            if self.no_args.is_some() {
+                return Err(meta.error("duplicate `no_args` option"));
+            }
+            self.no_args = Some(meta.value()?.parse()?);
+            Ok(())
+        } else {
+            Err(meta.error("unknown arma option, expected `version`, `name` or `no_args`"))
+        }
+    }
+
+    fn set(meta: &ParseNestedMeta, target: &mut Option<Expr>, option: &str) -> Result<()> {
+        if target.is_some() {
+            return Err(meta.error(format!("duplicate `{option}` option")));
+        }
+        let value: Expr = meta.value()?.parse()?;
+        if let Expr::Lit(ExprLit {
+            lit: Lit::Str(s), ..
+        }) = &value
+        {
+            if s.value().is_empty() {
+                return Err(Error::new_spanned(
+                    s,
+                    format!("`{option}` can not be empty"),
+                ));
+            }
+            if option == "name" && s.value().contains(['/', '\\']) {
+                return Err(Error::new_spanned(
+                    s,
+                    "`name` is the name of the extension, not a path",
+                ));
+            }
+        }
+        *target = Some(value);
+        Ok(())
+    }
+}
+
+/// Check that the function can be called to create the extension
+fn validate(ast: &ItemFn) -> Result<()> {
+    let sig = &ast.sig;
+    if let Some(asyncness) = sig.asyncness {
+        return Err(Error::new_spanned(
+            asyncness,
+            "#[arma] function can not be async",
+        ));
+    }
+    if !sig.generics.params.is_empty() {
+        return Err(Error::new_spanned(
+            &sig.generics,
+            "#[arma] function can not be generic",
+        ));
+    }
+    if let Some(input) = sig.inputs.first() {
+        let message = match input {
+            FnArg::Receiver(_) => "#[arma] function can not be a method",
+            FnArg::Typed(_) => "#[arma] function can not take arguments",
+        };
+        return Err(Error::new_spanned(&sig.inputs, message));
+    }
+    let returns_extension = match &sig.output {
+        ReturnType::Type(_, ty) => match ty.as_ref() {
+            Type::Path(path) => path.path.segments.last().is_some_and(|segment| {
+                segment.ident == "Extension" && segment.arguments.is_empty()
+            }),
+            _ => false,
+        },
+        ReturnType::Default => false,
+    };
+    if !returns_extension {
+        let message = "#[arma] function must return `Extension`, created with `Extension::build()...finish()`";
+        return Err(match &sig.output {
+            ReturnType::Type(_, ty) => Error::new_spanned(ty, message),
+            ReturnType::Default => Error::new_spanned(sig, message),
+        });
+    }
+    Ok(())
+}
+
+pub fn generate_extension(options: Options, ast: ItemFn) -> Result<TokenStream> {
+    validate(&ast)?;
+    let init = ast.sig.ident.clone();
+
+    let extern_type = "system";
+
+    let version = options.version.map_or_else(
+        || quote! { env!("CARGO_PKG_VERSION") },
+        |version| quote! { #version },
+    );
+    let name = options
+        .name
+        .map_or_else(|| quote! { None }, |name| quote! { Some(#name) });
+    let no_args = options.no_args.is_none_or(|no_args| no_args.value);
+
+    #[cfg(all(target_os = "windows", target_arch = "x86"))]
+    let prefix = "safe32_";
+
+    #[cfg(not(all(target_os = "windows", target_arch = "x86")))]
+    let prefix = "";
+
+    macro_rules! fn_ident {
+        ( $name:literal ) => {
+            Ident::new(&format!("{prefix}{}", $name), Span::call_site())
+        };
+    }
+    let versionfn = fn_ident!("RVExtensionVersion");
+    let noargfn = fn_ident!("RVExtension");
+    let argfn = fn_ident!("RVExtensionArgs");
+    let callbackfn = fn_ident!("RVExtensionRegisterCallback");
+    let contextfn = fn_ident!("RVExtensionContext");
+
+    let (noarg_export, noarg_link) = if no_args {
+        (
+            quote! {
+                /// Run extension function, called by Arma on `callExtension` without arguments.
+                /// This function is generated by the [`arma_rs::arma`] proc macro.
+                #[no_mangle]
+                #[doc(hidden)]
+                pub unsafe extern #extern_type fn #noargfn(output: *mut arma_rs_libc::c_char, size: arma_rs_libc::size_t, function: *mut arma_rs_libc::c_char) {
+                    let ext = arma_rs_extension();
+                    if ext.allow_no_args() {
+                        ext.handle_call(function, output, size, None, None, true);
+                    }
+                }
+            },
+            quote! {
+                raw("/EXPORT:_RVExtension@12=_safe32_RVExtension@12");
+            },
+        )
+    } else {
+        (TokenStream::new(), TokenStream::new())
+    };
+
+    Ok(quote! {
+        use arma_rs::libc as arma_rs_libc;
+
+        static RV_EXTENSION: std::sync::OnceLock<arma_rs::Extension> = std::sync::OnceLock::new();
+
+        /// The extension, built by the first exported function Arma calls.
+        /// This function is generated by the [`arma_rs::arma`] proc macro.
+        #[doc(hidden)]
+        fn arma_rs_extension() -> &'static arma_rs::Extension {
+            RV_EXTENSION.get_or_init(|| {
+                arma_rs::module::set_attributes(#name, #version);
+                let ext = #init();
+                ext.publish_feature_flags();
+                unsafe { arma_rs_libc::atexit(arma_rs_shutdown) };
+                ext
+            })
+        }
+
+        #[cfg(all(target_os="windows", target_arch="x86"))]
+        arma_rs::link_args::windows! {
+            unsafe {
+                raw("/EXPORT:_RVExtensionVersion@8=_safe32_RVExtensionVersion@8");
+                #noarg_link
+                raw("/EXPORT:_RVExtensionArgs@20=_safe32_RVExtensionArgs@20");
+                raw("/EXPORT:_RVExtensionRegisterCallback@4=_safe32_RVExtensionRegisterCallback@4");
+                raw("/EXPORT:_RVExtensionContext@8=_safe32_RVExtensionContext@8");
+            }
+        }
+
+        /// Returns extension version, called by Arma on extension load.
+        /// This function is generated by the [`arma_rs::arma`] proc macro.
+        #[no_mangle]
+        #[doc(hidden)]
+        pub unsafe extern #extern_type fn #versionfn(output: *mut arma_rs_libc::c_char, size: arma_rs_libc::size_t) -> arma_rs_libc::c_int {
+            arma_rs::write_cstr(arma_rs_extension().version().to_string(), output, size);
+            0
+        }
+
+        #noarg_export
+
+        /// Run extension function with arguments, called by Arma on `callExtension` with arguments.
+        /// This function is generated by the [`arma_rs::arma`] proc macro.
+        #[no_mangle]
+        #[doc(hidden)]
+        pub unsafe extern #extern_type fn #argfn(output: *mut arma_rs_libc::c_char, size: arma_rs_libc::size_t, function: *mut arma_rs_libc::c_char, args: *mut *mut arma_rs_libc::c_char, arg_count: arma_rs_libc::c_int) -> arma_rs_libc::c_int {
+            arma_rs_extension().handle_call(function, output, size, Some(args), Some(arg_count), true)
+        }
+
+        /// Set extension callback, called by Arma on extension load.
+        /// This function is generated by the [`arma_rs::arma`] proc macro.
+        #[no_mangle]
+        #[doc(hidden)]
+        pub unsafe extern #extern_type fn #callbackfn(callback: arma_rs::Callback) {
+            let ext = arma_rs_extension();
+            ext.register_callback(callback);
+            ext.run_callbacks();
+        }
+
+        /// Shut the extension down, registered with `atexit` when the extension is loaded.
+        /// Runs when the process exits or the library is unloaded.
+        /// On Windows it runs while the library is detached, where joining threads can deadlock.
+        /// This function is generated by the [`arma_rs::arma`] proc macro.
+        #[doc(hidden)]
+        extern "C" fn arma_rs_shutdown() {
+            if let Some(ext) = RV_EXTENSION.get() {
+                #[cfg(windows)]
+                ext.shutdown_detached();
+                #[cfg(not(windows))]
+                ext.shutdown();
+            }
+        }
+
+        /// Provide extension call context, called by Arma on `callExtension`.
+        /// This function is generated by the [`arma_rs::arma`] proc macro.
+        #[no_mangle]
+        #[doc(hidden)]
+        pub unsafe extern #extern_type fn #contextfn(args: *mut *mut arma_rs_libc::c_char, arg_count: arma_rs_libc::c_int) {
+            arma_rs_extension().handle_call_context(args, arg_count);
+        }
+
+        #ast
+    })
+}
@@ -0,0 +1,111 @@
+use std::ffi::CString;
+
+use arma_rs::{Extension, FromArma, Group, Value};
+use criterion::{Criterion, black_box, criterion_group, criterion_main};
+
+const BUFFER_SIZE: libc::size_t = 10 * 1024;
+
+fn extension() -> Extension {
+    Extension::build()
+        .command("ping", || {})
+        .group(
+            "math",
+            Group::new()
+                .command("add", |a: i32, b: i32, c: i32| a + b + c)
+                .command("range", |n: u32| (0..n).collect::<Vec<u32>>()),
+        )
+        .command("echo", |s: String| s)
+        .finish()
+}
+
+/// Call `function` like Arma would, with arguments owned by the caller
+fn call(
+    extension: &Extension,
+    function: &CString,
+    args: &mut [*mut libc::c_char],
+    output: &mut [libc::c_char],
+) -> libc::c_int {
+    let count = libc::c_int::try_from(args.len()).unwrap();
+    unsafe {
+        extension.handle_call(
+            function.as_ptr().cast_mut(),
+            output.as_mut_ptr(),
+            BUFFER_SIZE,
+            (!args.is_empty()).then_some(args.as_mut_ptr()),
+            Some(count),
+            true,
+        )
+    }
+}
+
+fn dispatch(c: &mut Criterion) {
+    let extension = extension();
+    let mut output = vec![0; BUFFER_SIZE];
+
+    let ping = CString::new("ping").unwrap();
+    c.bench_function("dispatch no arguments", |b| {
+        b.iter(|| call(&extension, black_box(&ping), &mut [], &mut output));
+    });
+
+    let add = CString::new("math:add").unwrap();
+    let numbers: Vec<CString> = ["1", "2", "3"]
+        .into_iter()
+        .map(|n| CString::new(n).unwrap())
+        .collect();
+    let mut args: Vec<_> = numbers.iter().map(|n| n.as_ptr().cast_mut()).collect();
+    c.bench_function("dispatch three arguments", |b| {
+        b.iter(|| call(&extension, black_box(&add), &mut args, &mut output));
+    });
+
+    let mut args: Vec<_> = numbers[..2].iter().map(|n| n.as_ptr().cast_mut()).collect();
+    c.bench_function("dispatch wrong argument count", |b| {
+        b.iter(|| call(&extension, black_box(&add), &mut args, &mut output));
+    });
+
+    let echo = CString::new("echo").unwrap();
+    let text = CString::new(format!("\"{}\"", "a".repeat(1024))).unwrap();
+    let mut args = vec![text.as_ptr().cast_mut()];
+    c.bench_function("dispatch string argument", |b| {
+        b.iter(|| call(&extension, black_box(&echo), &mut args, &mut output));
+    });
+
+    let range = CString::new("math:range").unwrap();
+    let length = CString::new("500").unwrap();
+    let mut args = vec![length.as_ptr().cast_mut()];
+    c.bench_function("dispatch array output", |b| {
+        b.iter(|| call(&extension, black_box(&range), &mut args, &mut output));
+    });
+}
+
+fn output(c: &mut Criterion) {
+    let value = Value::Array(
+        (0..100)
+            .map(|i| {
+                Value::Array(vec![
+                    Value::Number(f64::from(i)),
+                    Value::String(format!("item \"{i}\"")),
+                    Value::Boolean(i % 2 == 0),
+                ])
+            })
+            .collect(),
+    );
+    c.bench_function("value to string", |b| {
+        b.iter(|| black_box(&value).to_string());
+    });
+}
+
+fn parse(c: &mut Criterion) {
+    let input = format!(
+        "[{}]",
+        (0..100)
+            .map(|i| format!("[{i}, \"item \"\"{i}\"\"\", [{i}, {i}]]"))
+            .collect::<Vec<_>>()
+            .join(", ")
+    );
+    c.bench_function("parse nested array", |b| {
+        b.iter(|| <Vec<(u32, String, [u32; 2])>>::from_arma_str(black_box(&input)).unwrap());
+    });
+}
+
+criterion_group!(benches, dispatch, output, parse);
+criterion_main!(benches);
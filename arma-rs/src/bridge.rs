@@ -0,0 +1,369 @@
+//! Requests answered by SQF, see [`crate::Context::request`].
+
+use std::{
+    collections::HashMap,
+    future::Future,
+    pin::Pin,
+    sync::{
+        Arc, Condvar, Mutex,
+        atomic::{AtomicU64, Ordering},
+    },
+    task::{Poll, Waker},
+    time::{Duration, Instant},
+};
+
+use crate::{CallbackError, FromArma, FromArmaError, Value};
+
+/// Reference SQF function to answer requests sent with [`crate::Context::request`].
+///
+/// Call it from the `ExtensionCallback` event handler with the extension's name, the callback data
+/// and the code computing the answer. The code receives the request's data in `_this`.
+/// ```sqf
+/// addMissionEventHandler ["ExtensionCallback", {
+///     params ["_name", "_function", "_data"];
+///     if (_name == "my_extension" && _function == "unit_position") then {
+///         ["my_extension", _data, { getPosASL (objectFromNetId _this) }] call arma_rs_fnc_reply;
+///     };
+/// }];
+/// ```
+pub const REPLY_SQF: &str = r#"params ["_extension", "_data", "_code"];
+(parseSimpleArray _data) params ["_id", "_args"];
+private _result = _args call _code;
+if (isNil "_result") then { _result = [] };
+_extension callExtension ["::reply", [_id, _result]];
+"#;
+
+/// Error that can occur while waiting for the answer to a request
+#[derive(Debug)]
+pub enum RequestError {
+    /// The request could not be sent
+    Callback(CallbackError),
+    /// No answer was received before the timeout
+    Timeout,
+    /// The extension shut down before an answer was received
+    Closed,
+    /// The answer could not be converted to the requested type
+    Decode(FromArmaError),
+}
+
+impl std::fmt::Display for RequestError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Self::Callback(e) => write!(f, "failed to send request: {e}"),
+            Self::Timeout => write!(f, "request timed out"),
+            Self::Closed => write!(f, "extension shut down before the request was answered"),
+            Self::Decode(e) => write!(f, "invalid answer: {e}"),
+        }
+    }
+}
+
+impl std::error::Error for RequestError {}
+
+impl From<CallbackError> for RequestError {
+    fn from(e: CallbackError) -> Self {
+        Self::Callback(e)
+    }
+}
+
+enum Answer {
+    Waiting(Option<Waker>),
+    Ready(Value),
+    Closed,
+    TimedOut,
+}
+
+struct Slot {
+    answer: Mutex<Answer>,
+    ready: Condvar,
+}
+
+impl Slot {
+    fn resolve(&self, answer: Answer) {
+        let mut current = self
+            .answer
+            .lock()
+            .unwrap_or_else(std::sync::PoisonError::into_inner);
+        if let Answer::Waiting(Some(waker)) = std::mem::replace(&mut *current, answer) {
+            waker.wake();
+        }
+        self.ready.notify_all();
+    }
+}
+
+#[derive(Default)]
+/// Requests waiting for an answer from SQF
+pub(crate) struct Bridge {
+    next_id: AtomicU64,
+    pending: Mutex<HashMap<u64, Arc<Slot>>>,
+}
+
+impl Bridge {
+    pub(crate) fn open(self: &Arc<Self>) -> PendingRequest {
+        let id = self.next_id.fetch_add(1, Ordering::Relaxed);
+        let slot = Arc::new(Slot {
+            answer: Mutex::new(Answer::Waiting(None)),
+            ready: Condvar::new(),
+        });
+        self.pending
+            .lock()
+            .unwrap_or_else(std::sync::PoisonError::into_inner)
+            .insert(id, slot.clone());
+        PendingRequest {
+            id,
+            slot,
+            bridge: self.clone(),
+        }
+    }
+
+    /// Answer a request, returns false if the request is not pending
+    pub(crate) fn reply(&self, id: u64, value: Value) -> bool {
+        let slot = self
+            .pending
+            .lock()
+            .unwrap_or_else(std::sync::PoisonError::into_inner)
+            .remove(&id);
+        slot.map(|slot| slot.resolve(Answer::Ready(value)))
+            .is_some()
+    }
+
+    /// Fail every pending request
+    pub(crate) fn close(&self) {
+        let pending = std::mem::take(
+            &mut *self
+                .pending
+                .lock()
+                .unwrap_or_else(std::sync::PoisonError::into_inner),
+        );
+        for slot in pending.into_values() {
+            slot.resolve(Answer::Closed);
+        }
+    }
+
+    #[cfg(test)]
+    fn pending(&self) -> usize {
+        self.pending
+            .lock()
+            .unwrap_or_else(std::sync::PoisonError::into_inner)
+            .len()
+    }
+
+    fn forget(&self, id: u64) {
+        self.pending
+            .lock()
+            .unwrap_or_else(std::sync::PoisonError::into_inner)
+            .remove(&id);
+    }
+}
+
+/// A request sent to SQF, waiting for its answer.
+///
+/// The request is forgotten when this is dropped, a later answer is rejected.
+/// Can be awaited, or blocked on with [`PendingRequest::wait`].
+/// Awaiting waits forever unless a deadline is set with [`PendingRequest::timeout`].
+pub struct PendingRequest {
+    id: u64,
+    slot: Arc<Slot>,
+    bridge: Arc<Bridge>,
+}
+
+impl PendingRequest {
+    #[must_use]
+    /// Correlation id of the request, the first element of the callback data
+    pub const fn id(&self) -> u64 {
+        self.id
+    }
+
+    #[must_use]
+    /// Fail the request with [`RequestError::Timeout`] if no answer was received within `timeout`.
+    /// The request is forgotten when it expires, a later answer is rejected.
+    pub fn timeout(self, timeout: Duration) -> Self {
+        let deadline = Instant::now() + timeout;
+        let (id, slot, bridge) = (self.id, self.slot.clone(), self.bridge.clone());
+        std::thread::spawn(move || {
+            let answer = slot
+                .answer
+                .lock()
+                .unwrap_or_else(std::sync::PoisonError::into_inner);
+            let remaining = deadline.saturating_duration_since(Instant::now());
+            let (answer, _) = slot
+                .ready
+                .wait_timeout_while(answer, remaining, |answer| {
+                    matches!(answer, Answer::Waiting(_))
+                })
+                .unwrap_or_else(std::sync::PoisonError::into_inner);
+            if matches!(*answer, Answer::Waiting(_)) {
+                drop(answer);
+                bridge.forget(id);
+                slot.resolve(Answer::TimedOut);
+            }
+        });
+        self
+    }
+
+    /// Block until the answer is received and convert it with [`FromArma`].
+    /// # Note
+    /// SQF can not answer while the game is waiting for the extension,
+    /// never wait on the thread that is handling a call from Arma.
+    /// # Errors
+    /// Returns an error if no answer was received before the timeout or the answer could not be converted.
+    pub fn wait<R: FromArma>(self, timeout: Duration) -> Result<R, RequestError> {
+        let deadline = Instant::now() + timeout;
+        let mut answer = self
+            .slot
+            .answer
+            .lock()
+            .unwrap_or_else(std::sync::PoisonError::into_inner);
+        loop {
+            match &*answer {
+                Answer::Ready(value) => {
+                    return R::from_arma(value.to_string()).map_err(RequestError::Decode);
+                }
+                Answer::Closed => return Err(RequestError::Closed),
+                Answer::TimedOut => return Err(RequestError::Timeout),
+                Answer::Waiting(_) => {}
+            }
+            let Some(remaining) = deadline.checked_duration_since(Instant::now()) else {
+                return Err(RequestError::Timeout);
+            };
+            answer = self
+                .slot
+                .ready
+                .wait_timeout(answer, remaining)
+                .unwrap_or_else(std::sync::PoisonError::into_inner)
+                .0;
+        }
+    }
+}
+
+impl Future for PendingRequest {
+    type Output = Result<Value, RequestError>;
+
+    fn poll(self: Pin<&mut Self>, cx: &mut std::task::Context<'_>) -> Poll<Self::Output> {
+        let mut answer = self
+            .slot
+            .answer
+            .lock()
+            .unwrap_or_else(std::sync::PoisonError::into_inner);
+        match &mut *answer {
+            Answer::Ready(value) => Poll::Ready(Ok(value.clone())),
+            Answer::Closed => Poll::Ready(Err(RequestError::Closed)),
+            Answer::TimedOut => Poll::Ready(Err(RequestError::Timeout)),
+            Answer::Waiting(waker) => {
+                *waker = Some(cx.waker().clone());
+                Poll::Pending
+            }
+        }
+    }
+}
+
+impl Drop for PendingRequest {
+    fn drop(&mut self) {
+        self.bridge.forget(self.id);
+        // Stops the thread waiting for the deadline, if any
+        self.slot.resolve(Answer::Closed);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn reply() {
+        let bridge = Arc::new(Bridge::default());
+        let request = bridge.open();
+        let id = request.id();
+        let waiter = std::thread::spawn(move || request.wait::<u32>(Duration::from_secs(5)));
+        std::thread::sleep(Duration::from_millis(10));
+        assert!(bridge.reply(id, Value::Number(42.0)));
+        assert_eq!(waiter.join().unwrap().unwrap(), 42);
+        assert_eq!(bridge.pending(), 0);
+        assert!(!bridge.reply(id, Value::Null));
+    }
+
+    #[test]
+    fn timeout_forgets() {
+        let bridge = Arc::new(Bridge::default());
+        let request = bridge.open();
+        let id = request.id();
+        assert_eq!(bridge.pending(), 1);
+        assert!(matches!(
+            request.wait::<Value>(Duration::from_millis(10)),
+            Err(RequestError::Timeout)
+        ));
+        assert_eq!(bridge.pending(), 0);
+        assert!(!bridge.reply(id, Value::Null));
+    }
+
+    #[test]
+    fn close() {
+        let bridge = Arc::new(Bridge::default());
+        let request = bridge.open();
+        bridge.close();
+        assert!(matches!(
+            request.wait::<Value>(Duration::from_secs(5)),
+            Err(RequestError::Closed)
+        ));
+    }
+
+    #[test]
+    fn future() {
+        struct Wake(std::sync::atomic::AtomicBool);
+        impl std::task::Wake for Wake {
+            fn wake(self: Arc<Self>) {
+                self.0.store(true, Ordering::SeqCst);
+            }
+        }
+
+        let bridge = Arc::new(Bridge::default());
+        let mut request = bridge.open();
+        let wake = Arc::new(Wake(std::sync::atomic::AtomicBool::new(false)));
+        let waker = Waker::from(wake.clone());
+        let mut cx = std::task::Context::from_waker(&waker);
+        assert!(Pin::new(&mut request).poll(&mut cx).is_pending());
+        assert!(bridge.reply(request.id(), Value::Boolean(true)));
+        assert!(wake.0.load(Ordering::SeqCst));
+        assert!(matches!(
+            Pin::new(&mut request).poll(&mut cx),
+            Poll::Ready(Ok(Value::Boolean(true)))
+        ));
+    }
+
+    #[test]
+    fn future_timeout() {
+        struct Wake(std::sync::atomic::AtomicBool);
+        impl std::task::Wake for Wake {
+            fn wake(self: Arc<Self>) {
+                self.0.store(true, Ordering::SeqCst);
+            }
+        }
+
+        let bridge = Arc::new(Bridge::default());
+        let mut request = bridge.open().timeout(Duration::from_millis(20));
+        let id = request.id();
+        let wake = Arc::new(Wake(std::sync::atomic::AtomicBool::new(false)));
+        let waker = Waker::from(wake.clone());
+        let mut cx = std::task::Context::from_waker(&waker);
+        assert!(Pin::new(&mut request).poll(&mut cx).is_pending());
+        let start = Instant::now();
+        while !wake.0.load(Ordering::SeqCst) {
+            assert!(start.elapsed() < Duration::from_secs(5));
+            std::thread::sleep(Duration::from_millis(5));
+        }
+        assert!(matches!(
+            Pin::new(&mut request).poll(&mut cx),
+            Poll::Ready(Err(RequestError::Timeout))
+        ));
+        assert_eq!(bridge.pending(), 0);
+        assert!(!bridge.reply(id, Value::Null));
+    }
+
+    #[test]
+    fn timeout_after_reply() {
+        let bridge = Arc::new(Bridge::default());
+        let request = bridge.open().timeout(Duration::from_millis(20));
+        assert!(bridge.reply(request.id(), Value::Number(1.0)));
+        std::thread::sleep(Duration::from_millis(40));
+        assert_eq!(request.wait::<u32>(Duration::from_secs(5)).unwrap(), 1);
+    }
+}
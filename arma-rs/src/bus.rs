@@ -0,0 +1,99 @@
+//! Typed events published between groups, see [`crate::Group::subscribe`] and [`crate::Context::publish`].
+
+use std::{
+    any::{Any, TypeId},
+    collections::HashMap,
+    sync::Arc,
+};
+
+use crate::{Context, State};
+
+pub(crate) type Handler = Arc<dyn Fn(Context, &(dyn Any + Send + Sync)) + Send + Sync>;
+
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+/// How an event is delivered to a subscriber
+pub enum Delivery {
+    #[default]
+    /// The subscriber runs on the publishing thread, before `publish` returns
+    Sync,
+    /// The subscriber runs on the worker pool, see [`crate::Context::spawn`]
+    Pool,
+}
+
+/// A subscriber added to a group, not yet bound to the group's state
+pub(crate) struct Subscriber {
+    event: TypeId,
+    delivery: Delivery,
+    handler: Handler,
+}
+
+impl Subscriber {
+    pub(crate) fn new<E, F>(delivery: Delivery, handler: F) -> Self
+    where
+        E: Send + Sync + 'static,
+        F: Fn(Context, &E) + Send + Sync + 'static,
+    {
+        Self {
+            event: TypeId::of::<E>(),
+            delivery,
+            handler: Arc::new(move |ctx, event| {
+                if let Some(event) = event.downcast_ref::<E>() {
+                    handler(ctx, event);
+                }
+            }),
+        }
+    }
+}
+
+/// A subscriber with the state of the group it was added to
+pub(crate) struct Registered {
+    pub(crate) delivery: Delivery,
+    pub(crate) handler: Handler,
+    pub(crate) state: Arc<State>,
+}
+
+#[derive(Default)]
+/// Subscribers of every group, by event type
+pub(crate) struct Bus {
+    subscribers: HashMap<TypeId, Vec<Registered>>,
+}
+
+impl Bus {
+    pub(crate) fn register(&mut self, subscriber: Subscriber, state: &Arc<State>) {
+        self.subscribers
+            .entry(subscriber.event)
+            .or_default()
+            .push(Registered {
+                delivery: subscriber.delivery,
+                handler: subscriber.handler,
+                state: state.clone(),
+            });
+    }
+
+    pub(crate) fn subscribers<E: 'static>(&self) -> &[Registered] {
+        self.subscribers
+            .get(&TypeId::of::<E>())
+            .map_or(&[], Vec::as_slice)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn by_type() {
+        let mut bus = Bus::default();
+        let state = Arc::new(State::default());
+        bus.register(Subscriber::new::<u8, _>(Delivery::Sync, |_, _| {}), &state);
+        bus.register(Subscriber::new::<u8, _>(Delivery::Pool, |_, _| {}), &state);
+        bus.register(
+            Subscriber::new::<String, _>(Delivery::Sync, |_, _| {}),
+            &state,
+        );
+        assert_eq!(bus.subscribers::<u8>().len(), 2);
+        assert_eq!(bus.subscribers::<u8>()[1].delivery, Delivery::Pool);
+        assert_eq!(bus.subscribers::<String>().len(), 1);
+        assert!(bus.subscribers::<u16>().is_empty());
+    }
+}
@@ -1,3 +1,4 @@
+use std::borrow::Cow;
 use std::path::Path;
 
 use super::stack::ArmaContextStackTrace;
@@ -13,7 +14,13 @@ struct RawArmaCallContext {
 }
 
 impl RawArmaCallContext {
-    fn from_arma(args: *mut *mut i8, count: libc::c_int) -> Self {
+    fn from_arma(args: *mut *mut i8, count: libc::c_int) -> Result<Self, CallContextError> {
+        if args.is_null() {
+            return Err(CallContextError::NullPointer("context"));
+        }
+        if count < 5 {
+            return Err(CallContextError::MissingValues(count));
+        }
         let steam_id = unsafe { *args.offset(0) as u64 };
         let source = unsafe { *args.offset(1) as *const libc::c_char };
         let mission = unsafe { *args.offset(2) as *const libc::c_char };
@@ -27,23 +34,76 @@ impl RawArmaCallContext {
             None
         };
 
-        Self {
+        Ok(Self {
             steam_id,
             source,
             mission,
             server,
             remote_exec_owner,
             call_stack,
+        })
+    }
+}
+
+/// Read a field of the context, recording the first problem found in `error`.
+///
+/// # Safety
+/// `ptr` must be null or point to a valid C string.
+unsafe fn read_field(
+    ptr: *const libc::c_char,
+    field: &'static str,
+    error: &mut Option<CallContextError>,
+) -> String {
+    if ptr.is_null() {
+        error.get_or_insert(CallContextError::NullPointer(field));
+        return String::new();
+    }
+    match unsafe { crate::read_cstr(ptr) } {
+        Cow::Borrowed(s) => s.to_string(),
+        Cow::Owned(s) => {
+            error.get_or_insert(CallContextError::InvalidUtf8(field));
+            s
         }
     }
 }
 
+/// Problem with the context provided by Arma.
+///
+/// The context is still provided to the command, with the affected values empty or converted lossily.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum CallContextError {
+    /// Arma provided fewer values than expected, with the number of values received
+    MissingValues(libc::c_int),
+    /// A value was a null pointer and was read as empty
+    NullPointer(&'static str),
+    /// A value was not valid UTF-8, invalid sequences were replaced with `U+FFFD`
+    InvalidUtf8(&'static str),
+}
+
+impl std::fmt::Display for CallContextError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Self::MissingValues(count) => {
+                write!(f, "Call context has {count} values, expected at least 5")
+            }
+            Self::NullPointer(field) => write!(f, "Call context {field} is a null pointer"),
+            Self::InvalidUtf8(field) => write!(f, "Call context {field} is not valid UTF-8"),
+        }
+    }
+}
+
+impl std::error::Error for CallContextError {}
+
+/// Stack trace of calls where Arma did not provide one
+static EMPTY_STACK: ArmaContextStackTrace = ArmaContextStackTrace { lines: Vec::new() };
+
 pub trait StackRequest {}
 
-#[derive(Default)]
+#[derive(Clone, Default)]
 pub struct WithStackTrace;
 impl StackRequest for WithStackTrace {}
 
+#[derive(Clone)]
 pub struct WithoutStackTrace;
 impl StackRequest for WithoutStackTrace {}
 
@@ -63,6 +123,7 @@ pub struct ArmaCallContext<T: StackRequest> {
 
     _stack_marker: std::marker::PhantomData<T>,
     stack: Option<ArmaContextStackTrace>,
+    error: Option<CallContextError>,
 }
 
 impl<T: StackRequest> ArmaCallContext<T> {
@@ -82,23 +143,42 @@ impl<T: StackRequest> ArmaCallContext<T> {
 
             _stack_marker: std::marker::PhantomData,
             stack: None,
+            error: None,
         }
     }
 
     /// Create a new ArmaCallContext from pointers provided by Arma.
+    ///
+    /// Never fails, missing or invalid values are left empty or converted lossily and reported by [`Self::error`].
     pub fn from_arma(args: *mut *mut i8, count: libc::c_int) -> Self {
-        let raw = RawArmaCallContext::from_arma(args, count);
+        let raw = match RawArmaCallContext::from_arma(args, count) {
+            Ok(raw) => raw,
+            Err(error) => {
+                let mut context = Self::new(
+                    Caller::default(),
+                    Source::default(),
+                    Mission::default(),
+                    Server::default(),
+                    0,
+                );
+                context.error = Some(error);
+                return context;
+            }
+        };
+        let mut error = None;
+        let source = unsafe { read_field(raw.source, "source", &mut error) };
+        let mission = unsafe { read_field(raw.mission, "mission", &mut error) };
+        let server = unsafe { read_field(raw.server, "server", &mut error) };
         Self {
             caller: Caller::Steam(raw.steam_id),
-            source: Source::from(unsafe { std::ffi::CStr::from_ptr(raw.source).to_str().unwrap() }),
-            mission: Mission::from(unsafe {
-                std::ffi::CStr::from_ptr(raw.mission).to_str().unwrap()
-            }),
-            server: Server::from(unsafe { std::ffi::CStr::from_ptr(raw.server).to_str().unwrap() }),
+            source: Source::from(source.as_str()),
+            mission: Mission::from(mission.as_str()),
+            server: Server::from(server.as_str()),
             remote_exec_owner: raw.remote_exec_owner,
 
             _stack_marker: std::marker::PhantomData,
             stack: raw.call_stack.map(ArmaContextStackTrace::from),
+            error,
         }
     }
 
@@ -135,25 +215,36 @@ impl<T: StackRequest> ArmaCallContext<T> {
     pub const fn remote_exec_owner(&self) -> i16 {
         self.remote_exec_owner
     }
+
+    #[must_use]
+    /// Problem found while reading the context provided by Arma, if any.
+    pub const fn error(&self) -> Option<&CallContextError> {
+        self.error.as_ref()
+    }
 }
 
 impl ArmaCallContext<WithStackTrace> {
     #[must_use]
     /// Call stack of the extension call.
+    /// Empty when Arma did not provide one.
     pub const fn stack_trace(&self) -> &ArmaContextStackTrace {
-        // By the time this gets to consumer code, to_without_stack would've been called if the stack was not requested
-        self.stack.as_ref().expect("Stack is missing")
+        match &self.stack {
+            Some(stack) => stack,
+            None => &EMPTY_STACK,
+        }
     }
 
     /// Convert the context to one without a stack trace.
     pub(crate) fn into_without_stack(self) -> ArmaCallContext<WithoutStackTrace> {
-        ArmaCallContext::new(
+        let mut context = ArmaCallContext::new(
             self.caller,
             self.source,
             self.mission,
             self.server,
             self.remote_exec_owner,
-        )
+        );
+        context.error = self.error;
+        context
     }
 }
 
@@ -247,7 +338,7 @@ impl From<&str> for Source {
 impl From<*const libc::c_char> for Source {
     #[allow(clippy::not_unsafe_ptr_arg_deref)]
     fn from(s: *const libc::c_char) -> Self {
-        Self::from(unsafe { std::ffi::CStr::from_ptr(s).to_str().unwrap() })
+        Self::from(unsafe { crate::read_cstr(s) }.as_ref())
     }
 }
 
@@ -284,7 +375,7 @@ impl From<&str> for Mission {
 impl From<*const libc::c_char> for Mission {
     #[allow(clippy::not_unsafe_ptr_arg_deref)]
     fn from(s: *const libc::c_char) -> Self {
-        Self::from(unsafe { std::ffi::CStr::from_ptr(s).to_str().unwrap() })
+        Self::from(unsafe { crate::read_cstr(s) }.as_ref())
     }
 }
 
@@ -321,7 +412,7 @@ impl From<&str> for Server {
 impl From<*const libc::c_char> for Server {
     #[allow(clippy::not_unsafe_ptr_arg_deref)]
     fn from(s: *const libc::c_char) -> Self {
-        Self::from(unsafe { std::ffi::CStr::from_ptr(s).to_str().unwrap() })
+        Self::from(unsafe { crate::read_cstr(s) }.as_ref())
     }
 }
 
@@ -365,4 +456,55 @@ mod tests {
     fn server_empty() {
         assert_eq!(Server::from(""), Server::Singleplayer);
     }
+
+    fn raw_context(source: &[u8], mission: &[u8], server: &[u8]) -> CallContext {
+        let source = std::ffi::CString::new(source).unwrap();
+        let mission = std::ffi::CString::new(mission).unwrap();
+        let server = std::ffi::CString::new(server).unwrap();
+        let mut args = [
+            76_561_198_000_000_000_u64 as *mut i8,
+            source.as_ptr().cast_mut(),
+            mission.as_ptr().cast_mut(),
+            server.as_ptr().cast_mut(),
+            2 as *mut i8,
+        ];
+        CallContext::from_arma(args.as_mut_ptr(), 5)
+    }
+
+    #[test]
+    fn from_arma_invalid_utf8() {
+        let context = raw_context(b"", b"mission\xff.VR", b"server");
+        assert_eq!(context.caller(), &Caller::Steam(76_561_198_000_000_000));
+        assert_eq!(context.source(), &Source::Console);
+        assert_eq!(
+            context.mission(),
+            &Mission::Mission("mission\u{fffd}.VR".to_string())
+        );
+        assert_eq!(context.server(), &Server::Multiplayer("server".to_string()));
+        assert_eq!(context.remote_exec_owner(), 2);
+        assert_eq!(
+            context.error(),
+            Some(&CallContextError::InvalidUtf8("mission"))
+        );
+        assert_eq!(raw_context(b"", b"", b"").error(), None);
+    }
+
+    #[test]
+    fn from_arma_null() {
+        let context = CallContext::from_arma(std::ptr::null_mut(), 5);
+        assert_eq!(
+            context.error(),
+            Some(&CallContextError::NullPointer("context"))
+        );
+        let mut args = [std::ptr::null_mut(); 5];
+        let context = CallContextStackTrace::from_arma(args.as_mut_ptr(), 3);
+        assert_eq!(context.error(), Some(&CallContextError::MissingValues(3)));
+        assert!(context.stack_trace().lines.is_empty());
+        let context = CallContext::from_arma(args.as_mut_ptr(), 5);
+        assert_eq!(context.source(), &Source::Console);
+        assert_eq!(
+            context.error(),
+            Some(&CallContextError::NullPointer("source"))
+        );
+    }
 }
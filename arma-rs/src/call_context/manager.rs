@@ -1,38 +1,84 @@
-use std::{cell::RefCell, rc::Rc};
+use std::cell::RefCell;
 
 use crate::ContextRequest;
 
 use super::CallContextStackTrace;
 
+thread_local! {
+    /// Context of the call running on this thread.
+    /// Arma provides the context on the thread making the call, so concurrent calls each see their own.
+    static STATE: RefCell<Option<CallContextStackTrace>> = const { RefCell::new(None) };
+}
+
+#[cfg(feature = "tracing")]
+thread_local! {
+    /// Span of the call running on this thread, records the context when it is requested
+    static SPAN: RefCell<Option<tracing::Span>> = const { RefCell::new(None) };
+}
+
+#[cfg(feature = "tracing")]
+/// Makes a span the span of the call running on this thread until it is dropped
+pub(crate) struct CallSpan(Option<tracing::Span>);
+
+#[cfg(feature = "tracing")]
+impl CallSpan {
+    pub(crate) fn new(span: tracing::Span) -> Self {
+        // Calls can be nested, restore the span of the outer call when this one ends
+        Self(SPAN.replace(Some(span)))
+    }
+}
+
+#[cfg(feature = "tracing")]
+impl Drop for CallSpan {
+    fn drop(&mut self) {
+        SPAN.set(self.0.take());
+    }
+}
+
 /// Manages requesting and replacing the `ArmaCallContext`
 pub struct ArmaContextManager {
-    pub(crate) request: RefCell<ContextRequest>,
-    state: Rc<RefCell<Option<CallContextStackTrace>>>,
+    request: ContextRequest,
 }
 
 impl ArmaContextManager {
     /// Create a new `ArmaContextManager`
     pub fn new(request: ContextRequest) -> Self {
-        Self {
-            request: RefCell::new(request),
-            state: Rc::new(RefCell::new(None)),
-        }
+        Self { request }
     }
 
-    /// Request a new `ArmaCallContext` from Arma
+    /// Request a new `ArmaCallContext` from Arma, unless Arma already provided it for this call
     pub fn request(&self) -> CallContextStackTrace {
-        // When the request is called, Arma will send the request to the extension
-        // The extension will set the state to the request it just received
-        unsafe {
-            (self.request.borrow())();
-        }
-        // When the request function returns, the state has been set by Arma
-        // It can now be taken and sent to the Context
-        self.state.replace(None).unwrap_or_default()
+        let context = if let Some(context) = STATE.take() {
+            context
+        } else {
+            // When the request is called, Arma will send the request to the extension
+            // The extension will set the state to the request it just received
+            unsafe {
+                (self.request)();
+            }
+            // When the request function returns, the state has been set by Arma
+            // It can now be taken and sent to the Context
+            STATE.take().unwrap_or_default()
+        };
+        #[cfg(feature = "tracing")]
+        SPAN.with_borrow(|span| {
+            if let Some(span) = span {
+                span.record("caller", context.caller().as_u64());
+                span.record("source", context.source().as_str());
+                span.record("mission", context.mission().as_str());
+                span.record("server", context.server().as_str());
+            }
+        });
+        context
+    }
+
+    /// Look at the `ArmaCallContext` Arma already provided for this call, without requesting it
+    pub(crate) fn peek<R>(&self, f: impl FnOnce(&CallContextStackTrace) -> R) -> Option<R> {
+        STATE.with_borrow(|context| context.as_ref().map(f))
     }
 
-    /// Replace the current `ArmaCallContext` with a new one
+    /// Replace the `ArmaCallContext` of the current thread
     pub fn replace(&self, value: Option<CallContextStackTrace>) {
-        *self.state.borrow_mut() = value;
+        STATE.set(value);
     }
 }
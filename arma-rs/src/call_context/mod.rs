@@ -4,3 +4,5 @@ mod stack;
 
 pub use call::*;
 pub use manager::ArmaContextManager;
+#[cfg(feature = "tracing")]
+pub(crate) use manager::CallSpan;
@@ -1,5 +1,3 @@
-use std::ffi::CStr;
-
 #[repr(C)]
 pub struct RawStackTraceLine {
     // Line number in file (before preprocessing if preprocessed with line numbers)
@@ -45,28 +43,23 @@ pub struct ArmaStackTraceLine {
 }
 
 impl From<*const RawContextStackTrace> for ArmaContextStackTrace {
+    /// Null pointers are read as an empty stack or empty strings, invalid UTF-8 is replaced with `U+FFFD`
     fn from(raw: *const RawContextStackTrace) -> Self {
-        unsafe {
-            let raw = raw.as_ref().unwrap();
-            let lines = raw
-                .to_lines()
-                .unwrap()
-                .iter()
-                .map(|line| ArmaStackTraceLine {
-                    line_number: line.line_number,
-                    file_offset: line.file_offset,
-                    source_file: CStr::from_ptr(line.source_file)
-                        .to_string_lossy()
-                        .into_owned(),
-                    scope_name: CStr::from_ptr(line.scope_name)
-                        .to_string_lossy()
-                        .into_owned(),
-                    file_content: CStr::from_ptr(line.file_content)
-                        .to_string_lossy()
-                        .into_owned(),
-                })
-                .collect();
-            Self { lines }
-        }
+        let Some(raw) = (unsafe { raw.as_ref() }) else {
+            return Self { lines: Vec::new() };
+        };
+        let lines = raw
+            .to_lines()
+            .unwrap_or_default()
+            .iter()
+            .map(|line| ArmaStackTraceLine {
+                line_number: line.line_number,
+                file_offset: line.file_offset,
+                source_file: unsafe { crate::read_cstr(line.source_file) }.into_owned(),
+                scope_name: unsafe { crate::read_cstr(line.scope_name) }.into_owned(),
+                file_content: unsafe { crate::read_cstr(line.file_content) }.into_owned(),
+            })
+            .collect();
+        Self { lines }
     }
 }
@@ -0,0 +1,541 @@
+//! Queueing and delivery of callbacks to Arma.
+
+use std::{
+    cell::Cell,
+    sync::{
+        Arc,
+        atomic::{AtomicBool, AtomicU64, Ordering},
+    },
+    time::{Duration, Instant},
+};
+
+use crossbeam_channel::{Receiver, SendTimeoutError, Sender, TrySendError, bounded, unbounded};
+
+use crate::{CallbackError, CallbackMessage, Value};
+
+/// Default maximum size in bytes of the data of a single callback
+pub const DEFAULT_CHUNK_SIZE: usize = 10 * 1024;
+
+/// Function name of the fragments of a callback that was split because its data was too large.
+/// The data of each fragment is `[id, index, total, function, data]`.
+pub const CHUNK_FUNCTION: &str = "::chunk";
+
+/// Reference SQF function to reassemble callbacks split into fragments.
+///
+/// Call it from the `ExtensionCallback` handler with the handler's parameters.
+/// Returns `[function, data]` once the callback is complete, or `nil` while fragments are missing.
+/// ```sqf
+/// addMissionEventHandler ["ExtensionCallback", {
+///     private _callback = _this call arma_rs_fnc_reassemble;
+///     if (isNil "_callback") exitWith {};
+///     _callback params ["_function", "_data"];
+/// }];
+/// ```
+pub const REASSEMBLE_SQF: &str = r#"params ["_name", "_function", "_data"];
+if (_function != "::chunk") exitWith { [_function, _data] };
+(parseSimpleArray _data) params ["_id", "_index", "_total", "_original", "_piece"];
+if (isNil "arma_rs_chunks") then { arma_rs_chunks = createHashMap };
+private _key = _name + ":" + _id;
+private _pieces = arma_rs_chunks getOrDefault [_key, [], true];
+_pieces set [_index, _piece];
+if ({ !isNil "_x" } count _pieces < _total) exitWith { nil };
+arma_rs_chunks deleteAt _key;
+[_original, _pieces joinString ""]
+"#;
+
+/// What happens when a callback is sent while the queue is full
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub enum OverflowPolicy {
+    /// Discard the oldest queued callback to make room
+    DropOldest,
+    /// Reject the new callback with [`CallbackError::QueueFull`]
+    DropNewest,
+    #[default]
+    /// Wait until there is room in the queue.
+    /// Behaves like [`OverflowPolicy::DropNewest`] while no thread is sending callbacks to Arma,
+    /// before the callback is registered and after the extension has shut down.
+    Block,
+}
+
+/// Configuration for the callback queue, see [`crate::ExtensionBuilder::callback_queue`]
+#[derive(Debug, Clone)]
+pub struct CallbackConfig {
+    capacity: Option<usize>,
+    policy: OverflowPolicy,
+    initial_backoff: Duration,
+    max_backoff: Duration,
+    max_retry: Option<Duration>,
+    chunk_size: usize,
+}
+
+impl Default for CallbackConfig {
+    fn default() -> Self {
+        Self {
+            capacity: None,
+            policy: OverflowPolicy::default(),
+            initial_backoff: Duration::from_millis(1),
+            max_backoff: Duration::from_millis(100),
+            max_retry: None,
+            chunk_size: DEFAULT_CHUNK_SIZE,
+        }
+    }
+}
+
+impl CallbackConfig {
+    #[must_use]
+    /// Create a new configuration with an unbounded queue that retries callbacks until Arma accepts them
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    #[must_use]
+    /// Limit the number of queued callbacks, at least 1
+    pub fn capacity(mut self, capacity: usize) -> Self {
+        self.capacity = Some(capacity.max(1));
+        self
+    }
+
+    #[must_use]
+    /// Sets what happens when the queue is full
+    pub const fn policy(mut self, policy: OverflowPolicy) -> Self {
+        self.policy = policy;
+        self
+    }
+
+    #[must_use]
+    /// Sets the delay before the first retry when Arma's callback queue is full.
+    /// The delay doubles with every retry up to `max`.
+    pub const fn backoff(mut self, initial: Duration, max: Duration) -> Self {
+        self.initial_backoff = initial;
+        self.max_backoff = max;
+        self
+    }
+
+    #[must_use]
+    /// Drop a callback if Arma has not accepted it after retrying for `duration`
+    pub const fn max_retry(mut self, duration: Duration) -> Self {
+        self.max_retry = Some(duration);
+        self
+    }
+
+    #[must_use]
+    /// Sets the maximum size in bytes of the data of a single callback.
+    /// Larger data is split into fragments sent with the function [`CHUNK_FUNCTION`],
+    /// see [`REASSEMBLE_SQF`] to reassemble them in Arma.
+    pub const fn chunk_size(mut self, size: usize) -> Self {
+        self.chunk_size = size;
+        self
+    }
+
+    pub(crate) fn channel(&self) -> (Sender<CallbackMessage>, Receiver<CallbackMessage>) {
+        self.capacity.map_or_else(unbounded, bounded)
+    }
+}
+
+thread_local! {
+    static DELIVERY_THREAD: Cell<bool> = const { Cell::new(false) };
+}
+
+/// Mark the current thread as the one sending callbacks to Arma
+pub(crate) fn mark_delivery_thread() {
+    DELIVERY_THREAD.with(|d| d.set(true));
+}
+
+/// Whether the current thread is the one sending callbacks to Arma
+pub(crate) fn on_delivery_thread() -> bool {
+    DELIVERY_THREAD.with(Cell::get)
+}
+
+/// How long a blocked sender waits before checking if callbacks are still being sent
+const BLOCK_POLL: Duration = Duration::from_millis(100);
+
+#[derive(Default)]
+struct Counters {
+    dropped: AtomicU64,
+    retried: AtomicU64,
+    next_chunk_id: AtomicU64,
+}
+
+/// Snapshot of the callback counters, see [`crate::Extension::callback_stats`]
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct CallbackStats {
+    queued: usize,
+    dropped: u64,
+    retried: u64,
+}
+
+impl CallbackStats {
+    #[must_use]
+    /// Number of callbacks waiting to be sent
+    pub const fn queued(&self) -> usize {
+        self.queued
+    }
+
+    #[must_use]
+    /// Number of callbacks dropped by the overflow policy or after retrying for too long
+    pub const fn dropped(&self) -> u64 {
+        self.dropped
+    }
+
+    #[must_use]
+    /// Number of callbacks that Arma did not accept on the first attempt
+    pub const fn retried(&self) -> u64 {
+        self.retried
+    }
+}
+
+#[derive(Clone)]
+/// Sending side of the callback queue, applying the overflow policy
+pub(crate) struct CallbackQueue {
+    tx: Sender<CallbackMessage>,
+    // Only kept to discard the oldest message when the queue is full
+    oldest: Option<Receiver<CallbackMessage>>,
+    config: Arc<CallbackConfig>,
+    counters: Arc<Counters>,
+    // Set while a thread is sending callbacks to Arma, senders only block while it is
+    running: Arc<AtomicBool>,
+}
+
+impl CallbackQueue {
+    pub(crate) fn new(
+        config: CallbackConfig,
+        (tx, rx): &(Sender<CallbackMessage>, Receiver<CallbackMessage>),
+    ) -> Self {
+        Self {
+            tx: tx.clone(),
+            oldest: (config.policy == OverflowPolicy::DropOldest).then(|| rx.clone()),
+            config: Arc::new(config),
+            counters: Arc::default(),
+            running: Arc::default(),
+        }
+    }
+
+    /// Mark whether a thread is sending callbacks to Arma
+    pub(crate) fn set_running(&self, running: bool) {
+        self.running.store(running, Ordering::SeqCst);
+    }
+
+    pub(crate) fn send(&self, message: CallbackMessage) -> Result<(), CallbackError> {
+        self.send_with(message, true)
+    }
+
+    /// Send without ever waiting, [`OverflowPolicy::Block`] drops the new callback instead
+    pub(crate) fn try_send(&self, message: CallbackMessage) -> Result<(), CallbackError> {
+        self.send_with(message, false)
+    }
+
+    fn send_with(&self, message: CallbackMessage, wait: bool) -> Result<(), CallbackError> {
+        let mut message = message;
+        loop {
+            match self.tx.try_send(message) {
+                Ok(()) => return Ok(()),
+                Err(TrySendError::Disconnected(_)) => return Err(CallbackError::ChannelClosed),
+                Err(TrySendError::Full(full)) => match (self.config.policy, &self.oldest) {
+                    (OverflowPolicy::DropOldest, Some(rx)) => match rx.try_recv() {
+                        Ok(CallbackMessage::Terminate) => {
+                            // Keep the shutdown queued, the new callback is dropped instead
+                            let _ = self.tx.send(CallbackMessage::Terminate);
+                            return self.drop_newest();
+                        }
+                        Ok(CallbackMessage::Call(..)) => {
+                            self.counters.dropped.fetch_add(1, Ordering::Relaxed);
+                            message = full;
+                        }
+                        Err(_) => message = full,
+                    },
+                    (OverflowPolicy::Block, _) if wait => return self.block(full),
+                    _ => return self.drop_newest(),
+                },
+            }
+        }
+    }
+
+    fn drop_newest(&self) -> Result<(), CallbackError> {
+        self.counters.dropped.fetch_add(1, Ordering::Relaxed);
+        Err(CallbackError::QueueFull)
+    }
+
+    /// Wait for room in the queue while a thread is sending callbacks, nothing would make room otherwise
+    fn block(&self, message: CallbackMessage) -> Result<(), CallbackError> {
+        let mut message = message;
+        while self.running.load(Ordering::SeqCst) {
+            match self.tx.send_timeout(message, BLOCK_POLL) {
+                Ok(()) => return Ok(()),
+                Err(SendTimeoutError::Disconnected(_)) => return Err(CallbackError::ChannelClosed),
+                Err(SendTimeoutError::Timeout(timeout)) => message = timeout,
+            }
+        }
+        self.drop_newest()
+    }
+
+    /// Call `attempt` until it succeeds, backing off between attempts.
+    /// Returns false if the callback was dropped after retrying for too long.
+    pub(crate) fn deliver(&self, mut attempt: impl FnMut() -> bool) -> bool {
+        if attempt() {
+            return true;
+        }
+        self.counters.retried.fetch_add(1, Ordering::Relaxed);
+        let start = Instant::now();
+        let mut delay = self.config.initial_backoff;
+        loop {
+            if self
+                .config
+                .max_retry
+                .is_some_and(|max| start.elapsed() + delay > max)
+            {
+                self.counters.dropped.fetch_add(1, Ordering::Relaxed);
+                return false;
+            }
+            std::thread::sleep(delay);
+            if attempt() {
+                return true;
+            }
+            delay = (delay * 2).min(self.config.max_backoff);
+        }
+    }
+
+    pub(crate) fn chunk_size(&self) -> usize {
+        self.config.chunk_size
+    }
+
+    /// Split the data into fragments no larger than the chunk size.
+    /// Returns the function and data unchanged if it already fits.
+    pub(crate) fn split(&self, func: String, data: String) -> Vec<(String, String)> {
+        if data.len() <= self.config.chunk_size {
+            return vec![(func, data)];
+        }
+        let id = self
+            .counters
+            .next_chunk_id
+            .fetch_add(1, Ordering::Relaxed)
+            .to_string();
+        let fragment = |index: usize, total: usize, piece: String| {
+            Value::Array(vec![
+                Value::String(id.clone()),
+                Value::Number(index as f64),
+                Value::Number(total as f64),
+                Value::String(func.clone()),
+                Value::String(piece),
+            ])
+            .to_string()
+        };
+        // Index and total never have more digits than the length of the data
+        let widest = data.len();
+        let header = fragment(widest, widest, String::new()).len();
+        // Always leave room for at least one escaped character
+        let budget = self.config.chunk_size.saturating_sub(header).max(4);
+
+        let mut pieces = Vec::new();
+        let mut piece = String::new();
+        let mut size = 0;
+        for c in data.chars() {
+            let len = if c == '"' { 2 } else { c.len_utf8() };
+            if size + len > budget {
+                pieces.push(std::mem::take(&mut piece));
+                size = 0;
+            }
+            piece.push(c);
+            size += len;
+        }
+        pieces.push(piece);
+
+        let total = pieces.len();
+        pieces
+            .into_iter()
+            .enumerate()
+            .map(|(index, piece)| (CHUNK_FUNCTION.to_string(), fragment(index, total, piece)))
+            .collect()
+    }
+
+    pub(crate) fn stats(&self) -> CallbackStats {
+        CallbackStats {
+            queued: self.tx.len(),
+            dropped: self.counters.dropped.load(Ordering::Relaxed),
+            retried: self.counters.retried.load(Ordering::Relaxed),
+        }
+    }
+}
+
+impl From<Sender<CallbackMessage>> for CallbackQueue {
+    fn from(tx: Sender<CallbackMessage>) -> Self {
+        Self {
+            tx,
+            oldest: None,
+            config: Arc::default(),
+            counters: Arc::default(),
+            // The owner of the receiver is expected to be reading from it
+            running: Arc::new(AtomicBool::new(true)),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn call(func: &str) -> CallbackMessage {
+        CallbackMessage::Call(String::new(), func.to_string(), None)
+    }
+
+    fn funcs(rx: &Receiver<CallbackMessage>) -> Vec<String> {
+        rx.try_iter()
+            .filter_map(|message| match message {
+                CallbackMessage::Call(_, func, _) => Some(func),
+                CallbackMessage::Terminate => None,
+            })
+            .collect()
+    }
+
+    #[test]
+    fn split_small() {
+        let queue = CallbackQueue::from(unbounded().0);
+        assert_eq!(
+            queue.split(String::from("func"), String::from("data")),
+            vec![(String::from("func"), String::from("data"))]
+        );
+    }
+
+    #[test]
+    fn split_large() {
+        let config = CallbackConfig::new().chunk_size(64);
+        let queue = CallbackQueue::new(config.clone(), &config.channel());
+        let data = format!("[{}]", r#""quoted ""value"" ü","#.repeat(20));
+        let fragments = queue.split(String::from("func"), data.clone());
+        assert!(fragments.len() > 1);
+
+        let mut joined = String::new();
+        for (index, (func, fragment)) in fragments.iter().enumerate() {
+            assert_eq!(func, CHUNK_FUNCTION);
+            assert!(fragment.len() <= 64, "{fragment}");
+            let Value::Array(parts) =
+                <Value as crate::FromArma>::from_arma(fragment.clone()).unwrap()
+            else {
+                panic!("fragment is not an array");
+            };
+            assert_eq!(parts[0], Value::String(String::from("0")));
+            assert_eq!(parts[1], Value::Number(index as f64));
+            assert_eq!(parts[2], Value::Number(fragments.len() as f64));
+            assert_eq!(parts[3], Value::String(String::from("func")));
+            let Value::String(piece) = &parts[4] else {
+                panic!("piece is not a string");
+            };
+            joined.push_str(piece);
+        }
+        assert_eq!(joined, data);
+
+        // Every split callback gets a new id
+        let fragments = queue.split(String::from("func"), data);
+        assert!(fragments[0].1.starts_with(r#"["1","#));
+    }
+
+    #[test]
+    fn drop_oldest() {
+        let config = CallbackConfig::new()
+            .capacity(2)
+            .policy(OverflowPolicy::DropOldest);
+        let channel = config.channel();
+        let queue = CallbackQueue::new(config, &channel);
+        for func in ["a", "b", "c"] {
+            queue.send(call(func)).unwrap();
+        }
+        assert_eq!(funcs(&channel.1), vec!["b", "c"]);
+        assert_eq!(queue.stats().dropped(), 1);
+    }
+
+    #[test]
+    fn drop_newest() {
+        let config = CallbackConfig::new()
+            .capacity(2)
+            .policy(OverflowPolicy::DropNewest);
+        let channel = config.channel();
+        let queue = CallbackQueue::new(config, &channel);
+        queue.send(call("a")).unwrap();
+        queue.send(call("b")).unwrap();
+        assert!(matches!(
+            queue.send(call("c")),
+            Err(CallbackError::QueueFull)
+        ));
+        assert_eq!(queue.stats().queued(), 2);
+        assert_eq!(funcs(&channel.1), vec!["a", "b"]);
+        assert_eq!(queue.stats().dropped(), 1);
+    }
+
+    #[test]
+    fn drop_oldest_keeps_terminate() {
+        let config = CallbackConfig::new()
+            .capacity(1)
+            .policy(OverflowPolicy::DropOldest);
+        let channel = config.channel();
+        let queue = CallbackQueue::new(config, &channel);
+        channel.0.send(CallbackMessage::Terminate).unwrap();
+        assert!(matches!(
+            queue.send(call("a")),
+            Err(CallbackError::QueueFull)
+        ));
+        assert!(matches!(
+            channel.1.try_recv(),
+            Ok(CallbackMessage::Terminate)
+        ));
+        assert_eq!(queue.stats().dropped(), 1);
+    }
+
+    #[test]
+    fn block_without_thread() {
+        let config = CallbackConfig::new().capacity(1);
+        let channel = config.channel();
+        let queue = CallbackQueue::new(config, &channel);
+        queue.send(call("a")).unwrap();
+        assert!(matches!(
+            queue.send(call("b")),
+            Err(CallbackError::QueueFull)
+        ));
+        assert_eq!(funcs(&channel.1), vec!["a"]);
+        assert_eq!(queue.stats().dropped(), 1);
+    }
+
+    #[test]
+    fn block() {
+        let config = CallbackConfig::new().capacity(1);
+        let channel = config.channel();
+        let queue = CallbackQueue::new(config, &channel);
+        queue.set_running(true);
+        queue.send(call("a")).unwrap();
+        let sender = {
+            let queue = queue.clone();
+            std::thread::spawn(move || queue.send(call("b")))
+        };
+        std::thread::sleep(Duration::from_millis(50));
+        assert!(!sender.is_finished());
+        assert_eq!(funcs(&channel.1), vec!["a"]);
+        sender.join().unwrap().unwrap();
+        assert_eq!(funcs(&channel.1), vec!["b"]);
+    }
+
+    #[test]
+    fn deliver_backoff() {
+        let config =
+            CallbackConfig::new().backoff(Duration::from_millis(1), Duration::from_millis(4));
+        let queue = CallbackQueue::new(config.clone(), &config.channel());
+        let mut attempts = 0;
+        assert!(queue.deliver(|| {
+            attempts += 1;
+            attempts == 4
+        }));
+        assert!(queue.deliver(|| true));
+        assert_eq!(queue.stats().retried(), 1);
+        assert_eq!(queue.stats().dropped(), 0);
+    }
+
+    #[test]
+    fn deliver_max_retry() {
+        let config = CallbackConfig::new()
+            .backoff(Duration::from_millis(1), Duration::from_millis(2))
+            .max_retry(Duration::from_millis(20));
+        let queue = CallbackQueue::new(config.clone(), &config.channel());
+        let start = Instant::now();
+        assert!(!queue.deliver(|| false));
+        assert!(start.elapsed() < Duration::from_secs(1));
+        assert_eq!(queue.stats().retried(), 1);
+        assert_eq!(queue.stats().dropped(), 1);
+    }
+}
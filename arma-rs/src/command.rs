@@ -1,18 +1,22 @@
+use std::borrow::Cow;
+
 use crate::Context;
 use crate::call_context::{ArmaContextManager, CallContext, CallContextStackTrace};
 use crate::ext_result::IntoExtResult;
 use crate::flags::FeatureFlags;
-use crate::value::{FromArma, Value};
+use crate::value::{FromArma, FromArmaError, Value};
 
+/// A command handler, shared by every thread calling the extension
 type HandlerFunc = Box<
     dyn Fn(
-        Context,
-        &ArmaContextManager,
-        *mut libc::c_char,
-        libc::size_t,
-        Option<*mut *mut i8>,
-        Option<libc::c_int>,
-    ) -> libc::c_int,
+            Context,
+            &ArmaContextManager,
+            *mut libc::c_char,
+            libc::size_t,
+            Arguments<'_>,
+        ) -> libc::c_int
+        + Send
+        + Sync,
 >;
 
 #[doc(hidden)]
@@ -20,24 +24,26 @@ type HandlerFunc = Box<
 pub struct Handler {
     /// The function to call
     pub handler: HandlerFunc,
+    /// The feature flags the function needs
+    pub flags: FeatureFlags,
 }
 
 #[doc(hidden)]
 /// Create a new handler from a Factory
 pub fn fn_handler<C, I, R>(command: C) -> Handler
 where
-    C: Factory<I, R> + 'static,
+    C: Factory<I, R> + Send + Sync + 'static,
 {
     Handler {
+        flags: command.feature_flags(),
         handler: Box::new(
             move |context: Context,
                   acm: &ArmaContextManager,
                   output: *mut libc::c_char,
                   size: libc::size_t,
-                  args: Option<*mut *mut i8>,
-                  count: Option<libc::c_int>|
+                  args: Arguments<'_>|
                   -> libc::c_int {
-                unsafe { command.call(context, acm, output, size, args, count) }
+                unsafe { command.call(context, acm, output, size, args) }
             },
         ),
     }
@@ -54,8 +60,7 @@ pub trait Executor: 'static {
         acm: &ArmaContextManager,
         output: *mut libc::c_char,
         size: libc::size_t,
-        args: Option<*mut *mut i8>,
-        count: Option<libc::c_int>,
+        args: Arguments<'_>,
     );
 }
 
@@ -73,56 +78,119 @@ pub trait Factory<A, R> {
         acm: &ArmaContextManager,
         output: *mut libc::c_char,
         size: libc::size_t,
-        args: Option<*mut *mut i8>,
-        count: Option<libc::c_int>,
+        args: Arguments<'_>,
     ) -> libc::c_int;
+
+    /// The feature flags the command needs from Arma
+    fn feature_flags(&self) -> FeatureFlags {
+        FeatureFlags::new()
+    }
+}
+
+/// Prefix digit of a code returned by [`error_code`], such as `3` for `310`
+pub(crate) const fn error_prefix(code: libc::c_int) -> libc::c_int {
+    let mut code = code;
+    while code >= 10 {
+        code /= 10;
+    }
+    code
+}
+
+/// Result code made of a prefix digit followed by the digits of `n`, such as `23` for a call with 3 arguments.
+/// Returns only the prefix if `n` is negative or the code does not fit.
+pub(crate) const fn error_code(prefix: libc::c_int, n: libc::c_int) -> libc::c_int {
+    if n < 0 {
+        return prefix;
+    }
+    let mut scale: i64 = 10;
+    while scale <= n as i64 {
+        scale *= 10;
+    }
+    let code = prefix as i64 * scale + n as i64;
+    if code > libc::c_int::MAX as i64 {
+        prefix
+    } else {
+        code as libc::c_int
+    }
+}
+
+#[doc(hidden)]
+#[derive(Clone, Copy)]
+/// Arguments of a call, stringified SQF values as passed to `RVExtensionArgs`
+pub struct Arguments<'a>(&'a [*mut libc::c_char]);
+
+impl Arguments<'_> {
+    /// Arguments passed by Arma, empty when the call has none
+    ///
+    /// # Safety
+    /// `args` must hold at least `count` valid C strings that outlive the returned value.
+    pub(crate) unsafe fn from_raw(
+        args: Option<*mut *mut libc::c_char>,
+        count: Option<libc::c_int>,
+    ) -> Self {
+        match (args, count.and_then(|c| usize::try_from(c).ok())) {
+            (Some(args), Some(count)) if !args.is_null() && count > 0 => {
+                Self(unsafe { std::slice::from_raw_parts(args, count) })
+            }
+            _ => Self(&[]),
+        }
+    }
+
+    /// Number of arguments
+    pub(crate) const fn len(&self) -> usize {
+        self.0.len()
+    }
+
+    /// Number of arguments, as reported in error codes
+    pub(crate) fn count(&self) -> libc::c_int {
+        libc::c_int::try_from(self.len()).unwrap_or(-1)
+    }
+
+    /// SQF representation of argument `index`, borrowed from Arma's buffer when it is valid UTF-8
+    ///
+    /// A null argument is read as an empty string, which fails to parse as any value.
+    ///
+    /// # Safety
+    /// The strings must be null or valid C strings.
+    pub(crate) unsafe fn sqf(&self, index: usize) -> Cow<'_, str> {
+        self.0
+            .get(index)
+            .map_or(Cow::Borrowed(""), |arg| unsafe { crate::read_cstr(*arg) })
+    }
+
+    /// Convert argument `index`
+    ///
+    /// # Safety
+    /// The strings must be valid C strings.
+    unsafe fn parse<T: FromArma>(&self, index: libc::c_int) -> Result<T, FromArmaError> {
+        let index = usize::try_from(index).unwrap_or(usize::MAX);
+        T::from_arma_str(&unsafe { self.sqf(index) })
+    }
 }
 
 macro_rules! execute {
-    ($s:ident, $c:expr, $count:expr, $output:expr, $size:expr, $args:expr, ($( $vars:ident )*), ($( $param:ident, )*)) => {{
-        let count = $count.unwrap_or_else(|| 0);
-        if count != $c {
-            return format!("2{}", count).parse::<libc::c_int>().unwrap();
+    ($s:ident, $c:expr, $output:expr, $size:expr, $args:expr, ($( $vars:ident )*), ($( $param:ident, )*)) => {{ unsafe {
+        #[allow(unused_variables)] // Caused by the 0 loop
+        let args: Arguments = $args;
+        if args.len() != $c {
+            return error_code(2, args.count());
         }
-        if $c == 0 {
-            handle_output_and_return(
-                ($s)($( $vars, )* $($param::from_arma("".to_string()).unwrap(),)*),
-                $output,
-                $size
-            )
-        } else {
-            #[allow(unused_variables, unused_mut)]
-            let mut argv: Vec<String> = {
-                let argv: &[*mut libc::c_char; $c] = &*($args.unwrap() as *const [*mut i8; $c]);
-                let mut argv = argv
-                    .to_vec()
-                    .into_iter()
-                    .map(|s| {
-                        std::ffi::CStr::from_ptr(s).to_string_lossy().to_string()
-                    })
-                    .collect::<Vec<String>>();
-                argv.reverse();
-                argv
-            };
-            #[allow(unused_variables, unused_mut)] // Caused by the 0 loop
-            let mut c = 0;
-            #[allow(unused_assignments, clippy::mixed_read_write_in_expression)]
-            handle_output_and_return(
-                {
-                    ($s)($( $vars, )* $(
-                        if let Ok(val) = $param::from_arma(argv.pop().unwrap()) {
-                            c += 1;
-                            val
-                        } else {
-                            return format!("3{}", c).parse::<libc::c_int>().unwrap()
-                        },
-                    )*)
+        #[allow(unused_variables, unused_mut)]
+        let mut c = 0;
+        #[allow(unused_assignments, clippy::mixed_read_write_in_expression)]
+        handle_output_and_return(
+            ($s)($( $vars, )* $(
+                if let Ok(val) = args.parse::<$param>(c) {
+                    c += 1;
+                    val
+                } else {
+                    return error_code(3, c);
                 },
-                $output,
-                $size
-            )
-        }
-    }};
+            )*),
+            $output,
+            $size
+        )
+    }}};
 }
 
 macro_rules! factory_tuple ({ $c: expr, $($param:ident)* } => {
@@ -137,10 +205,9 @@ macro_rules! factory_tuple ({ $c: expr, $($param:ident)* } => {
             acm: &ArmaContextManager,
             output: *mut libc::c_char,
             size: libc::size_t,
-            args: Option<*mut *mut i8>,
-            count: Option<libc::c_int>,
+            args: Arguments<'_>,
         ) {
-            self.call(context, acm, output, size, args, count);
+            unsafe { self.call(context, acm, output, size, args) };
         }
     }
 
@@ -152,49 +219,8 @@ macro_rules! factory_tuple ({ $c: expr, $($param:ident)* } => {
         $($param: FromArma,)*
     {
         #[allow(non_snake_case)]
-        unsafe fn call(&self, _: Context, _: &ArmaContextManager, output: *mut libc::c_char, size: libc::size_t, args: Option<*mut *mut i8>, count: Option<libc::c_int>) -> libc::c_int {
-            let count = count.unwrap_or_else(|| 0);
-            if count != $c {
-                return format!("2{}", count).parse::<libc::c_int>().unwrap();
-            }
-            if $c == 0 {
-                handle_output_and_return(
-                    (self)($($param::from_arma("".to_string()).unwrap(),)*),
-                    output,
-                    size
-                )
-            } else {
-                #[allow(unused_variables, unused_mut)]
-                let mut argv: Vec<String> = {
-                    let argv: &[*mut libc::c_char; $c] = &*(args.unwrap() as *const [*mut i8; $c]);
-                    let mut argv = argv
-                        .to_vec()
-                        .into_iter()
-                        .map(|s| {
-                            std::ffi::CStr::from_ptr(s).to_string_lossy().to_string()
-                        })
-                        .collect::<Vec<String>>();
-                    argv.reverse();
-                    argv
-                };
-                #[allow(unused_variables, unused_mut)] // Caused by the 0 loop
-                let mut c = 0;
-                #[allow(unused_assignments, clippy::mixed_read_write_in_expression)]
-                handle_output_and_return(
-                    {
-                        (self)($(
-                            if let Ok(val) = $param::from_arma(argv.pop().unwrap()) {
-                                c += 1;
-                                val
-                            } else {
-                                return format!("3{}", c).parse::<libc::c_int>().unwrap()
-                            },
-                        )*)
-                    },
-                    output,
-                    size
-                )
-            }
+        unsafe fn call(&self, _: Context, _: &ArmaContextManager, output: *mut libc::c_char, size: libc::size_t, args: Arguments<'_>) -> libc::c_int {
+            execute!(self, $c, output, size, args, (), ($($param,)*))
         }
     }
 
@@ -206,8 +232,8 @@ macro_rules! factory_tuple ({ $c: expr, $($param:ident)* } => {
         $($param: FromArma,)*
     {
         #[allow(non_snake_case)]
-        unsafe fn call(&self, context: Context, _: &ArmaContextManager, output: *mut libc::c_char, size: libc::size_t, args: Option<*mut *mut i8>, count: Option<libc::c_int>) -> libc::c_int {
-            execute!(self, $c, count, output, size, args, (context), ($($param,)*))
+        unsafe fn call(&self, context: Context, _: &ArmaContextManager, output: *mut libc::c_char, size: libc::size_t, args: Arguments<'_>) -> libc::c_int {
+            execute!(self, $c, output, size, args, (context), ($($param,)*))
         }
     }
 
@@ -219,10 +245,9 @@ macro_rules! factory_tuple ({ $c: expr, $($param:ident)* } => {
         $($param: FromArma,)*
     {
         #[allow(non_snake_case)]
-        unsafe fn call(&self, _: Context, acm: &ArmaContextManager, output: *mut libc::c_char, size: libc::size_t, args: Option<*mut *mut i8>, count: Option<libc::c_int>) -> libc::c_int {
-            crate::RVExtensionFeatureFlags = FeatureFlags::default().with_context_stack_trace(false).as_bits();
+        unsafe fn call(&self, _: Context, acm: &ArmaContextManager, output: *mut libc::c_char, size: libc::size_t, args: Arguments<'_>) -> libc::c_int {
             let call_context = acm.request().into_without_stack();
-            execute!(self, $c, count, output, size, args, (call_context), ($($param,)*))
+            execute!(self, $c, output, size, args, (call_context), ($($param,)*))
         }
     }
 
@@ -234,10 +259,13 @@ macro_rules! factory_tuple ({ $c: expr, $($param:ident)* } => {
         $($param: FromArma,)*
     {
         #[allow(non_snake_case)]
-        unsafe fn call(&self, _: Context, acm: &ArmaContextManager, output: *mut libc::c_char, size: libc::size_t, args: Option<*mut *mut i8>, count: Option<libc::c_int>) -> libc::c_int {
-            crate::RVExtensionFeatureFlags = FeatureFlags::default().with_context_stack_trace(true).as_bits();
+        unsafe fn call(&self, _: Context, acm: &ArmaContextManager, output: *mut libc::c_char, size: libc::size_t, args: Arguments<'_>) -> libc::c_int {
             let call_context = acm.request();
-            execute!(self, $c, count, output, size, args, (call_context), ($($param,)*))
+            execute!(self, $c, output, size, args, (call_context), ($($param,)*))
+        }
+
+        fn feature_flags(&self) -> FeatureFlags {
+            FeatureFlags::new().with_context_stack_trace(true)
         }
     }
 
@@ -249,10 +277,9 @@ macro_rules! factory_tuple ({ $c: expr, $($param:ident)* } => {
         $($param: FromArma,)*
     {
         #[allow(non_snake_case)]
-        unsafe fn call(&self, context: Context, acm: &ArmaContextManager, output: *mut libc::c_char, size: libc::size_t, args: Option<*mut *mut i8>, count: Option<libc::c_int>) -> libc::c_int {
-            crate::RVExtensionFeatureFlags = FeatureFlags::default().with_context_stack_trace(false).as_bits();
+        unsafe fn call(&self, context: Context, acm: &ArmaContextManager, output: *mut libc::c_char, size: libc::size_t, args: Arguments<'_>) -> libc::c_int {
             let call_context = acm.request().into_without_stack();
-            execute!(self, $c, count, output, size, args, (context call_context), ($($param,)*))
+            execute!(self, $c, output, size, args, (context call_context), ($($param,)*))
         }
     }
 
@@ -264,10 +291,13 @@ macro_rules! factory_tuple ({ $c: expr, $($param:ident)* } => {
         $($param: FromArma,)*
     {
         #[allow(non_snake_case)]
-        unsafe fn call(&self, context: Context, acm: &ArmaContextManager, output: *mut libc::c_char, size: libc::size_t, args: Option<*mut *mut i8>, count: Option<libc::c_int>) -> libc::c_int {
-            crate::RVExtensionFeatureFlags = FeatureFlags::default().with_context_stack_trace(true).as_bits();
+        unsafe fn call(&self, context: Context, acm: &ArmaContextManager, output: *mut libc::c_char, size: libc::size_t, args: Arguments<'_>) -> libc::c_int {
             let call_context = acm.request();
-            execute!(self, $c, count, output, size, args, (context call_context), ($($param,)*))
+            execute!(self, $c, output, size, args, (context call_context), ($($param,)*))
+        }
+
+        fn feature_flags(&self) -> FeatureFlags {
+            FeatureFlags::new().with_context_stack_trace(true)
         }
     }
 });
@@ -280,23 +310,23 @@ unsafe fn handle_output_and_return<R>(
 where
     R: IntoExtResult + 'static,
 {
-    let ret = ret.to_ext_result();
+    unsafe { write_ext_result(ret.to_ext_result(), output, size) }
+}
+
+/// Write the result of a command to the output buffer, returning the code of the call
+pub(crate) unsafe fn write_ext_result(
+    ret: Result<Value, Value>,
+    output: *mut libc::c_char,
+    size: libc::size_t,
+) -> libc::c_int {
     let ok = ret.is_ok();
-    if crate::write_cstr(
-        {
-            let value = match ret {
-                Ok(x) | Err(x) => x,
-            };
-            match value {
-                Value::String(s) => s,
-                v => v.to_string(),
-            }
+    let written = match &ret {
+        Ok(Value::String(s)) | Err(Value::String(s)) => unsafe {
+            crate::write_display(s.as_str(), output, size)
         },
-        output,
-        size,
-    )
-    .is_none()
-    {
+        Ok(value) | Err(value) => unsafe { crate::write_display(value, output, size) },
+    };
+    if written.is_none() {
         4
     } else if ok {
         0
@@ -332,3 +362,28 @@ factory_tuple! { 23, A B C D E F G H I J K L M N O P Q R S T U V W }
 factory_tuple! { 24, A B C D E F G H I J K L M N O P Q R S T U V W X }
 factory_tuple! { 25, A B C D E F G H I J K L M N O P Q R S T U V W X Y }
 factory_tuple! { 26, A B C D E F G H I J K L M N O P Q R S T U V W X Y Z }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn error_codes() {
+        assert_eq!(error_code(2, 0), 20);
+        assert_eq!(error_code(2, 3), 23);
+        assert_eq!(error_code(2, 12), 212);
+        assert_eq!(error_code(3, 10), 310);
+        assert_eq!(error_code(2, 100), 2100);
+        assert_eq!(error_code(2, -1), 2);
+        assert_eq!(error_code(2, libc::c_int::MAX), 2);
+    }
+
+    #[test]
+    fn error_prefixes() {
+        assert_eq!(error_prefix(error_code(3, 0)), 3);
+        assert_eq!(error_prefix(error_code(3, 25)), 3);
+        assert_eq!(error_prefix(error_code(2, 12)), 2);
+        assert_eq!(error_prefix(error_code(3, -1)), 3);
+        assert_eq!(error_prefix(4), 4);
+    }
+}
@@ -0,0 +1,387 @@
+//! Configuration loaded from a file next to the extension.
+
+use std::{
+    marker::PhantomData,
+    path::{Path, PathBuf},
+    sync::{Arc, Mutex, RwLock},
+};
+
+use serde::de::DeserializeOwned;
+
+use crate::{Context, State};
+
+/// Configuration value stored in the global state, see [`crate::ExtensionBuilder::config`].
+pub struct Config<T> {
+    value: RwLock<Arc<T>>,
+}
+
+impl<T> Config<T> {
+    fn new(value: T) -> Self {
+        Self {
+            value: RwLock::new(Arc::new(value)),
+        }
+    }
+
+    #[must_use]
+    /// Get the current configuration.
+    /// The returned value is not affected by later reloads.
+    pub fn get(&self) -> Arc<T> {
+        self.value
+            .read()
+            .unwrap_or_else(std::sync::PoisonError::into_inner)
+            .clone()
+    }
+
+    fn replace(&self, value: T) {
+        *self
+            .value
+            .write()
+            .unwrap_or_else(std::sync::PoisonError::into_inner) = Arc::new(value);
+    }
+}
+
+/// Error that can occur when loading a configuration file
+#[derive(Debug)]
+pub enum ConfigError {
+    /// Reading the file failed
+    Io(std::io::Error),
+    /// The TOML file could not be parsed
+    Toml(toml::de::Error),
+    /// The JSON file could not be parsed
+    Json(serde_json::Error),
+}
+
+impl std::fmt::Display for ConfigError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Self::Io(e) => write!(f, "io error: {e}"),
+            Self::Toml(e) => write!(f, "invalid toml: {e}"),
+            Self::Json(e) => write!(f, "invalid json: {e}"),
+        }
+    }
+}
+
+impl std::error::Error for ConfigError {}
+
+/// Applies a configuration read by [`Source::stage`]
+type Staged<'a> = Box<dyn FnOnce(&State) + 'a>;
+
+trait Source: Send + Sync {
+    fn load(&self, state: &State);
+    /// Read and parse the file without applying it, `None` if it did not change
+    fn stage(&self) -> Result<Option<Staged<'_>>, ConfigError>;
+}
+
+struct Entry<T> {
+    path: Option<PathBuf>,
+    contents: Mutex<Option<Vec<u8>>>,
+    _marker: PhantomData<fn() -> T>,
+}
+
+impl<T> Entry<T>
+where
+    T: DeserializeOwned + Default + Send + Sync + 'static,
+{
+    /// The configured path, or the first `<name>.toml` or `<name>.json` next to the extension
+    fn resolve(&self) -> Option<PathBuf> {
+        if let Some(path) = &self.path {
+            return Some(path.clone());
+        }
+        let dir = crate::module::module_dir()?;
+        let name = crate::module::module_name()?;
+        ["toml", "json"]
+            .into_iter()
+            .map(|ext| dir.join(format!("{name}.{ext}")))
+            .find(|path| path.exists())
+    }
+
+    /// Read the file, `None` if there is no file
+    fn read(&self) -> Result<Option<Vec<u8>>, ConfigError> {
+        match self.resolve() {
+            Some(path) if path.exists() => std::fs::read(&path).map(Some).map_err(ConfigError::Io),
+            _ => Ok(None),
+        }
+    }
+
+    fn parse(&self, contents: Option<&[u8]>) -> Result<T, ConfigError> {
+        let Some(contents) = contents else {
+            return Ok(T::default());
+        };
+        let is_json = self
+            .resolve()
+            .as_deref()
+            .and_then(Path::extension)
+            .is_some_and(|ext| ext.eq_ignore_ascii_case("json"));
+        if is_json {
+            serde_json::from_slice(contents).map_err(ConfigError::Json)
+        } else {
+            let contents = String::from_utf8_lossy(contents);
+            toml::from_str(&contents).map_err(ConfigError::Toml)
+        }
+    }
+}
+
+impl<T> Source for Entry<T>
+where
+    T: DeserializeOwned + Default + Send + Sync + 'static,
+{
+    fn load(&self, state: &State) {
+        let contents = self.read().unwrap_or_else(|e| {
+            error!("failed to read config: {e}");
+            None
+        });
+        let value = self.parse(contents.as_deref()).unwrap_or_else(|e| {
+            error!("failed to parse config, using the default: {e}");
+            T::default()
+        });
+        *self
+            .contents
+            .lock()
+            .unwrap_or_else(std::sync::PoisonError::into_inner) = contents;
+        crate::group::set_state(state, Config::new(value));
+    }
+
+    fn stage(&self) -> Result<Option<Staged<'_>>, ConfigError> {
+        let contents = self.read()?;
+        if *self
+            .contents
+            .lock()
+            .unwrap_or_else(std::sync::PoisonError::into_inner)
+            == contents
+        {
+            return Ok(None);
+        }
+        let value = self.parse(contents.as_deref())?;
+        Ok(Some(Box::new(move |state: &State| {
+            if let Some(config) = state.try_get::<Config<T>>() {
+                config.replace(value);
+            }
+            *self
+                .contents
+                .lock()
+                .unwrap_or_else(std::sync::PoisonError::into_inner) = contents;
+        })))
+    }
+}
+
+#[derive(Default)]
+/// Configuration types registered on the extension
+pub(crate) struct Configs {
+    entries: Vec<Box<dyn Source>>,
+    listeners: Vec<Box<dyn Fn(Context) + Send + Sync>>,
+    // Held while reloading, so concurrent reloads apply the files in the order they were read
+    reloading: Mutex<()>,
+}
+
+impl Configs {
+    pub(crate) fn add<T>(&mut self, path: Option<PathBuf>)
+    where
+        T: DeserializeOwned + Default + Send + Sync + 'static,
+    {
+        self.entries.push(Box::new(Entry::<T> {
+            path,
+            contents: Mutex::new(None),
+            _marker: PhantomData,
+        }));
+    }
+
+    pub(crate) fn on_change(&mut self, listener: impl Fn(Context) + Send + Sync + 'static) {
+        self.listeners.push(Box::new(listener));
+    }
+
+    pub(crate) fn load(&self, state: &State) {
+        for entry in &self.entries {
+            entry.load(state);
+        }
+    }
+
+    /// Reload every configuration, notifying the listeners if any of them changed.
+    /// Nothing is applied unless every file could be read and parsed.
+    pub(crate) fn reload(
+        &self,
+        state: &State,
+        context: impl Fn() -> Context,
+    ) -> Result<bool, ConfigError> {
+        let _reloading = self
+            .reloading
+            .lock()
+            .unwrap_or_else(std::sync::PoisonError::into_inner);
+        let staged = self
+            .entries
+            .iter()
+            .map(|entry| entry.stage())
+            .collect::<Result<Vec<_>, _>>()?;
+        let mut changed = false;
+        for apply in staged.into_iter().flatten() {
+            apply(state);
+            changed = true;
+        }
+        if changed {
+            for listener in &self.listeners {
+                listener(context());
+            }
+        }
+        Ok(changed)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[derive(Debug, Default, PartialEq, Eq, serde::Deserialize)]
+    struct Settings {
+        url: String,
+        verbose: bool,
+    }
+
+    /// Directory for the files of one test, removed when the test ends
+    struct TempDir(PathBuf);
+
+    impl TempDir {
+        fn new(test: &str) -> Self {
+            let dir =
+                std::env::temp_dir().join(format!("arma-rs-config-{}-{test}", std::process::id()));
+            std::fs::create_dir_all(&dir).unwrap();
+            Self(dir)
+        }
+
+        fn path(&self, name: &str) -> PathBuf {
+            self.0.join(name)
+        }
+    }
+
+    impl Drop for TempDir {
+        fn drop(&mut self) {
+            let _ = std::fs::remove_dir_all(&self.0);
+        }
+    }
+
+    fn settings(state: &State) -> Arc<Settings> {
+        state.try_get::<Config<Settings>>().unwrap().get()
+    }
+
+    #[test]
+    fn missing_uses_default() {
+        let dir = TempDir::new("missing");
+        let mut configs = Configs::default();
+        configs.add::<Settings>(Some(dir.path("missing.toml")));
+        let state = State::default();
+        configs.load(&state);
+        assert_eq!(*settings(&state), Settings::default());
+    }
+
+    #[test]
+    fn load_toml() {
+        let dir = TempDir::new("toml");
+        let path = dir.path("load.toml");
+        std::fs::write(&path, "url = \"localhost\"\nverbose = true\n").unwrap();
+        let mut configs = Configs::default();
+        configs.add::<Settings>(Some(path));
+        let state = State::default();
+        configs.load(&state);
+        assert_eq!(
+            *settings(&state),
+            Settings {
+                url: String::from("localhost"),
+                verbose: true
+            }
+        );
+    }
+
+    #[test]
+    fn load_json() {
+        let dir = TempDir::new("json");
+        let path = dir.path("load.json");
+        std::fs::write(&path, r#"{"url":"localhost","verbose":false}"#).unwrap();
+        let mut configs = Configs::default();
+        configs.add::<Settings>(Some(path));
+        let state = State::default();
+        configs.load(&state);
+        assert_eq!(settings(&state).url, "localhost");
+    }
+
+    #[test]
+    fn invalid_uses_default() {
+        let dir = TempDir::new("invalid");
+        let path = dir.path("invalid.toml");
+        std::fs::write(&path, "url = ").unwrap();
+        let mut configs = Configs::default();
+        configs.add::<Settings>(Some(path));
+        let state = State::default();
+        configs.load(&state);
+        assert_eq!(*settings(&state), Settings::default());
+    }
+
+    #[derive(Debug, Default, PartialEq, Eq, serde::Deserialize)]
+    struct Limits {
+        players: u32,
+    }
+
+    /// Configs for `Settings` and `Limits`, counting the change notifications
+    fn reloadable(dir: &TempDir) -> (Configs, State, Arc<std::sync::atomic::AtomicUsize>) {
+        std::fs::write(dir.path("settings.toml"), "url = \"a\"\nverbose = false\n").unwrap();
+        std::fs::write(dir.path("limits.toml"), "players = 10\n").unwrap();
+        let notified = Arc::new(std::sync::atomic::AtomicUsize::new(0));
+        let mut configs = Configs::default();
+        configs.add::<Settings>(Some(dir.path("settings.toml")));
+        configs.add::<Limits>(Some(dir.path("limits.toml")));
+        let counter = notified.clone();
+        configs.on_change(move |_| {
+            counter.fetch_add(1, std::sync::atomic::Ordering::SeqCst);
+        });
+        let state = State::default();
+        configs.load(&state);
+        (configs, state, notified)
+    }
+
+    fn players(state: &State) -> u32 {
+        state.try_get::<Config<Limits>>().unwrap().get().players
+    }
+
+    fn context() -> Context {
+        crate::Extension::build().finish().context()
+    }
+
+    #[test]
+    fn reload_unchanged() {
+        let dir = TempDir::new("unchanged");
+        let (configs, state, notified) = reloadable(&dir);
+        assert!(!configs.reload(&state, context).unwrap());
+        assert_eq!(notified.load(std::sync::atomic::Ordering::SeqCst), 0);
+    }
+
+    #[test]
+    fn reload_changed() {
+        let dir = TempDir::new("changed");
+        let (configs, state, notified) = reloadable(&dir);
+        std::fs::write(dir.path("limits.toml"), "players = 20\n").unwrap();
+        assert!(configs.reload(&state, context).unwrap());
+        assert_eq!(players(&state), 20);
+        assert_eq!(settings(&state).url, "a");
+        assert_eq!(notified.load(std::sync::atomic::Ordering::SeqCst), 1);
+        assert!(!configs.reload(&state, context).unwrap());
+    }
+
+    #[test]
+    fn reload_partial_failure() {
+        let dir = TempDir::new("partial");
+        let (configs, state, notified) = reloadable(&dir);
+        std::fs::write(dir.path("settings.toml"), "url = \"b\"\nverbose = true\n").unwrap();
+        std::fs::write(dir.path("limits.toml"), "players = ").unwrap();
+        assert!(matches!(
+            configs.reload(&state, context),
+            Err(ConfigError::Toml(_))
+        ));
+        // The valid file is not applied either
+        assert_eq!(settings(&state).url, "a");
+        assert_eq!(players(&state), 10);
+        assert_eq!(notified.load(std::sync::atomic::Ordering::SeqCst), 0);
+
+        std::fs::write(dir.path("limits.toml"), "players = 30\n").unwrap();
+        assert!(configs.reload(&state, context).unwrap());
+        assert_eq!(settings(&state).url, "b");
+        assert_eq!(players(&state), 30);
+        assert_eq!(notified.load(std::sync::atomic::Ordering::SeqCst), 1);
+    }
+}
@@ -2,14 +2,15 @@ use std::sync::Arc;
 
 use crate::{ContextState, State};
 
+#[derive(Clone)]
 /// Contains information about the extension
 pub struct GlobalContext {
-    version: String,
+    version: Arc<str>,
     state: Arc<State>,
 }
 
 impl GlobalContext {
-    pub(crate) const fn new(version: String, state: Arc<State>) -> Self {
+    pub(crate) const fn new(version: Arc<str>, state: Arc<State>) -> Self {
         Self { version, state }
     }
 
@@ -32,6 +33,6 @@ impl ContextState for GlobalContext {
     where
         T: Send + Sync + 'static,
     {
-        self.state.set(value)
+        crate::group::set_state(&self.state, value)
     }
 }
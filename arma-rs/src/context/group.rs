@@ -2,6 +2,7 @@ use std::sync::Arc;
 
 use crate::{ContextState, State};
 
+#[derive(Clone)]
 /// Contains information about the current group
 pub struct GroupContext {
     state: Arc<State>,
@@ -25,6 +26,6 @@ impl ContextState for GroupContext {
     where
         T: Send + Sync + 'static,
     {
-        self.state.set(value)
+        crate::group::set_state(&self.state, value)
     }
 }
@@ -1,8 +1,16 @@
 //! Contextual execution information.
 
-use crossbeam_channel::Sender;
+use std::sync::Arc;
 
-use crate::{CallbackMessage, IntoArma, Value};
+use crate::{
+    ArmaEvent, CallbackMessage, IntoArma, IntoExtResult, Value,
+    bridge::{Bridge, PendingRequest},
+    bus::{Bus, Delivery},
+    callback::CallbackQueue,
+    job::{CancellationToken, JobId, JobManager},
+    pool::{SpawnError, WorkerPool},
+    registry::Registry,
+};
 
 mod global;
 mod group;
@@ -14,7 +22,10 @@ pub use group::GroupContext;
 
 /// Contains information about the current execution context
 pub struct Context {
-    callback_tx: Sender<CallbackMessage>,
+    callback_queue: CallbackQueue,
+    bridge: Arc<Bridge>,
+    pool: Arc<WorkerPool>,
+    bus: Arc<Bus>,
     global: GlobalContext,
     group: GroupContext,
     buffer_size: usize,
@@ -22,12 +33,18 @@ pub struct Context {
 
 impl Context {
     pub(crate) const fn new(
-        callback_tx: Sender<CallbackMessage>,
+        callback_queue: CallbackQueue,
+        bridge: Arc<Bridge>,
+        pool: Arc<WorkerPool>,
+        bus: Arc<Bus>,
         global: GlobalContext,
         group: GroupContext,
     ) -> Self {
         Self {
-            callback_tx,
+            callback_queue,
+            bridge,
+            pool,
+            bus,
             global,
             group,
             buffer_size: 0,
@@ -44,6 +61,18 @@ impl Context {
         self
     }
 
+    /// A context for use outside of the current call, with the same callbacks and state
+    fn detached(&self) -> Self {
+        Self::new(
+            self.callback_queue.clone(),
+            self.bridge.clone(),
+            self.pool.clone(),
+            self.bus.clone(),
+            self.global.clone(),
+            self.group.clone(),
+        )
+    }
+
     #[must_use]
     /// Global context
     pub const fn global(&self) -> &GlobalContext {
@@ -67,14 +96,24 @@ impl Context {
         }
     }
 
-    fn callback(&self, name: &str, func: &str, data: Option<Value>) -> Result<(), CallbackError> {
-        self.callback_tx
-            .send(CallbackMessage::Call(
-                name.to_string(),
-                func.to_string(),
-                data,
-            ))
-            .map_err(|_| CallbackError::ChannelClosed)
+    #[must_use]
+    /// Returns the maximum length in bytes of the data of a single callback.
+    /// Larger data is split into fragments, see [`crate::callback::REASSEMBLE_SQF`].
+    pub fn callback_len(&self) -> usize {
+        self.callback_queue.chunk_size()
+    }
+
+    pub(crate) fn callback(
+        &self,
+        name: &str,
+        func: &str,
+        data: Option<Value>,
+    ) -> Result<(), CallbackError> {
+        self.callback_queue.send(CallbackMessage::Call(
+            name.to_string(),
+            func.to_string(),
+            data,
+        ))
     }
 
     /// Sends a callback with data into Arma
@@ -86,6 +125,114 @@ impl Context {
         self.callback(name, func, Some(data.to_arma()))
     }
 
+    /// Sends a typed event into Arma, using the callback name and function of the event
+    pub fn emit<E>(&self, event: E) -> Result<(), CallbackError>
+    where
+        E: ArmaEvent,
+    {
+        self.callback(E::NAME, E::FUNC, Some(event.to_arma()))
+    }
+
+    /// Sends a request into Arma, to be answered by SQF through the `::reply` command.
+    /// The data of the callback is `[id, data]`, see [`crate::bridge::REPLY_SQF`] for a helper to answer it.
+    ///
+    /// Wait for the answer with [`PendingRequest::wait`] or by awaiting it,
+    /// set a deadline with [`PendingRequest::timeout`] before awaiting it.
+    pub fn request<V>(
+        &self,
+        name: &str,
+        func: &str,
+        data: V,
+    ) -> Result<PendingRequest, CallbackError>
+    where
+        V: IntoArma,
+    {
+        let request = self.bridge.open();
+        self.callback(
+            name,
+            func,
+            Some(Value::Array(vec![
+                Value::String(request.id().to_string()),
+                data.to_arma(),
+            ])),
+        )?;
+        Ok(request)
+    }
+
+    #[must_use]
+    /// Commands registered while the extension is running.
+    /// Commands can be added and removed from any thread, for example to enable features per mission.
+    /// # Panics
+    /// Panics if the context was not created by an [`crate::Extension`].
+    pub fn registry(&self) -> &Registry {
+        self.global
+            .get::<Registry>()
+            .expect("the registry is added to the state when the extension is built")
+    }
+
+    /// Runs a task on the extension's worker pool, see [`crate::ExtensionBuilder::worker_pool`].
+    /// The task receives a context with the same callbacks and state as this one.
+    /// # Errors
+    /// Returns [`SpawnError::Saturated`] if every worker is busy and the queue is full,
+    /// the task is not run.
+    pub fn spawn<F>(&self, task: F) -> Result<(), SpawnError>
+    where
+        F: FnOnce(Self) + Send + 'static,
+    {
+        let ctx = self.detached();
+        self.pool.spawn(move || task(ctx))
+    }
+
+    /// Publishes an event to the subscribers of every group, see [`crate::Group::subscribe`].
+    /// Returns the number of subscribers the event was delivered or queued to.
+    /// # Errors
+    /// Returns [`SpawnError`] if the event could not be queued for a subscriber using [`Delivery::Pool`],
+    /// the other subscribers still receive it.
+    pub fn publish<E>(&self, event: E) -> Result<usize, SpawnError>
+    where
+        E: Send + Sync + 'static,
+    {
+        let event = Arc::new(event);
+        let mut delivered = 0;
+        let mut error = None;
+        for subscriber in self.bus.subscribers::<E>() {
+            let ctx = self
+                .detached()
+                .with_group(GroupContext::new(subscriber.state.clone()));
+            match subscriber.delivery {
+                Delivery::Sync => (subscriber.handler)(ctx, event.as_ref()),
+                Delivery::Pool => {
+                    let handler = subscriber.handler.clone();
+                    let event = event.clone();
+                    if let Err(e) = self.pool.spawn(move || handler(ctx, event.as_ref())) {
+                        error = Some(e);
+                        continue;
+                    }
+                }
+            }
+            delivered += 1;
+        }
+        error.map_or(Ok(delivered), Err)
+    }
+
+    /// Runs a job on a background thread, returning its id.
+    ///
+    /// The job receives a [`CancellationToken`] that is cancelled by `::job_cancel` or when the extension shuts down.
+    /// When it returns, a callback is sent with the name [`crate::job::JOB_CALLBACK`], the final status as the function
+    /// and `[id, name, result]` as the data.
+    /// # Panics
+    /// Panics if the context was not created by an [`crate::Extension`].
+    pub fn spawn_job<F, R>(&self, name: &str, job: F) -> JobId
+    where
+        F: FnOnce(CancellationToken) -> R + Send + 'static,
+        R: IntoExtResult,
+    {
+        self.global
+            .get::<JobManager>()
+            .expect("the job manager is added to the state when the extension is built")
+            .spawn(name, self.callback_queue.clone(), job)
+    }
+
     /// Sends a callback without data into Arma
     /// <https://community.bistudio.com/wiki/Arma_3:_Mission_Event_Handlers#ExtensionCallback>
     pub fn callback_null(&self, name: &str, func: &str) -> Result<(), CallbackError> {
@@ -98,12 +245,15 @@ impl Context {
 pub enum CallbackError {
     /// The callback channel has been closed
     ChannelClosed,
+    /// The callback queue is full and the message was rejected, see [`crate::callback::OverflowPolicy`]
+    QueueFull,
 }
 
 impl std::fmt::Display for CallbackError {
     fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
         match self {
             Self::ChannelClosed => write!(f, "Callback channel closed"),
+            Self::QueueFull => write!(f, "Callback queue full"),
         }
     }
 }
@@ -119,12 +269,14 @@ mod tests {
     use super::*;
     use crate::State;
     use crossbeam_channel::{Sender, bounded};
-    use std::sync::Arc;
 
     fn context(tx: Sender<CallbackMessage>) -> Context {
         Context::new(
-            tx,
-            GlobalContext::new(String::new(), Arc::new(State::default())),
+            CallbackQueue::from(tx),
+            Arc::default(),
+            Arc::default(),
+            Arc::default(),
+            GlobalContext::new(Arc::from(""), Arc::new(State::default())),
             GroupContext::new(Arc::new(State::default())),
         )
     }
@@ -141,6 +293,15 @@ mod tests {
         assert_eq!(context(tx).with_buffer_size(100).buffer_len(), 99);
     }
 
+    #[test]
+    fn context_callback_len() {
+        let (tx, _) = bounded(0);
+        assert_eq!(
+            context(tx).callback_len(),
+            crate::callback::DEFAULT_CHUNK_SIZE
+        );
+    }
+
     #[test]
     fn context_callback_block() {
         let (tx, rx) = bounded(0);
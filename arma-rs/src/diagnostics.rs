@@ -0,0 +1,513 @@
+//! Diagnostic commands, called from Arma using `::[name]`.
+//!
+//! The built-in commands are available on every platform:
+//! - `::version` returns the version of the extension
+//! - `::health` returns the [`Health`] of the extension
+//! - `::log_level` sets the level of the logger, such as `"debug"`
+//! - `::dump_state` returns the names of the state types of the extension and its groups
+//!
+//! On Windows `::console` allocates a console window for the extension.
+//!
+//! More commands can be added with [`crate::ExtensionBuilder::diagnostic`],
+//! and access to them can be limited with [`crate::ExtensionBuilder::diagnostic_access`].
+//! The policy also covers the other `::` commands of arma-rs, such as `::stats`, `::jobs` and `::task_pause`,
+//! except `::reply` which answers requests made by the extension.
+
+use std::{collections::HashMap, sync::Arc, time::Duration};
+
+use log::LevelFilter;
+
+use crate::{
+    CallContext, Extension, FeatureFlags, FromArma, IntoArma, Value,
+    call_context::ArmaContextManager,
+    command::{Arguments, Handler, error_code},
+    write_cstr,
+};
+
+#[cfg(windows)]
+/// Allows a console to be allocated for the extension.
+static CONSOLE_ALLOCATED: std::sync::atomic::AtomicBool = std::sync::atomic::AtomicBool::new(false);
+
+/// Decides if a call may run a diagnostic command, given the name of the command without the `::` prefix
+type AccessCheck = dyn Fn(&str, &CallContext) -> bool + Send + Sync;
+
+#[derive(Clone, Default)]
+/// Who may call the diagnostic commands and the other `::` commands of arma-rs
+pub enum AccessPolicy {
+    #[default]
+    /// Every caller
+    Allow,
+    /// No caller, the commands return code 1 as if they did not exist
+    Deny,
+    /// Callers accepted by the function, given the name of the command without the `::` prefix and the call context.
+    /// Denied calls return code 1 as if the command did not exist.
+    Custom(Arc<AccessCheck>),
+}
+
+impl AccessPolicy {
+    #[must_use]
+    /// Create a policy that accepts the callers accepted by `check`
+    pub fn custom<F>(check: F) -> Self
+    where
+        F: Fn(&str, &CallContext) -> bool + Send + Sync + 'static,
+    {
+        Self::Custom(Arc::new(check))
+    }
+}
+
+#[derive(Default)]
+/// Diagnostic commands added to the extension, and the policy protecting every diagnostic command
+pub(crate) struct Diagnostics {
+    policy: AccessPolicy,
+    commands: HashMap<String, Handler>,
+}
+
+impl Diagnostics {
+    pub(crate) fn set_policy(&mut self, policy: AccessPolicy) {
+        self.policy = policy;
+    }
+
+    /// Add a command, called using `::[name]`
+    /// # Panics
+    /// Panics if the name is empty, contains `:` or is reserved by arma-rs
+    pub(crate) fn add(&mut self, name: String, handler: Handler) {
+        assert!(
+            !name.is_empty() && !name.contains(':'),
+            "diagnostic name `{name}` must not be empty or contain `:`"
+        );
+        assert!(
+            built_in(&name).is_none(),
+            "diagnostic name `{name}` is reserved by arma-rs"
+        );
+        self.commands.insert(name, handler);
+    }
+
+    /// The feature flags needed by the added commands
+    pub(crate) fn feature_flags(&self) -> FeatureFlags {
+        self.commands
+            .values()
+            .map(|command| command.flags)
+            .fold(FeatureFlags::new(), FeatureFlags::union)
+    }
+
+    fn allowed(&self, name: &str, acm: &ArmaContextManager) -> bool {
+        match &self.policy {
+            AccessPolicy::Allow => true,
+            AccessPolicy::Deny => false,
+            AccessPolicy::Custom(check) => {
+                let call_context = acm.request();
+                let allowed = check(name, &call_context.clone().into_without_stack());
+                // Keep the context for a command that takes it
+                acm.replace(Some(call_context));
+                allowed
+            }
+        }
+    }
+}
+
+/// Handler of a built-in `::` command, given the name of the command without the prefix
+type BuiltInHandler =
+    unsafe fn(&Extension, &str, *mut libc::c_char, libc::size_t, Arguments<'_>) -> libc::c_int;
+
+/// A command provided by arma-rs in the `::` namespace
+struct BuiltIn {
+    /// Name without the `::` prefix, reserved even where the command is not available
+    name: &'static str,
+    /// Whether the access policy applies to the command
+    protected: bool,
+    handler: BuiltInHandler,
+}
+
+impl BuiltIn {
+    const fn protected(name: &'static str, handler: BuiltInHandler) -> Self {
+        Self {
+            name,
+            protected: true,
+            handler,
+        }
+    }
+}
+
+/// Every built-in `::` command, used both to reserve the names and to route the calls
+const BUILT_IN: &[BuiltIn] = &[
+    BuiltIn::protected("version", version),
+    BuiltIn::protected("health", health),
+    BuiltIn::protected("log_level", log_level),
+    BuiltIn::protected("dump_state", dump_state),
+    BuiltIn::protected("console", console),
+    // Answers requests made by the extension, denying it would leave them waiting until they time out
+    BuiltIn {
+        name: "reply",
+        protected: false,
+        handler: reply,
+    },
+    BuiltIn::protected("jobs", jobs),
+    BuiltIn::protected("job_status", job),
+    BuiltIn::protected("job_cancel", job),
+    BuiltIn::protected("tasks", tasks),
+    BuiltIn::protected("task_pause", task),
+    BuiltIn::protected("task_resume", task),
+    BuiltIn::protected("stats", stats),
+    BuiltIn::protected("save_state", save_state),
+    BuiltIn::protected("reload_config", reload_config),
+];
+
+fn built_in(name: &str) -> Option<&'static BuiltIn> {
+    BUILT_IN.iter().find(|built_in| built_in.name == name)
+}
+
+/// Whether the access policy denies the call to the `::` command `name`
+fn denied(extension: &Extension, name: &str) -> bool {
+    let diagnostics = &extension.diagnostics;
+    let protected = built_in(name).map_or_else(
+        || diagnostics.commands.contains_key(name),
+        |built_in| built_in.protected,
+    );
+    protected && !diagnostics.allowed(name, extension.context_manager.as_ref())
+}
+
+/// Run the `::` command `function`, `None` if it is not one
+///
+/// # Safety
+/// `output` must be valid for writes of `size` bytes and the arguments must be null or valid C strings.
+pub(crate) unsafe fn handle(
+    extension: &Extension,
+    function: &str,
+    output: *mut libc::c_char,
+    size: libc::size_t,
+    args: Arguments<'_>,
+) -> Option<libc::c_int> {
+    let name = function.strip_prefix("::")?;
+    let command = extension.diagnostics.commands.get(name);
+    let built_in = built_in(name);
+    if command.is_none() && built_in.is_none() {
+        return None;
+    }
+    if denied(extension, name) {
+        return Some(1);
+    }
+    if let Some(command) = command {
+        let acm = extension.context_manager.as_ref();
+        let context = extension.context().with_buffer_size(size);
+        return Some((command.handler)(context, acm, output, size, args));
+    }
+    built_in.map(|built_in| unsafe { (built_in.handler)(extension, name, output, size, args) })
+}
+
+/// Write `value` to the output, returning code 0, or 4 if it does not fit
+unsafe fn write(value: String, output: *mut libc::c_char, size: libc::size_t) -> libc::c_int {
+    if unsafe { write_cstr(value, output, size) }.is_some() {
+        0
+    } else {
+        4
+    }
+}
+
+/// Write `error` to the output, returning code 9
+unsafe fn fail(error: String, output: *mut libc::c_char, size: libc::size_t) -> libc::c_int {
+    unsafe { write_cstr(error, output, size) };
+    9
+}
+
+unsafe fn version(
+    extension: &Extension,
+    _: &str,
+    output: *mut libc::c_char,
+    size: libc::size_t,
+    _: Arguments<'_>,
+) -> libc::c_int {
+    unsafe { write(extension.version().to_string(), output, size) }
+}
+
+unsafe fn health(
+    extension: &Extension,
+    _: &str,
+    output: *mut libc::c_char,
+    size: libc::size_t,
+    _: Arguments<'_>,
+) -> libc::c_int {
+    unsafe { write(extension.health().to_arma().to_string(), output, size) }
+}
+
+unsafe fn log_level(
+    extension: &Extension,
+    _: &str,
+    output: *mut libc::c_char,
+    size: libc::size_t,
+    args: Arguments<'_>,
+) -> libc::c_int {
+    if args.len() != 1 {
+        return error_code(2, args.count());
+    }
+    let level = unsafe { args.sqf(0) };
+    let parsed = String::from_arma_str(&level)
+        .unwrap_or_else(|_| level.to_string())
+        .parse::<LevelFilter>();
+    match parsed {
+        Ok(level) => {
+            extension.logger().map_or_else(
+                || log::set_max_level(level),
+                |logger| logger.set_level(level),
+            );
+            0
+        }
+        Err(_) => unsafe { fail(format!("invalid log level: {level}"), output, size) },
+    }
+}
+
+unsafe fn dump_state(
+    extension: &Extension,
+    _: &str,
+    output: *mut libc::c_char,
+    size: libc::size_t,
+    _: Arguments<'_>,
+) -> libc::c_int {
+    let value = Value::Array(
+        extension
+            .group
+            .state_names()
+            .into_iter()
+            .map(|(path, names)| {
+                Value::Array(vec![
+                    Value::String(path),
+                    Value::Array(
+                        names
+                            .into_iter()
+                            .map(|name| Value::String(name.to_string()))
+                            .collect(),
+                    ),
+                ])
+            })
+            .collect(),
+    );
+    unsafe { write(value.to_string(), output, size) }
+}
+
+/// Only available on Windows, returns code 1 as if the command did not exist elsewhere
+unsafe fn console(
+    _: &Extension,
+    _: &str,
+    _: *mut libc::c_char,
+    _: libc::size_t,
+    _: Arguments<'_>,
+) -> libc::c_int {
+    #[cfg(windows)]
+    {
+        if !CONSOLE_ALLOCATED.swap(true, std::sync::atomic::Ordering::SeqCst) {
+            let _ = unsafe { windows::Win32::System::Console::AllocConsole() };
+        }
+        0
+    }
+    #[cfg(not(windows))]
+    1
+}
+
+unsafe fn reply(
+    extension: &Extension,
+    _: &str,
+    output: *mut libc::c_char,
+    size: libc::size_t,
+    args: Arguments<'_>,
+) -> libc::c_int {
+    if args.len() != 2 {
+        return error_code(2, args.count());
+    }
+    let id = unsafe { args.sqf(0) };
+    let value = unsafe { args.sqf(1) };
+    match extension.reply(&id, &value) {
+        Ok(()) => 0,
+        Err(e) => unsafe { fail(e, output, size) },
+    }
+}
+
+unsafe fn jobs(
+    extension: &Extension,
+    _: &str,
+    output: *mut libc::c_char,
+    size: libc::size_t,
+    _: Arguments<'_>,
+) -> libc::c_int {
+    unsafe { write(extension.jobs.jobs().to_arma().to_string(), output, size) }
+}
+
+/// `::job_status` and `::job_cancel`
+unsafe fn job(
+    extension: &Extension,
+    name: &str,
+    output: *mut libc::c_char,
+    size: libc::size_t,
+    args: Arguments<'_>,
+) -> libc::c_int {
+    if args.len() != 1 {
+        return error_code(2, args.count());
+    }
+    let id = unsafe { args.sqf(0) };
+    match extension.job_command(name == "job_cancel", &id) {
+        Ok(value) => unsafe { write(value.to_string(), output, size) },
+        Err(e) => unsafe { fail(e, output, size) },
+    }
+}
+
+unsafe fn tasks(
+    extension: &Extension,
+    _: &str,
+    output: *mut libc::c_char,
+    size: libc::size_t,
+    _: Arguments<'_>,
+) -> libc::c_int {
+    unsafe {
+        write(
+            extension.scheduler.tasks().to_arma().to_string(),
+            output,
+            size,
+        )
+    }
+}
+
+/// `::task_pause` and `::task_resume`
+unsafe fn task(
+    extension: &Extension,
+    name: &str,
+    output: *mut libc::c_char,
+    size: libc::size_t,
+    args: Arguments<'_>,
+) -> libc::c_int {
+    if args.len() != 1 {
+        return error_code(2, args.count());
+    }
+    let id = unsafe { args.sqf(0) };
+    match usize::from_arma(id.to_string()) {
+        Ok(id) => {
+            let found = if name == "task_pause" {
+                extension.scheduler.pause(id)
+            } else {
+                extension.scheduler.resume(id)
+            };
+            if found {
+                0
+            } else {
+                unsafe { fail(format!("task {id} not found"), output, size) }
+            }
+        }
+        Err(e) => unsafe { fail(format!("invalid task id: {e}"), output, size) },
+    }
+}
+
+unsafe fn stats(
+    extension: &Extension,
+    _: &str,
+    output: *mut libc::c_char,
+    size: libc::size_t,
+    _: Arguments<'_>,
+) -> libc::c_int {
+    unsafe { write(extension.metrics.to_arma().to_string(), output, size) }
+}
+
+/// Only available with the `serde` feature, returns code 1 as if the command did not exist otherwise
+unsafe fn save_state(
+    extension: &Extension,
+    _: &str,
+    output: *mut libc::c_char,
+    size: libc::size_t,
+    _: Arguments<'_>,
+) -> libc::c_int {
+    #[cfg(feature = "serde")]
+    {
+        match extension.save_state() {
+            Ok(()) => 0,
+            Err(e) => unsafe { fail(e.to_string(), output, size) },
+        }
+    }
+    #[cfg(not(feature = "serde"))]
+    {
+        let _ = (extension, output, size);
+        1
+    }
+}
+
+/// Only available with the `config` feature, returns code 1 as if the command did not exist otherwise
+unsafe fn reload_config(
+    extension: &Extension,
+    _: &str,
+    output: *mut libc::c_char,
+    size: libc::size_t,
+    _: Arguments<'_>,
+) -> libc::c_int {
+    #[cfg(feature = "config")]
+    {
+        match extension.reload_config() {
+            Ok(changed) => {
+                unsafe { write_cstr(changed.to_string(), output, size) };
+                0
+            }
+            Err(e) => unsafe { fail(e.to_string(), output, size) },
+        }
+    }
+    #[cfg(not(feature = "config"))]
+    {
+        let _ = (extension, output, size);
+        1
+    }
+}
+
+/// Snapshot of the health of the extension, see [`crate::Extension::health`].
+/// Returned to Arma by `::health` as `[uptime in seconds, callback thread running, queued callbacks]`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Health {
+    uptime: Duration,
+    callback_thread: bool,
+    queued_callbacks: usize,
+}
+
+impl Health {
+    pub(crate) const fn new(
+        uptime: Duration,
+        callback_thread: bool,
+        queued_callbacks: usize,
+    ) -> Self {
+        Self {
+            uptime,
+            callback_thread,
+            queued_callbacks,
+        }
+    }
+
+    #[must_use]
+    /// Time since the extension was built
+    pub const fn uptime(&self) -> Duration {
+        self.uptime
+    }
+
+    #[must_use]
+    /// Whether the thread delivering callbacks to Arma is running
+    pub const fn callback_thread(&self) -> bool {
+        self.callback_thread
+    }
+
+    #[must_use]
+    /// Number of callbacks waiting to be delivered to Arma
+    pub const fn queued_callbacks(&self) -> usize {
+        self.queued_callbacks
+    }
+}
+
+impl IntoArma for Health {
+    fn to_arma(&self) -> Value {
+        Value::Array(vec![
+            Value::Number(self.uptime.as_secs_f64()),
+            Value::Boolean(self.callback_thread),
+            Value::Number(self.queued_callbacks as f64),
+        ])
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    #[should_panic(expected = "reserved")]
+    fn reserved_name() {
+        Diagnostics::default().add("stats".to_string(), crate::fn_handler(|| {}));
+    }
+}
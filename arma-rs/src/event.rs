@@ -0,0 +1,11 @@
+use crate::IntoArma;
+
+/// A callback with a fixed name and function, sent with `Context::emit`.
+///
+/// Usually derived with `#[derive(ArmaEvent)]`, the data of the callback is the type's [`IntoArma`] value.
+pub trait ArmaEvent: IntoArma {
+    /// Name of the callback, the first parameter of the `ExtensionCallback` event handler
+    const NAME: &'static str;
+    /// Function of the callback, the second parameter of the `ExtensionCallback` event handler
+    const FUNC: &'static str;
+}
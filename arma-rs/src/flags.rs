@@ -2,6 +2,8 @@
 //!
 //! <https://community.bistudio.com/wiki/Extensions#Feature_Flags>
 
+use std::sync::atomic::{AtomicBool, AtomicU64, Ordering};
+
 /// `RVExtensionContext` takes const void** as argument, instead of the default const char**, and arguments will be passed in their custom types
 pub const RV_CONTEXT_ARGUMENTS_VOID_PTR: u64 = 1 << 0;
 /// `RVExtensionContext` will retrieve a full Stacktrace
@@ -11,36 +13,87 @@ pub const RV_CONTEXT_NO_DEFAULT_CALL: u64 = 1 << 2;
 
 #[derive(Debug, Clone, Copy, Default, PartialEq, Eq, PartialOrd, Ord, Hash)]
 /// Feature flags for RV Extensions
+///
+/// Every command declares the flags it needs, the flags of all commands are combined and published to Arma.
 pub struct FeatureFlags {
     context_stack_trace: bool,
+    context_always: bool,
 }
 
 impl FeatureFlags {
+    #[must_use]
+    /// Create `FeatureFlags` with every flag unset
+    pub const fn new() -> Self {
+        Self {
+            context_stack_trace: false,
+            context_always: false,
+        }
+    }
+
     /// Set the `context_stack_trace` flag
     pub const fn set_context_stack_trace(&mut self, value: bool) {
         self.context_stack_trace = value;
     }
 
+    #[must_use]
+    /// Set the `context_stack_trace` flag
     pub const fn with_context_stack_trace(mut self, value: bool) -> Self {
         self.set_context_stack_trace(value);
         self
     }
 
+    #[must_use]
     /// Get the `context_stack_trace` flag
     pub const fn context_stack_trace(&self) -> bool {
         self.context_stack_trace
     }
 
+    /// Set the `context_always` flag, Arma provides the call context before every call instead of when it is requested
+    pub const fn set_context_always(&mut self, value: bool) {
+        self.context_always = value;
+    }
+
+    #[must_use]
+    /// Set the `context_always` flag
+    pub const fn with_context_always(mut self, value: bool) -> Self {
+        self.set_context_always(value);
+        self
+    }
+
+    #[must_use]
+    /// Get the `context_always` flag
+    pub const fn context_always(&self) -> bool {
+        self.context_always
+    }
+
+    #[must_use]
+    /// Combine the flags of two commands, a flag is set if either requires it
+    pub const fn union(self, other: Self) -> Self {
+        Self {
+            context_stack_trace: self.context_stack_trace || other.context_stack_trace,
+            context_always: self.context_always || other.context_always,
+        }
+    }
+
+    #[must_use]
     /// Create a new `FeatureFlags` from the given bits
-    pub fn from_bits(bits: u64) -> Self {
-        let mut flags = Self::default();
-        flags.set_context_stack_trace(bits & RV_CONTEXT_STACK_TRACE != 0);
-        flags
+    pub const fn from_bits(bits: u64) -> Self {
+        Self {
+            context_stack_trace: bits & RV_CONTEXT_STACK_TRACE != 0,
+            context_always: bits & RV_CONTEXT_NO_DEFAULT_CALL == 0,
+        }
     }
 
+    #[must_use]
     /// Get the bits of the `FeatureFlags`
+    ///
+    /// `RV_CONTEXT_ARGUMENTS_VOID_PTR` is always set, it only changes the arguments of `RVExtensionContext`,
+    /// which the call context is read from. The arguments of `RVExtensionArgs` are always strings.
     pub const fn as_bits(&self) -> u64 {
-        let mut bits = RV_CONTEXT_NO_DEFAULT_CALL | RV_CONTEXT_ARGUMENTS_VOID_PTR;
+        let mut bits = RV_CONTEXT_ARGUMENTS_VOID_PTR;
+        if !self.context_always() {
+            bits |= RV_CONTEXT_NO_DEFAULT_CALL;
+        }
         if self.context_stack_trace() {
             bits |= RV_CONTEXT_STACK_TRACE;
         }
@@ -48,6 +101,41 @@ impl FeatureFlags {
     }
 }
 
+/// Flags published by one extension.
+/// Copied into `RVExtensionFeatureFlags` once the extension is the one loaded by Arma, see [`Published::make_global`].
+pub(crate) struct Published {
+    bits: AtomicU64,
+    global: AtomicBool,
+}
+
+impl Published {
+    pub(crate) const fn new(flags: FeatureFlags) -> Self {
+        Self {
+            bits: AtomicU64::new(flags.as_bits()),
+            global: AtomicBool::new(false),
+        }
+    }
+
+    /// Publish the flags Arma reads on each callExtension call
+    pub(crate) fn publish(&self, flags: FeatureFlags) {
+        self.bits.store(flags.as_bits(), Ordering::Release);
+        if self.global.load(Ordering::Acquire) {
+            crate::RVExtensionFeatureFlags.store(flags.as_bits(), Ordering::Release);
+        }
+    }
+
+    /// The flags Arma reads on each callExtension call
+    pub(crate) fn get(&self) -> FeatureFlags {
+        FeatureFlags::from_bits(self.bits.load(Ordering::Acquire))
+    }
+
+    /// Copy the flags into `RVExtensionFeatureFlags`, now and whenever they are published
+    pub(crate) fn make_global(&self) {
+        self.global.store(true, Ordering::Release);
+        crate::RVExtensionFeatureFlags.store(self.bits.load(Ordering::Acquire), Ordering::Release);
+    }
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
@@ -70,4 +158,35 @@ mod tests {
             RV_CONTEXT_NO_DEFAULT_CALL | RV_CONTEXT_STACK_TRACE | RV_CONTEXT_ARGUMENTS_VOID_PTR
         );
     }
+
+    #[test]
+    fn union_round_trip() {
+        let flags = FeatureFlags::new()
+            .with_context_stack_trace(true)
+            .union(FeatureFlags::new().with_context_always(true));
+        assert_eq!(
+            flags.as_bits(),
+            RV_CONTEXT_STACK_TRACE | RV_CONTEXT_ARGUMENTS_VOID_PTR
+        );
+        assert_eq!(FeatureFlags::from_bits(flags.as_bits()), flags);
+        assert_eq!(
+            FeatureFlags::from_bits(FeatureFlags::new().as_bits()),
+            FeatureFlags::new()
+        );
+    }
+
+    #[test]
+    fn published() {
+        let global = || crate::RVExtensionFeatureFlags.load(Ordering::Acquire);
+        let stack_trace = FeatureFlags::new().with_context_stack_trace(true);
+        let published = Published::new(FeatureFlags::new());
+        published.publish(stack_trace);
+        assert_eq!(published.get(), stack_trace);
+        assert_eq!(global(), FeatureFlags::new().as_bits());
+
+        published.make_global();
+        assert_eq!(global(), stack_trace.as_bits());
+        published.publish(FeatureFlags::new());
+        assert_eq!(global(), FeatureFlags::new().as_bits());
+    }
 }
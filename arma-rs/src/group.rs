@@ -1,27 +1,95 @@
-use std::{collections::HashMap, sync::Arc};
+use std::{
+    any::type_name,
+    borrow::Cow,
+    collections::{HashMap, HashSet, VecDeque},
+    sync::{Arc, Mutex, PoisonError},
+    time::Instant,
+};
 
 use crate::{
-    State,
-    command::{Factory, Handler, fn_handler},
+    ArmaEvent, CallContextStackTrace, FeatureFlags, IntoExtResult, State, Value,
+    bus::{Bus, Delivery, Subscriber},
+    command::{Arguments, Factory, Handler, fn_handler, write_ext_result},
     context::{Context, GroupContext},
+    metrics::{CommandMetrics, Metrics},
+    registry::RegistryError,
 };
 
+/// Handler called with the unmatched remainder of the path and the arguments of the call
+type Fallback = dyn Fn(Context, String, Vec<String>) -> Result<Value, Value> + Send + Sync;
+
 #[derive(Default)]
 /// A group of commands.
 /// Called from Arma using `[group]:[command]`.
 pub struct Group {
     commands: HashMap<String, Box<Handler>>,
+    deprecated: HashMap<String, String>,
+    aliases: HashMap<String, String>,
     children: HashMap<String, Self>,
+    default_version: Option<u32>,
+    fallback: Option<Box<Fallback>>,
+    subscribers: Vec<Subscriber>,
     state: State,
 }
 
+#[derive(Default)]
+/// Type names of the values set in a state container, listed by `::dump_state`
+struct StateNames(Mutex<Vec<&'static str>>);
+
+/// Set a value in a state container, recording its type name for `::dump_state`.
+/// Every state value of the extension and its groups is set through this function.
+pub(crate) fn set_state<T>(state: &State, value: T) -> bool
+where
+    T: Send + Sync + 'static,
+{
+    if !state.set(value) {
+        return false;
+    }
+    state.set(StateNames::default());
+    if let Some(names) = state.try_get::<StateNames>() {
+        names
+            .0
+            .lock()
+            .unwrap_or_else(PoisonError::into_inner)
+            .push(type_name::<T>());
+    }
+    true
+}
+
+/// Type names of the values set in a state container with [`set_state`]
+pub(crate) fn state_names(state: &State) -> Vec<&'static str> {
+    state
+        .try_get::<StateNames>()
+        .map(|names| {
+            names
+                .0
+                .lock()
+                .unwrap_or_else(PoisonError::into_inner)
+                .clone()
+        })
+        .unwrap_or_default()
+}
+
+/// Names of commands, aliases and groups are a single segment of a path
+fn validate_name(kind: &str, name: &str) {
+    assert!(
+        !name.is_empty() && !name.contains(':'),
+        "{kind} name `{name}` must not be empty or contain `:`"
+    );
+}
+
 impl Group {
     #[must_use]
     /// Creates a new group
     pub fn new() -> Self {
         Self {
             commands: HashMap::new(),
+            deprecated: HashMap::new(),
+            aliases: HashMap::new(),
             children: HashMap::new(),
+            default_version: None,
+            fallback: None,
+            subscribers: Vec::new(),
             state: State::default(),
         }
     }
@@ -33,7 +101,7 @@ impl Group {
     where
         T: Send + Sync + 'static,
     {
-        self.state.set(state);
+        set_state(&self.state, state);
         self
     }
 
@@ -48,36 +116,533 @@ impl Group {
     #[inline]
     #[must_use]
     /// Add a command to the group
+    /// # Panics
+    /// Panics if the name is empty or contains `:`
     pub fn command<S, F, I, R>(mut self, name: S, handler: F) -> Self
     where
         S: Into<String>,
-        F: Factory<I, R> + 'static,
+        F: Factory<I, R> + Send + Sync + 'static,
     {
-        self.commands
-            .insert(name.into(), Box::new(fn_handler(handler)));
+        let name = name.into();
+        validate_name("command", &name);
+        self.commands.insert(name, Box::new(fn_handler(handler)));
         self
     }
 
+    #[inline]
+    #[must_use]
+    /// Add a deprecated command to the group.
+    /// A warning with the note is logged the first time each caller uses the command,
+    /// and the note is reported in the command's statistics, see [`crate::metrics::CommandStats::deprecated`].
+    /// # Panics
+    /// Panics if the name is empty or contains `:`
+    pub fn command_deprecated<S, N, F, I, R>(self, name: S, note: N, handler: F) -> Self
+    where
+        S: Into<String>,
+        N: Into<String>,
+        F: Factory<I, R> + Send + Sync + 'static,
+    {
+        let name = name.into();
+        let mut group = self.command(name.clone(), handler);
+        group.deprecated.insert(name, note.into());
+        group
+    }
+
+    #[inline]
+    #[must_use]
+    /// Add a command that needs the call context on every call.
+    /// Arma then provides the context before every call to the extension, instead of when a command requests it.
+    /// # Panics
+    /// Panics if the name is empty or contains `:`
+    pub fn command_always_context<S, F, I, R>(self, name: S, handler: F) -> Self
+    where
+        S: Into<String>,
+        F: Factory<I, R> + Send + Sync + 'static,
+    {
+        let name = name.into();
+        let mut group = self.command(name.clone(), handler);
+        if let Some(handler) = group.commands.get_mut(&name) {
+            handler.flags.set_context_always(true);
+        }
+        group
+    }
+
+    #[inline]
+    #[must_use]
+    /// Add another name for a command of the group, so renamed commands keep working under their old name.
+    /// # Panics
+    /// Panics if the alias is empty or contains `:`.
+    /// The extension panics when it is built if the command does not exist.
+    pub fn alias<S, T>(mut self, alias: S, command: T) -> Self
+    where
+        S: Into<String>,
+        T: Into<String>,
+    {
+        let alias = alias.into();
+        validate_name("alias", &alias);
+        self.aliases.insert(alias, command.into());
+        self
+    }
+
+    #[inline]
+    #[must_use]
+    /// Handle calls to commands and groups that do not exist in this group.
+    /// The handler receives the unmatched remainder of the path and the arguments of the call.
+    ///
+    /// When a nested group does not have a fallback, the fallback of the closest parent is used.
+    pub fn fallback<F, R>(mut self, handler: F) -> Self
+    where
+        F: Fn(Context, String, Vec<String>) -> R + Send + Sync + 'static,
+        R: IntoExtResult,
+    {
+        self.fallback = Some(Box::new(move |ctx, path, args| {
+            handler(ctx, path, args).to_ext_result()
+        }));
+        self
+    }
+
+    #[inline]
+    #[must_use]
+    /// Subscribe to events of type `E` published with [`Context::publish`] by any group.
+    /// The handler runs on the publishing thread and receives a context with this group's state.
+    pub fn subscribe<E, F>(self, handler: F) -> Self
+    where
+        E: Send + Sync + 'static,
+        F: Fn(Context, &E) + Send + Sync + 'static,
+    {
+        self.subscribe_with(Delivery::Sync, handler)
+    }
+
+    #[inline]
+    #[must_use]
+    /// Subscribe to events of type `E`, choosing how they are delivered
+    pub fn subscribe_with<E, F>(mut self, delivery: Delivery, handler: F) -> Self
+    where
+        E: Send + Sync + 'static,
+        F: Fn(Context, &E) + Send + Sync + 'static,
+    {
+        self.subscribers.push(Subscriber::new(delivery, handler));
+        self
+    }
+
+    #[inline]
+    #[must_use]
+    /// Forward events of type `E` published with [`Context::publish`] to Arma as callbacks
+    pub fn forward<E>(self) -> Self
+    where
+        E: ArmaEvent + Send + Sync + 'static,
+    {
+        self.subscribe(|ctx: Context, event: &E| {
+            if let Err(e) = ctx.callback(E::NAME, E::FUNC, Some(event.to_arma())) {
+                warn!("failed to forward event to Arma: {e}");
+            }
+        })
+    }
+
     #[inline]
     #[must_use]
     /// Add a group to the group
+    /// # Panics
+    /// Panics if the name is empty or contains `:`
     pub fn group<S>(mut self, name: S, child: Self) -> Self
     where
         S: Into<String>,
     {
-        self.children.insert(name.into(), child);
+        let name = name.into();
+        validate_name("group", &name);
+        self.children.insert(name, child);
+        self
+    }
+
+    #[inline]
+    #[must_use]
+    /// Add a version of the group's API, called using `v[version]:[command]`
+    /// # Panics
+    /// Panics if the version, or a group named `v[version]`, was already added
+    pub fn version(self, version: u32, child: Self) -> Self {
+        let name = format!("v{version}");
+        assert!(
+            !self.children.contains_key(&name),
+            "group `{name}` already exists"
+        );
+        self.group(name, child)
+    }
+
+    #[inline]
+    #[must_use]
+    /// Sets the version used for paths that do not start with a version,
+    /// so `stats:get` calls `v2:stats:get` when the default version is 2.
+    ///
+    /// Commands and groups of this group take precedence over the commands of the version.
+    /// The extension panics when it is built if the version does not exist.
+    pub const fn default_version(mut self, version: u32) -> Self {
+        self.default_version = Some(version);
         self
     }
+
+    /// Flatten the commands and aliases of the group and its children into their full paths, starting with `prefix`.
+    /// The commands of the default version are added as aliases at the paths the group does not define itself.
+    pub(crate) fn flatten(self, prefix: &str) -> Result<Vec<Flat>, RegistryError> {
+        if self.fallback.is_some() || !self.subscribers.is_empty() {
+            return Err(RegistryError::Unsupported(
+                prefix.trim_end_matches(':').to_string(),
+            ));
+        }
+        let state = Arc::new(self.state);
+        let mut entries = Vec::new();
+        let mut deprecated = self.deprecated;
+        for (name, handler) in self.commands {
+            entries.push(Flat::Command {
+                path: format!("{prefix}{name}"),
+                handler: *handler,
+                deprecated: deprecated.remove(&name),
+                state: state.clone(),
+            });
+        }
+        for (alias, target) in self.aliases {
+            if !entries
+                .iter()
+                .any(|entry| entry.path() == format!("{prefix}{target}"))
+            {
+                return Err(RegistryError::MissingTarget(format!("{prefix}{alias}")));
+            }
+            entries.push(Flat::Alias {
+                path: format!("{prefix}{alias}"),
+                target: format!("{prefix}{target}"),
+            });
+        }
+        let version = self.default_version.map(|version| format!("v{version}"));
+        let mut versioned = None;
+        for (name, child) in self.children {
+            let child_prefix = format!("{prefix}{name}:");
+            let child_entries = child.flatten(&child_prefix)?;
+            if version.as_ref() == Some(&name) {
+                versioned = Some((
+                    child_prefix,
+                    entries.len()..entries.len() + child_entries.len(),
+                ));
+            }
+            entries.extend(child_entries);
+        }
+        if let Some(version) = version {
+            let Some((child_prefix, range)) = versioned else {
+                return Err(RegistryError::MissingTarget(format!("{prefix}{version}")));
+            };
+            let defaults = entries[range]
+                .iter()
+                .map(|entry| {
+                    let target = entry.path().to_string();
+                    let path = format!("{prefix}{}", &target[child_prefix.len()..]);
+                    (path, target)
+                })
+                .filter(|(path, _)| !entries.iter().any(|entry| entry.path() == path))
+                .collect::<Vec<_>>();
+            entries.extend(
+                defaults
+                    .into_iter()
+                    .map(|(path, target)| Flat::Alias { path, target }),
+            );
+        }
+        Ok(entries)
+    }
+}
+
+/// A command or alias of a group, added while the extension is running with [`crate::registry::Registry::group`]
+pub(crate) enum Flat {
+    Command {
+        path: String,
+        handler: Handler,
+        deprecated: Option<String>,
+        state: Arc<State>,
+    },
+    /// Another path for the command or alias at `target`, which comes before it
+    Alias { path: String, target: String },
+}
+
+impl Flat {
+    pub(crate) fn path(&self) -> &str {
+        match self {
+            Self::Command { path, .. } | Self::Alias { path, .. } => path,
+        }
+    }
+}
+
+/// Number of callers remembered by a deprecated command, the oldest are forgotten first
+const MAX_WARNED: usize = 1024;
+
+struct InternalCommand {
+    handler: Box<Handler>,
+    metrics: Arc<CommandMetrics>,
+    deprecation: Option<Deprecation>,
+}
+
+pub(crate) struct Deprecation {
+    name: String,
+    note: String,
+    // Callers that have already been warned, by steam id and source, at most `MAX_WARNED`
+    warned: Mutex<Warned>,
+}
+
+#[derive(Default)]
+/// Set of callers that remembers the order they were added in
+struct Warned {
+    callers: HashSet<(u64, String)>,
+    order: VecDeque<(u64, String)>,
+}
+
+impl Deprecation {
+    pub(crate) fn new(name: String, note: String) -> Self {
+        Self {
+            name,
+            note,
+            warned: Mutex::default(),
+        }
+    }
+
+    /// Log a warning the first time a caller uses the command.
+    /// The call context is only requested from Arma if it was not provided for this call,
+    /// a caller that was already warned costs no round trip when Arma provides it.
+    pub(crate) fn warn_call(&self, acm: &crate::ArmaContextManager) {
+        if acm.peek(|call_context| self.warn(call_context)).is_some() {
+            return;
+        }
+        let call_context = acm.request();
+        self.warn(&call_context);
+        // Put the call context back for the command
+        acm.replace(Some(call_context));
+    }
+
+    /// Log a warning the first time a caller uses the command, returns true if a warning was logged
+    pub(crate) fn warn(&self, call_context: &CallContextStackTrace) -> bool {
+        let caller = call_context.caller().as_u64();
+        let source = call_context.source().as_str();
+        let mut warned = self
+            .warned
+            .lock()
+            .unwrap_or_else(std::sync::PoisonError::into_inner);
+        let key = (caller, source.to_string());
+        if warned.callers.contains(&key) {
+            return false;
+        }
+        if warned.order.len() >= MAX_WARNED
+            && let Some(oldest) = warned.order.pop_front()
+        {
+            warned.callers.remove(&oldest);
+        }
+        warned.callers.insert(key.clone());
+        warned.order.push_back(key);
+        drop(warned);
+        warn!(
+            "`{}` is deprecated: {} (called by {caller} from `{source}`)",
+            self.name, self.note
+        );
+        true
+    }
+}
+
+/// Where a call is routed to
+enum Route<'a> {
+    Command(&'a InternalGroup, &'a InternalCommand),
+    /// The fallback of a group, with the position of the unmatched remainder in the path
+    Fallback(&'a InternalGroup, &'a Fallback, usize),
 }
 
 pub struct InternalGroup {
-    commands: HashMap<String, Box<Handler>>,
+    commands: HashMap<String, Arc<InternalCommand>>,
     children: HashMap<String, Self>,
+    default_version: Option<String>,
+    fallback: Option<Box<Fallback>>,
+    case_insensitive: bool,
     pub(crate) state: Arc<State>,
+    metrics: Arc<Metrics>,
+}
+
+/// Key of a name in a group, lowercase when routing is case insensitive
+pub(crate) fn route_key(name: &str, case_insensitive: bool) -> Cow<'_, str> {
+    if case_insensitive && name.bytes().any(|b| b.is_ascii_uppercase()) {
+        Cow::Owned(name.to_ascii_lowercase())
+    } else {
+        Cow::Borrowed(name)
+    }
 }
 
 impl InternalGroup {
-    #[allow(clippy::too_many_arguments)]
+    /// Convert a group, registering the metrics of every command and the subscribers of every group
+    /// # Panics
+    /// Panics if an alias or default version points to something missing, or if two names only differ in case when routing is case insensitive.
+    pub(crate) fn new(
+        group: Group,
+        metrics: &Arc<Metrics>,
+        bus: &mut Bus,
+        case_insensitive: bool,
+    ) -> Self {
+        Self::with_prefix(group, "", metrics, bus, case_insensitive)
+    }
+
+    fn with_prefix(
+        group: Group,
+        prefix: &str,
+        metrics: &Arc<Metrics>,
+        bus: &mut Bus,
+        case_insensitive: bool,
+    ) -> Self {
+        let state = Arc::new(group.state);
+        for subscriber in group.subscribers {
+            bus.register(subscriber, &state);
+        }
+        let mut commands = HashMap::new();
+        let mut insert = |name: &str, command: Arc<InternalCommand>| {
+            let key = route_key(name, case_insensitive).into_owned();
+            assert!(
+                commands.insert(key, command).is_none(),
+                "`{prefix}{name}` is defined more than once when ignoring case"
+            );
+        };
+        let mut targets = HashMap::new();
+        let mut deprecated = group.deprecated;
+        for (name, handler) in group.commands {
+            let path = format!("{prefix}{name}");
+            let command_metrics = metrics.register(path.clone());
+            let deprecation = deprecated.remove(&name).map(|note| {
+                command_metrics.deprecate(note.clone());
+                Deprecation::new(path, note)
+            });
+            let command = Arc::new(InternalCommand {
+                handler,
+                metrics: command_metrics,
+                deprecation,
+            });
+            targets.insert(name.clone(), command.clone());
+            insert(&name, command);
+        }
+        for (alias, target) in group.aliases {
+            let command = targets.get(&target).unwrap_or_else(|| {
+                panic!("alias `{prefix}{alias}` points to missing command `{prefix}{target}`")
+            });
+            insert(&alias, command.clone());
+        }
+        let mut children = HashMap::new();
+        for (name, group) in group.children {
+            let child = Self::with_prefix(
+                group,
+                &format!("{prefix}{name}:"),
+                metrics,
+                bus,
+                case_insensitive,
+            );
+            assert!(
+                children
+                    .insert(route_key(&name, case_insensitive).into_owned(), child)
+                    .is_none(),
+                "group `{prefix}{name}` is defined more than once when ignoring case"
+            );
+        }
+        let default_version = group.default_version.map(|version| {
+            let key = format!("v{version}");
+            assert!(
+                children.contains_key(&key),
+                "default version `{prefix}{key}` does not exist"
+            );
+            key
+        });
+        Self {
+            commands,
+            children,
+            default_version,
+            fallback: group.fallback,
+            case_insensitive,
+            state,
+            metrics: metrics.clone(),
+        }
+    }
+
+    /// Feature flags needed by the commands of the group and its children
+    pub(crate) fn feature_flags(&self) -> FeatureFlags {
+        self.commands
+            .values()
+            .map(|command| command.handler.flags)
+            .chain(self.children.values().map(Self::feature_flags))
+            .fold(FeatureFlags::new(), FeatureFlags::union)
+    }
+
+    /// Feature flags of the command at `path`, `None` if no command is defined at the path
+    pub(crate) fn command_flags(&self, path: &str) -> Option<FeatureFlags> {
+        match self.resolve(&route_key(path, self.case_insensitive), 0) {
+            Some(Route::Command(_, command)) => Some(command.handler.flags),
+            _ => None,
+        }
+    }
+
+    /// Every path that resolves to a command, including aliases and the paths of the default version
+    pub(crate) fn paths(&self) -> HashSet<String> {
+        let mut paths: HashSet<String> = self.commands.keys().cloned().collect();
+        for (name, child) in &self.children {
+            paths.extend(
+                child
+                    .paths()
+                    .into_iter()
+                    .map(|path| format!("{name}:{path}")),
+            );
+        }
+        if let Some(child) = self
+            .default_version
+            .as_ref()
+            .and_then(|version| self.children.get(version))
+        {
+            paths.extend(child.paths());
+        }
+        paths
+    }
+
+    /// Names of the state types of the group and its children, by path, sorted by path
+    pub(crate) fn state_names(&self) -> Vec<(String, Vec<&'static str>)> {
+        let mut names = Vec::new();
+        self.collect_state_names(String::new(), &mut names);
+        names.sort_unstable_by(|(a, _), (b, _)| a.cmp(b));
+        names
+    }
+
+    fn collect_state_names(&self, path: String, names: &mut Vec<(String, Vec<&'static str>)>) {
+        for (name, child) in &self.children {
+            let child_path = if path.is_empty() {
+                name.clone()
+            } else {
+                format!("{path}:{name}")
+            };
+            child.collect_state_names(child_path, names);
+        }
+        let types = state_names(&self.state);
+        if !types.is_empty() {
+            names.push((path, types));
+        }
+    }
+
+    /// Find the command or fallback handling `path`, `offset` is the position of `path` in the full path
+    fn resolve(&self, path: &str, offset: usize) -> Option<Route<'_>> {
+        let found = match path.split_once(':') {
+            Some((group, rest)) => self
+                .children
+                .get(group)
+                .and_then(|child| child.resolve(rest, offset + group.len() + 1)),
+            None => self
+                .commands
+                .get(path)
+                .map(|command| Route::Command(self, command)),
+        };
+        found
+            .or_else(|| {
+                self.default_version
+                    .as_ref()
+                    .and_then(|version| self.children.get(version))
+                    .and_then(|child| child.resolve(path, offset))
+            })
+            .or_else(|| {
+                self.fallback
+                    .as_deref()
+                    .map(|fallback| Route::Fallback(self, fallback, offset))
+            })
+    }
+
     pub(crate) fn handle(
         &self,
         context: Context,
@@ -85,39 +650,148 @@ impl InternalGroup {
         function: &str,
         output: *mut libc::c_char,
         size: libc::size_t,
-        args: Option<*mut *mut i8>,
-        count: Option<libc::c_int>,
+        args: Arguments<'_>,
     ) -> libc::c_int {
-        if let Some((group, function)) = function.split_once(':') {
-            self.children.get(group).map_or(1, |group| {
-                group.handle(context, acm, function, output, size, args, count)
-            })
-        } else if let Some(handler) = self.commands.get(function) {
-            (handler.handler)(
-                context.with_group(GroupContext::new(self.state.clone())),
-                acm,
-                output,
-                size,
-                args,
-                count,
-            )
-        } else {
-            1
+        // Lowercasing ASCII keeps the positions in the path, the remainder is taken from the original
+        let path = route_key(function, self.case_insensitive);
+        match self.resolve(&path, 0) {
+            Some(Route::Command(group, command)) => {
+                if let Some(deprecation) = &command.deprecation {
+                    deprecation.warn_call(acm);
+                }
+                let start = Instant::now();
+                let code = (command.handler.handler)(
+                    context.with_group(GroupContext::new(group.state.clone())),
+                    acm,
+                    output,
+                    size,
+                    args,
+                );
+                command.metrics.record(code, start.elapsed());
+                code
+            }
+            Some(Route::Fallback(group, fallback, offset)) => {
+                let args = (0..args.len())
+                    .map(|i| unsafe { args.sqf(i) }.into_owned())
+                    .collect();
+                let result = fallback(
+                    context.with_group(GroupContext::new(group.state.clone())),
+                    function[offset..].to_string(),
+                    args,
+                );
+                unsafe { write_ext_result(result, output, size) }
+            }
+            None => {
+                self.metrics.record_unknown();
+                1
+            }
         }
     }
 }
 
-impl From<Group> for InternalGroup {
-    fn from(group: Group) -> Self {
-        let children = group
-            .children
-            .into_iter()
-            .map(|(name, group)| (name, Self::from(group)))
-            .collect();
-        Self {
-            commands: group.commands,
-            children,
-            state: Arc::new(group.state),
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{ArmaCallContext, Caller, Mission, Server, Source};
+
+    #[test]
+    fn paths() {
+        let group = Group::new()
+            .command("hello", || ())
+            .alias("hi", "hello")
+            .group("stats", Group::new().command("get", || ()))
+            .version(
+                2,
+                Group::new().group("stats", Group::new().command("reset", || ())),
+            )
+            .default_version(2);
+        let group = InternalGroup::new(group, &Arc::default(), &mut Bus::default(), false);
+        let mut paths = group.paths().into_iter().collect::<Vec<_>>();
+        paths.sort();
+        assert_eq!(
+            paths,
+            vec!["hello", "hi", "stats:get", "stats:reset", "v2:stats:reset"]
+        );
+    }
+
+    #[test]
+    fn deprecation_warns_once_per_caller() {
+        let deprecation =
+            Deprecation::new(String::from("stats:get"), String::from("use v2:stats:get"));
+        let call = |caller: u64, source: &str| {
+            ArmaCallContext::new(
+                Caller::Steam(caller),
+                Source::from(source),
+                Mission::None,
+                Server::Singleplayer,
+                0,
+            )
+        };
+        assert!(deprecation.warn(&call(1, "fn_stats.sqf")));
+        assert!(!deprecation.warn(&call(1, "fn_stats.sqf")));
+        assert!(deprecation.warn(&call(2, "fn_stats.sqf")));
+        assert!(deprecation.warn(&call(1, "fn_other.sqf")));
+
+        // Remembered callers are bounded, the oldest is forgotten first
+        for caller in 3..MAX_WARNED as u64 {
+            assert!(deprecation.warn(&call(caller, "fn_stats.sqf")));
         }
+        assert_eq!(deprecation.warned.lock().unwrap().callers.len(), MAX_WARNED);
+        assert!(deprecation.warn(&call(0, "fn_stats.sqf")));
+        assert_eq!(deprecation.warned.lock().unwrap().callers.len(), MAX_WARNED);
+        assert!(!deprecation.warn(&call(2, "fn_stats.sqf")));
+        assert!(!deprecation.warn(&call(0, "fn_stats.sqf")));
+        assert!(deprecation.warn(&call(1, "fn_stats.sqf")));
+    }
+
+    #[test]
+    fn deprecation_uses_provided_context() {
+        static REQUESTS: std::sync::atomic::AtomicUsize = std::sync::atomic::AtomicUsize::new(0);
+        unsafe extern "system" fn request() {
+            REQUESTS.fetch_add(1, std::sync::atomic::Ordering::SeqCst);
+        }
+
+        let acm = crate::ArmaContextManager::new(request);
+        let deprecation =
+            Deprecation::new(String::from("stats:get"), String::from("use v2:stats:get"));
+        let call = ArmaCallContext::new(
+            Caller::Steam(1),
+            Source::from("fn_stats.sqf"),
+            Mission::None,
+            Server::Singleplayer,
+            0,
+        );
+        for _ in 0..2 {
+            acm.replace(Some(call.clone()));
+            deprecation.warn_call(&acm);
+            // The context is left in place for the command
+            assert!(acm.peek(|_| ()).is_some());
+        }
+        assert_eq!(REQUESTS.load(std::sync::atomic::Ordering::SeqCst), 0);
+        assert_eq!(deprecation.warned.lock().unwrap().callers.len(), 1);
+
+        acm.replace(None);
+        deprecation.warn_call(&acm);
+        assert_eq!(REQUESTS.load(std::sync::atomic::Ordering::SeqCst), 1);
+        assert!(acm.peek(|_| ()).is_some());
+        acm.replace(None);
+    }
+
+    #[test]
+    fn state_names_recorded() {
+        let state = State::default();
+        assert!(state_names(&state).is_empty());
+        assert!(set_state(&state, 1_u32));
+        assert!(!set_state(&state, 2_u32));
+        assert!(set_state(&state, String::new()));
+        assert_eq!(state_names(&state), vec!["u32", "alloc::string::String"]);
+    }
+
+    #[test]
+    #[should_panic(expected = "group `v2` already exists")]
+    fn version_exists() {
+        let _ = Group::new()
+            .group("v2", Group::new())
+            .version(2, Group::new());
     }
 }
@@ -0,0 +1,374 @@
+//! Background jobs that can be listed, queried and cancelled from Arma, see [`crate::Context::spawn_job`].
+
+use std::{
+    collections::BTreeMap,
+    sync::{
+        Arc, Mutex,
+        atomic::{AtomicBool, AtomicU64, Ordering},
+    },
+};
+
+use crate::{CallbackMessage, IntoArma, IntoExtResult, Value, callback::CallbackQueue};
+
+/// Callback name used to report finished jobs.
+/// The function is the job's final [`JobStatus`] and the data is `[id, name, result]`.
+pub const JOB_CALLBACK: &str = "arma_rs:job";
+
+/// Maximum number of finished jobs kept for `::job_status`, the oldest are forgotten first
+const MAX_FINISHED: usize = 100;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash)]
+/// Identifier of a job, returned to Arma as a number
+pub struct JobId(u64);
+
+impl JobId {
+    #[must_use]
+    /// Create a job id from its numeric value
+    pub const fn new(id: u64) -> Self {
+        Self(id)
+    }
+
+    #[must_use]
+    /// Numeric value of the job id
+    pub const fn get(&self) -> u64 {
+        self.0
+    }
+}
+
+impl std::fmt::Display for JobId {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        self.0.fmt(f)
+    }
+}
+
+impl IntoArma for JobId {
+    #[allow(clippy::cast_precision_loss)]
+    fn to_arma(&self) -> Value {
+        Value::Number(self.0 as f64)
+    }
+}
+
+#[derive(Debug, Clone, Default)]
+/// Cooperative cancellation token passed to every job.
+///
+/// Jobs are never interrupted, they should check [`CancellationToken::is_cancelled`] regularly and return early.
+pub struct CancellationToken(Arc<AtomicBool>);
+
+impl CancellationToken {
+    #[must_use]
+    /// Returns true once the job has been asked to stop
+    pub fn is_cancelled(&self) -> bool {
+        self.0.load(Ordering::SeqCst)
+    }
+
+    /// Ask the job to stop
+    pub fn cancel(&self) {
+        self.0.store(true, Ordering::SeqCst);
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+/// Status of a job
+pub enum JobStatus {
+    /// The job is running
+    Running,
+    /// The job was asked to stop and has not returned yet
+    Cancelling,
+    /// The job returned a successful result
+    Completed,
+    /// The job returned an error or panicked
+    Failed,
+    /// The job returned after it was cancelled
+    Cancelled,
+}
+
+impl JobStatus {
+    #[must_use]
+    /// Returns true if the job has returned
+    pub const fn is_finished(&self) -> bool {
+        matches!(self, Self::Completed | Self::Failed | Self::Cancelled)
+    }
+
+    #[must_use]
+    /// Name of the status, as sent to Arma
+    pub const fn as_str(&self) -> &'static str {
+        match self {
+            Self::Running => "running",
+            Self::Cancelling => "cancelling",
+            Self::Completed => "completed",
+            Self::Failed => "failed",
+            Self::Cancelled => "cancelled",
+        }
+    }
+}
+
+impl IntoArma for JobStatus {
+    fn to_arma(&self) -> Value {
+        Value::String(self.as_str().to_string())
+    }
+}
+
+#[derive(Debug, Clone)]
+/// Information about a job
+pub struct JobInfo {
+    id: JobId,
+    name: String,
+    status: JobStatus,
+    result: Option<Value>,
+}
+
+impl JobInfo {
+    #[must_use]
+    /// Id of the job
+    pub const fn id(&self) -> JobId {
+        self.id
+    }
+
+    #[must_use]
+    /// Name given to the job when it was spawned
+    pub fn name(&self) -> &str {
+        &self.name
+    }
+
+    #[must_use]
+    /// Current status of the job
+    pub const fn status(&self) -> JobStatus {
+        self.status
+    }
+
+    #[must_use]
+    /// Value returned by the job, `None` until it has finished
+    pub const fn result(&self) -> Option<&Value> {
+        self.result.as_ref()
+    }
+}
+
+impl IntoArma for JobInfo {
+    fn to_arma(&self) -> Value {
+        Value::Array(vec![
+            self.id.to_arma(),
+            self.name.to_arma(),
+            self.status.to_arma(),
+            self.result.clone().unwrap_or(Value::Null),
+        ])
+    }
+}
+
+struct Job {
+    info: JobInfo,
+    token: CancellationToken,
+}
+
+#[derive(Default)]
+struct Jobs {
+    next_id: AtomicU64,
+    jobs: Mutex<BTreeMap<JobId, Job>>,
+}
+
+#[derive(Clone, Default)]
+/// Keeps track of the background jobs of an extension.
+///
+/// Available in the global state, and to Arma through the `::jobs`, `::job_status` and `::job_cancel` commands.
+pub struct JobManager(Arc<Jobs>);
+
+impl JobManager {
+    fn lock(&self) -> std::sync::MutexGuard<'_, BTreeMap<JobId, Job>> {
+        self.0
+            .jobs
+            .lock()
+            .unwrap_or_else(std::sync::PoisonError::into_inner)
+    }
+
+    pub(crate) fn spawn<F, R>(&self, name: &str, queue: CallbackQueue, job: F) -> JobId
+    where
+        F: FnOnce(CancellationToken) -> R + Send + 'static,
+        R: IntoExtResult,
+    {
+        let id = JobId(self.0.next_id.fetch_add(1, Ordering::Relaxed));
+        let token = CancellationToken::default();
+        self.lock().insert(
+            id,
+            Job {
+                info: JobInfo {
+                    id,
+                    name: name.to_string(),
+                    status: JobStatus::Running,
+                    result: None,
+                },
+                token: token.clone(),
+            },
+        );
+        let manager = self.clone();
+        let name = name.to_string();
+        std::thread::spawn(move || {
+            let job_token = token.clone();
+            let (status, result) =
+                match std::panic::catch_unwind(std::panic::AssertUnwindSafe(move || {
+                    job(job_token).to_ext_result()
+                })) {
+                    Ok(result) => {
+                        let status = if token.is_cancelled() {
+                            JobStatus::Cancelled
+                        } else if result.is_ok() {
+                            JobStatus::Completed
+                        } else {
+                            JobStatus::Failed
+                        };
+                        (status, result.unwrap_or_else(|e| e))
+                    }
+                    Err(_) => {
+                        error!("job {id} ({name}) panicked");
+                        (
+                            JobStatus::Failed,
+                            Value::String(String::from("job panicked")),
+                        )
+                    }
+                };
+            let Some(info) = manager.finish(id, status, result) else {
+                return;
+            };
+            let _ = queue.send(CallbackMessage::Call(
+                JOB_CALLBACK.to_string(),
+                status.as_str().to_string(),
+                Some(Value::Array(vec![
+                    info.id.to_arma(),
+                    info.name.to_arma(),
+                    info.result.unwrap_or(Value::Null),
+                ])),
+            ));
+        });
+        id
+    }
+
+    fn finish(&self, id: JobId, status: JobStatus, result: Value) -> Option<JobInfo> {
+        let mut jobs = self.lock();
+        let info = jobs.get_mut(&id).map(|job| {
+            job.info.status = status;
+            job.info.result = Some(result);
+            job.info.clone()
+        });
+        let finished: Vec<JobId> = jobs
+            .values()
+            .filter(|job| job.info.status.is_finished())
+            .map(|job| job.info.id)
+            .collect();
+        for id in &finished[..finished.len().saturating_sub(MAX_FINISHED)] {
+            jobs.remove(id);
+        }
+        info
+    }
+
+    #[must_use]
+    /// Returns every known job, running jobs and the most recently finished ones
+    pub fn jobs(&self) -> Vec<JobInfo> {
+        self.lock().values().map(|job| job.info.clone()).collect()
+    }
+
+    #[must_use]
+    /// Returns a job by its id
+    pub fn job(&self, id: JobId) -> Option<JobInfo> {
+        self.lock().get(&id).map(|job| job.info.clone())
+    }
+
+    /// Ask a job to stop, returns false if the job is unknown or has already finished
+    pub fn cancel(&self, id: JobId) -> bool {
+        let mut jobs = self.lock();
+        let Some(job) = jobs.get_mut(&id) else {
+            return false;
+        };
+        if job.info.status != JobStatus::Running {
+            return job.info.status == JobStatus::Cancelling;
+        }
+        job.token.cancel();
+        job.info.status = JobStatus::Cancelling;
+        true
+    }
+
+    /// Ask every running job to stop
+    pub fn cancel_all(&self) {
+        let ids: Vec<JobId> = self.lock().keys().copied().collect();
+        for id in ids {
+            self.cancel(id);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::time::Duration;
+
+    use crossbeam_channel::{Receiver, unbounded};
+
+    use super::*;
+
+    fn manager() -> (JobManager, CallbackQueue, Receiver<CallbackMessage>) {
+        let (tx, rx) = unbounded();
+        (JobManager::default(), tx.into(), rx)
+    }
+
+    fn finished(rx: &Receiver<CallbackMessage>) -> (String, String, Value) {
+        match rx.recv_timeout(Duration::from_secs(5)).unwrap() {
+            CallbackMessage::Call(name, func, data) => (name, func, data.unwrap()),
+            CallbackMessage::Terminate => panic!("unexpected terminate"),
+        }
+    }
+
+    #[test]
+    fn completed() {
+        let (manager, queue, rx) = manager();
+        let id = manager.spawn("sum", queue, |_| 1 + 2);
+        let (name, func, data) = finished(&rx);
+        assert_eq!(name, JOB_CALLBACK);
+        assert_eq!(func, "completed");
+        assert_eq!(data.to_string(), r#"[0,"sum",3]"#);
+        let info = manager.job(id).unwrap();
+        assert_eq!(info.status(), JobStatus::Completed);
+        assert_eq!(info.result(), Some(&Value::Number(3.0)));
+    }
+
+    #[test]
+    fn failed() {
+        let (manager, queue, rx) = manager();
+        let id = manager.spawn("fail", queue, |_| Err::<u8, _>("oops"));
+        assert_eq!(finished(&rx).1, "failed");
+        assert_eq!(manager.job(id).unwrap().status(), JobStatus::Failed);
+        assert!(!manager.cancel(id));
+    }
+
+    #[test]
+    fn panicked() {
+        let (manager, queue, rx) = manager();
+        let id = manager.spawn("panic", queue, |_| -> u8 { panic!("job panic") });
+        let (_, func, data) = finished(&rx);
+        assert_eq!(func, "failed");
+        assert_eq!(data.to_string(), r#"[0,"panic","job panicked"]"#);
+        assert_eq!(manager.job(id).unwrap().status(), JobStatus::Failed);
+    }
+
+    #[test]
+    fn cancelled() {
+        let (manager, queue, rx) = manager();
+        let id = manager.spawn("loop", queue, |token| {
+            while !token.is_cancelled() {
+                std::thread::sleep(Duration::from_millis(1));
+            }
+        });
+        assert_eq!(manager.job(id).unwrap().status(), JobStatus::Running);
+        assert!(manager.cancel(id));
+        assert!(manager.cancel(id));
+        assert_eq!(finished(&rx).1, "cancelled");
+        assert_eq!(manager.job(id).unwrap().status(), JobStatus::Cancelled);
+        assert!(!manager.cancel(JobId::new(42)));
+    }
+
+    #[test]
+    fn forget_finished() {
+        let (manager, queue, rx) = manager();
+        for _ in 0..=MAX_FINISHED {
+            manager.spawn("job", queue.clone(), |_| ());
+            finished(&rx);
+        }
+        assert_eq!(manager.jobs().len(), MAX_FINISHED);
+        assert!(manager.job(JobId::new(0)).is_none());
+    }
+}
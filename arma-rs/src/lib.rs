@@ -1,12 +1,10 @@
 #![warn(missing_docs, nonstandard_style)]
 #![doc = include_str!(concat!(env!("OUT_DIR"), "/README.md"))]
 
-use std::rc::Rc;
-
-pub use arma_rs_proc::{FromArma, IntoArma, arma};
+pub use arma_rs_proc::{ArmaEvent, FromArma, FromArmaRef, IntoArma, arma};
 
 #[cfg(feature = "extension")]
-use crossbeam_channel::{Receiver, Sender, unbounded};
+use crossbeam_channel::{Receiver, Sender, bounded};
 #[cfg(feature = "extension")]
 pub use libc;
 
@@ -17,17 +15,31 @@ pub use link_args;
 #[macro_use]
 extern crate log;
 
+mod event;
+pub use event::ArmaEvent;
+
 mod flags;
+pub use flags::FeatureFlags;
 
 mod value;
-pub use value::{DirectReturn, FromArma, FromArmaError, IntoArma, Value, loadout};
+pub use value::{
+    DirectReturn, FromArma, FromArmaError, FromArmaRef, IntoArma, Value, array_elements, loadout,
+};
 
+#[cfg(feature = "extension")]
+pub mod bridge;
+#[cfg(feature = "extension")]
+pub mod bus;
 #[cfg(feature = "extension")]
 mod call_context;
 #[cfg(feature = "extension")]
+pub mod callback;
+#[cfg(feature = "extension")]
 use call_context::{ArmaCallContext, ArmaContextManager};
 #[cfg(feature = "extension")]
-pub use call_context::{CallContext, CallContextStackTrace, Caller, Mission, Server, Source};
+pub use call_context::{
+    CallContext, CallContextError, CallContextStackTrace, Caller, Mission, Server, Source,
+};
 #[cfg(feature = "extension")]
 mod ext_result;
 #[cfg(feature = "extension")]
@@ -40,10 +52,32 @@ pub use command::*;
 pub mod context;
 #[cfg(feature = "extension")]
 pub use context::*;
+#[cfg(all(feature = "extension", feature = "config"))]
+pub mod config;
+#[cfg(feature = "extension")]
+pub mod diagnostics;
 #[cfg(feature = "extension")]
 mod group;
 #[cfg(feature = "extension")]
+pub mod job;
+#[cfg(feature = "extension")]
+mod lifecycle;
+#[cfg(feature = "extension")]
+pub mod logger;
+#[cfg(feature = "extension")]
+pub mod metrics;
+#[cfg(feature = "extension")]
+pub mod module;
+#[cfg(feature = "extension")]
 pub use group::Group;
+#[cfg(all(feature = "extension", feature = "serde"))]
+pub mod persist;
+#[cfg(feature = "extension")]
+pub mod pool;
+#[cfg(feature = "extension")]
+pub mod registry;
+#[cfg(feature = "extension")]
+pub mod schedule;
 #[cfg(feature = "extension")]
 pub mod testing;
 #[cfg(feature = "extension")]
@@ -70,38 +104,81 @@ enum CallbackMessage {
 /// State `TypeMap` that can hold at most one value per type key.
 pub type State = state::TypeMap![Send + Sync];
 
-#[cfg(windows)]
-/// Allows a console to be allocated for the extension.
-static CONSOLE_ALLOCATED: std::sync::atomic::AtomicBool = std::sync::atomic::AtomicBool::new(false);
+#[cfg(feature = "extension")]
+/// Maximum time to wait for queued callbacks to be sent when the extension shuts down
+const SHUTDOWN_TIMEOUT: std::time::Duration = std::time::Duration::from_secs(5);
 
 #[unsafe(no_mangle)]
 #[allow(non_upper_case_globals, reason = "This is a C API")]
 /// Feature flags read on each callExtension call.
-pub static mut RVExtensionFeatureFlags: u64 = flags::RV_CONTEXT_NO_DEFAULT_CALL;
+/// Published by the extension built by [`arma`] when it is loaded and when its [`registry::Registry`] changes,
+/// see [`Extension::feature_flags`].
+pub static RVExtensionFeatureFlags: std::sync::atomic::AtomicU64 =
+    std::sync::atomic::AtomicU64::new(FeatureFlags::new().as_bits());
 
 /// Contains all the information about your extension
 /// This is used by the generated code to interface with Arma
+///
+/// The extension is `Send + Sync`, Arma may call it from several threads at once.
+/// Command handlers can run concurrently and the call context is kept per thread.
 #[cfg(feature = "extension")]
 pub struct Extension {
-    version: String,
+    version: std::sync::Arc<str>,
     group: group::InternalGroup,
     allow_no_args: bool,
-    callback: Option<Callback>,
+    callback: std::sync::Mutex<Option<Callback>>,
     callback_channel: (Sender<CallbackMessage>, Receiver<CallbackMessage>),
-    callback_thread: Option<std::thread::JoinHandle<()>>,
-    context_manager: Rc<ArmaContextManager>,
+    callback_queue: callback::CallbackQueue,
+    bridge: std::sync::Arc<bridge::Bridge>,
+    jobs: job::JobManager,
+    scheduler: schedule::Scheduler,
+    pool: std::sync::Arc<pool::WorkerPool>,
+    bus: std::sync::Arc<bus::Bus>,
+    registry: registry::Registry,
+    callback_thread: std::sync::Mutex<Option<(std::thread::JoinHandle<()>, Receiver<()>)>>,
+    lifecycle: lifecycle::Lifecycle,
+    logger: Option<std::sync::Arc<logger::Logger>>,
+    metrics: std::sync::Arc<metrics::Metrics>,
+    context_manager: std::sync::Arc<ArmaContextManager>,
     pre218_clear_context_override: bool,
+    diagnostics: diagnostics::Diagnostics,
+    started: std::time::Instant,
+    #[cfg(feature = "serde")]
+    persistence: std::sync::Arc<persist::Persistence>,
+    #[cfg(feature = "serde")]
+    persist_saver: std::sync::Mutex<Option<persist::Saver>>,
+    #[cfg(feature = "config")]
+    configs: config::Configs,
 }
 
+#[cfg(feature = "extension")]
+// The generated code keeps the extension in a static, shared by every thread Arma calls from
+const _: () = {
+    const fn assert_send_sync<T: Send + Sync>() {}
+    assert_send_sync::<Extension>();
+};
+
 #[cfg(feature = "extension")]
 impl Extension {
     #[must_use]
     /// Creates a new extension.
     pub fn build() -> ExtensionBuilder {
         ExtensionBuilder {
-            version: String::from("0.0.0"),
+            version: None,
             group: Group::new(),
             allow_no_args: false,
+            case_insensitive: false,
+            freeze_state: false,
+            lifecycle: lifecycle::Lifecycle::default(),
+            logging: None,
+            callback: callback::CallbackConfig::default(),
+            scheduler: schedule::Scheduler::default(),
+            pool: pool::PoolConfig::default(),
+            diagnostics: diagnostics::Diagnostics::default(),
+            #[cfg(feature = "serde")]
+            persistence: persist::Persistence::default(),
+            #[cfg(feature = "config")]
+            configs: config::Configs::default(),
         }
     }
 }
@@ -124,17 +201,105 @@ impl Extension {
         self.allow_no_args
     }
 
+    #[must_use]
+    /// Returns the logger installed by [`ExtensionBuilder::logging`].
+    pub fn logger(&self) -> Option<&logger::Logger> {
+        self.logger.as_deref()
+    }
+
+    #[must_use]
+    /// Returns the metrics collected for every command.
+    /// Also available to Arma through the `::stats` command.
+    pub fn metrics(&self) -> &metrics::Metrics {
+        &self.metrics
+    }
+
+    #[must_use]
+    /// Returns the feature flags needed by every command, including commands in the [`registry::Registry`].
+    /// These are the flags published to Arma through `RVExtensionFeatureFlags` by the extension built by [`arma`].
+    pub fn feature_flags(&self) -> FeatureFlags {
+        self.registry.feature_flags()
+    }
+
+    #[must_use]
+    /// Returns the feature flags needed by the command at `path`, `None` if there is no command at the path
+    pub fn command_flags(&self, path: &str) -> Option<FeatureFlags> {
+        self.registry
+            .command_flags(path)
+            .or_else(|| self.group.command_flags(path))
+    }
+
+    #[doc(hidden)]
+    /// Called by generated code, do not call directly.
+    /// Publishes the feature flags of this extension through `RVExtensionFeatureFlags`,
+    /// other extensions built in the same process keep their flags to themselves.
+    pub fn publish_feature_flags(&self) {
+        self.registry.publish_global();
+    }
+
+    #[must_use]
+    /// Returns the background jobs spawned with [`Context::spawn_job`].
+    /// Also available to Arma through the `::jobs`, `::job_status` and `::job_cancel` commands.
+    pub const fn jobs(&self) -> &job::JobManager {
+        &self.jobs
+    }
+
+    #[must_use]
+    /// Returns the tasks added with [`ExtensionBuilder::every`], [`ExtensionBuilder::after`] and [`ExtensionBuilder::cron`].
+    /// Also available to Arma through the `::tasks`, `::task_pause` and `::task_resume` commands.
+    pub const fn scheduler(&self) -> &schedule::Scheduler {
+        &self.scheduler
+    }
+
+    #[must_use]
+    /// Returns the number of queued, dropped and retried callbacks.
+    pub fn callback_stats(&self) -> callback::CallbackStats {
+        self.callback_queue.stats()
+    }
+
+    #[must_use]
+    /// Returns the uptime, callback thread status and callback queue depth of the extension.
+    /// Also available to Arma through the `::health` command.
+    pub fn health(&self) -> diagnostics::Health {
+        let callback_thread = self
+            .callback_thread
+            .lock()
+            .unwrap_or_else(std::sync::PoisonError::into_inner)
+            .as_ref()
+            .is_some_and(|(thread, _)| !thread.is_finished());
+        diagnostics::Health::new(
+            self.started.elapsed(),
+            callback_thread,
+            self.callback_queue.stats().queued(),
+        )
+    }
+
+    #[must_use]
+    /// Returns the commands registered while the extension is running, see [`Context::registry`].
+    pub const fn registry(&self) -> &registry::Registry {
+        &self.registry
+    }
+
+    #[must_use]
+    /// Returns the number of threads, running tasks and queued tasks of the worker pool.
+    pub fn pool_stats(&self) -> pool::PoolStats {
+        self.pool.stats()
+    }
+
     #[doc(hidden)]
     /// Called by generated code, do not call directly.
-    pub fn register_callback(&mut self, callback: Callback) {
-        self.callback = Some(callback);
+    pub fn register_callback(&self, callback: Callback) {
+        *self
+            .callback
+            .lock()
+            .unwrap_or_else(std::sync::PoisonError::into_inner) = Some(callback);
     }
 
     #[doc(hidden)]
     /// Called by generated code, do not call directly.
     /// # Safety
     /// This function is unsafe because it interacts with the C API.
-    pub unsafe fn handle_call_context(&mut self, args: *mut *mut i8, count: libc::c_int) {
+    pub unsafe fn handle_call_context(&self, args: *mut *mut i8, count: libc::c_int) {
         self.context_manager
             .replace(Some(ArmaCallContext::from_arma(args, count)));
     }
@@ -143,7 +308,10 @@ impl Extension {
     /// Get a context for interacting with Arma
     pub fn context(&self) -> Context {
         Context::new(
-            self.callback_channel.0.clone(),
+            self.callback_queue.clone(),
+            self.bridge.clone(),
+            self.pool.clone(),
+            self.bus.clone(),
             GlobalContext::new(self.version.clone(), self.group.state.clone()),
             GroupContext::new(self.group.state.clone()),
         )
@@ -162,32 +330,114 @@ impl Extension {
         count: Option<libc::c_int>,
         clear_call_context: bool,
     ) -> libc::c_int {
-        if clear_call_context && !self.pre218_clear_context_override {
+        // Arma provides the context before the call when a command needs it on every call
+        if clear_call_context
+            && !self.pre218_clear_context_override
+            && !self.registry.published().context_always()
+        {
             self.context_manager.replace(None);
         }
-        let function = if let Ok(cstring) = unsafe { std::ffi::CStr::from_ptr(function).to_str() } {
-            cstring.to_string()
-        } else {
+        if function.is_null() {
+            return 1;
+        }
+        let Ok(function) = unsafe { std::ffi::CStr::from_ptr(function) }.to_str() else {
             return 1;
         };
-        match function.as_str() {
-            #[cfg(windows)]
-            "::console" => {
-                if !CONSOLE_ALLOCATED.swap(true, std::sync::atomic::Ordering::SeqCst) {
-                    let _ = windows::Win32::System::Console::AllocConsole();
-                }
-                0
-            }
-            _ => self.group.handle(
-                self.context().with_buffer_size(size),
-                self.context_manager.as_ref(),
-                &function,
-                output,
-                size,
-                args,
-                count,
-            ),
+        let args = unsafe { Arguments::from_raw(args, count) };
+        #[cfg(feature = "tracing")]
+        let span = tracing::info_span!(
+            "call",
+            function,
+            args = args.len(),
+            code = tracing::field::Empty,
+            duration_us = tracing::field::Empty,
+            caller = tracing::field::Empty,
+            source = tracing::field::Empty,
+            mission = tracing::field::Empty,
+            server = tracing::field::Empty,
+        );
+        #[cfg(feature = "tracing")]
+        let _entered = span.enter();
+        #[cfg(feature = "tracing")]
+        let _call_span = call_context::CallSpan::new(span.clone());
+        #[cfg(feature = "tracing")]
+        let start = std::time::Instant::now();
+        let code = unsafe { diagnostics::handle(self, function, output, size, args) }
+            .unwrap_or_else(|| self.dispatch(self.context(), function, output, size, args));
+        #[cfg(feature = "tracing")]
+        {
+            span.record("code", code);
+            span.record(
+                "duration_us",
+                u64::try_from(start.elapsed().as_micros()).unwrap_or(u64::MAX),
+            );
         }
+        code
+    }
+
+    /// Call a command registered at runtime, or a command added when the extension was built
+    pub(crate) fn dispatch(
+        &self,
+        context: Context,
+        function: &str,
+        output: *mut libc::c_char,
+        size: libc::size_t,
+        args: Arguments<'_>,
+    ) -> libc::c_int {
+        let acm = self.context_manager.as_ref();
+        let context = context.with_buffer_size(size);
+        self.registry
+            .handle(context, acm, function, output, size, args)
+            .unwrap_or_else(|context| {
+                self.group
+                    .handle(context, acm, function, output, size, args)
+            })
+    }
+
+    /// Answer a request sent with [`Context::request`], called by Arma through the `::reply` command
+    fn reply(&self, id: &str, value: &str) -> std::result::Result<(), String> {
+        let id = u64::from_arma(id.to_string()).map_err(|e| format!("invalid request id: {e}"))?;
+        let value =
+            Value::from_arma(value.to_string()).map_err(|e| format!("invalid reply: {e}"))?;
+        if self.bridge.reply(id, value) {
+            Ok(())
+        } else {
+            Err(format!("request {id} is not pending"))
+        }
+    }
+
+    /// Query or cancel a job, called by Arma through the `::job_status` and `::job_cancel` commands
+    fn job_command(&self, cancel: bool, id: &str) -> std::result::Result<Value, String> {
+        let id = job::JobId::new(
+            u64::from_arma(id.to_string()).map_err(|e| format!("invalid job id: {e}"))?,
+        );
+        let info = self
+            .jobs
+            .job(id)
+            .ok_or_else(|| format!("job {id} not found"))?;
+        if cancel {
+            Ok(Value::Boolean(self.jobs.cancel(id)))
+        } else {
+            Ok(info.to_arma())
+        }
+    }
+
+    #[cfg(feature = "serde")]
+    /// Save all persistent state values to disk.
+    /// Also available to Arma through the `::save_state` command.
+    /// # Errors
+    /// Returns the first error encountered, the remaining values are still saved.
+    pub fn save_state(&self) -> std::result::Result<(), persist::PersistError> {
+        self.persistence.save(&self.group.state)
+    }
+
+    #[cfg(feature = "config")]
+    /// Reload all configuration files, calling the `on_config_change` hooks if any of them changed.
+    /// Also available to Arma through the `::reload_config` command.
+    /// # Errors
+    /// Returns an error if a file could not be read or parsed, the previous configuration is kept.
+    pub fn reload_config(&self) -> std::result::Result<bool, config::ConfigError> {
+        self.configs.reload(&self.group.state, || self.context())
     }
 
     #[must_use]
@@ -198,70 +448,194 @@ impl Extension {
 
     #[doc(hidden)]
     /// Called by generated code, do not call directly.
-    pub fn run_callbacks(&mut self) {
-        let callback = self.callback;
+    pub fn run_callbacks(&self) {
+        let callback = *self
+            .callback
+            .lock()
+            .unwrap_or_else(std::sync::PoisonError::into_inner);
+        let mut callback_thread = self
+            .callback_thread
+            .lock()
+            .unwrap_or_else(std::sync::PoisonError::into_inner);
+        if callback_thread.is_some() {
+            return;
+        }
         let (_, rx) = self.callback_channel.clone();
-        self.callback_thread = Some(std::thread::spawn(move || {
+        let queue = self.callback_queue.clone();
+        let (done_tx, done_rx) = bounded::<()>(0);
+        let thread = std::thread::spawn(move || {
+            let _done = done_tx;
+            callback::mark_delivery_thread();
             while let Ok(CallbackMessage::Call(name, func, data)) = rx.recv() {
                 if let Some(c) = callback {
+                    #[cfg(feature = "tracing")]
+                    let event = (name.clone(), func.clone(), data.is_some());
                     let Ok(name) = std::ffi::CString::new(name) else {
                         error!("callback name was not valid");
                         continue;
                     };
-                    let Ok(func) = std::ffi::CString::new(func) else {
+                    if func.contains('\0') {
                         error!("callback func was not valid");
                         continue;
-                    };
-                    let Ok(data) =
-                        std::ffi::CString::new(data.map_or_else(
-                            String::new,
-                            |value| match value {
-                                Value::String(s) => s,
-                                v => v.to_string(),
-                            },
-                        ))
-                    else {
+                    }
+                    let data = data.map_or_else(String::new, |value| match value {
+                        Value::String(s) => s,
+                        v => v.to_string(),
+                    });
+                    if data.contains('\0') {
                         error!("callback data was not valid");
                         continue;
-                    };
+                    }
 
-                    let (name, func, data) = (name.into_raw(), func.into_raw(), data.into_raw());
-                    loop {
-                        if c(name, func, data) >= 0 {
-                            break;
+                    // Data larger than the chunk size is sent as multiple fragments
+                    let mut delivered = true;
+                    for (func, data) in queue.split(func, data) {
+                        let (Ok(func), Ok(data)) =
+                            (std::ffi::CString::new(func), std::ffi::CString::new(data))
+                        else {
+                            delivered = false;
+                            continue;
+                        };
+                        // Arma returns a negative value while its callback queue is full
+                        if !queue.deliver(|| c(name.as_ptr(), func.as_ptr(), data.as_ptr()) >= 0) {
+                            warn!("callback was not accepted by Arma and has been dropped");
+                            delivered = false;
                         }
-                        std::thread::sleep(std::time::Duration::from_millis(1));
                     }
-                    unsafe {
-                        drop(std::ffi::CString::from_raw(name));
-                        drop(std::ffi::CString::from_raw(func));
-                        drop(std::ffi::CString::from_raw(data));
+                    #[cfg(feature = "tracing")]
+                    {
+                        let (name, func, data) = event;
+                        tracing::debug!(name, func, data, delivered, "arma_rs::callback");
                     }
+                    #[cfg(not(feature = "tracing"))]
+                    let _ = delivered;
+                }
+            }
+        });
+        *callback_thread = Some((thread, done_rx));
+        drop(callback_thread);
+        self.callback_queue.set_running(true);
+        let queue = self.callback_queue.clone();
+        let bridge = self.bridge.clone();
+        let pool = self.pool.clone();
+        let bus = self.bus.clone();
+        let version = self.version.clone();
+        let state = self.group.state.clone();
+        self.scheduler.start(move || {
+            Context::new(
+                queue.clone(),
+                bridge.clone(),
+                pool.clone(),
+                bus.clone(),
+                GlobalContext::new(version.clone(), state.clone()),
+                GroupContext::new(state.clone()),
+            )
+        });
+        for hook in self.lifecycle.take_on_callback_registered() {
+            hook(self.context());
+        }
+    }
+
+    /// Shuts the extension down, called by generated code when the process exits.
+    /// Runs the `on_shutdown` hooks, saves persistent state and sends any queued callbacks,
+    /// waiting up to 5 seconds for each background thread to finish.
+    ///
+    /// Calling this more than once has no effect.
+    pub fn shutdown(&self) {
+        self.shutdown_with(true);
+    }
+
+    #[doc(hidden)]
+    /// Called by generated code when the library is unloaded on Windows, do not call directly.
+    /// Runs while the library is being detached, after the OS has stopped the other threads of the extension,
+    /// so no thread is joined. The `on_shutdown` hooks still run and must not wait on other threads,
+    /// persistent state is saved unless a save was interrupted and queued callbacks are dropped.
+    pub fn shutdown_detached(&self) {
+        self.shutdown_with(false);
+    }
+
+    fn shutdown_with(&self, wait: bool) {
+        for hook in self.lifecycle.take_on_shutdown() {
+            hook(self.context());
+        }
+        self.bridge.close();
+        self.jobs.cancel_all();
+        self.scheduler.stop(wait);
+        self.pool.shutdown(wait);
+        #[cfg(feature = "serde")]
+        {
+            let saver = self
+                .persist_saver
+                .lock()
+                .unwrap_or_else(std::sync::PoisonError::into_inner)
+                .take();
+            if let Some(saver) = saver {
+                if wait {
+                    saver.stop();
+                    let _ = self.save_state();
+                } else {
+                    saver.detach();
+                    let _ = self.persistence.try_save(&self.group.state);
+                }
+            }
+        }
+        let callback_thread = self
+            .callback_thread
+            .lock()
+            .unwrap_or_else(std::sync::PoisonError::into_inner)
+            .take();
+        if let Some((thread, done)) = callback_thread {
+            self.callback_queue.set_running(false);
+            let (tx, _) = &self.callback_channel;
+            if !wait {
+                let _ = tx.try_send(CallbackMessage::Terminate);
+                return;
+            }
+            if tx
+                .send_timeout(CallbackMessage::Terminate, SHUTDOWN_TIMEOUT)
+                .is_err()
+            {
+                warn!("callback thread did not accept the shutdown before the timeout");
+                return;
+            }
+            // The thread may have been killed by the OS, only join it once it has signaled it finished
+            match done.recv_timeout(SHUTDOWN_TIMEOUT) {
+                Err(crossbeam_channel::RecvTimeoutError::Disconnected) => {
+                    thread.join().expect("Failed to join callback thread");
                 }
+                _ => warn!("callback thread did not finish before shutdown"),
             }
-        }));
+        }
     }
 }
 
 #[cfg(feature = "extension")]
 impl Drop for Extension {
-    // Never called when loaded by arma, instead this is purely required for rust testing.
+    // Never called when loaded by arma, the generated code calls `shutdown` instead.
+    // This is purely required for rust testing.
     fn drop(&mut self) {
-        if let Some(thread) = self.callback_thread.take() {
-            let (tx, _) = &self.callback_channel;
-            tx.send(CallbackMessage::Terminate)
-                .expect("Failed to send terminate message to callback thread");
-            thread.join().expect("Failed to join callback thread");
-        }
+        self.shutdown();
     }
 }
 
 /// Used to build an extension.
 #[cfg(feature = "extension")]
 pub struct ExtensionBuilder {
-    version: String,
+    version: Option<String>,
     group: Group,
     allow_no_args: bool,
+    case_insensitive: bool,
+    freeze_state: bool,
+    lifecycle: lifecycle::Lifecycle,
+    logging: Option<logger::LogConfig>,
+    callback: callback::CallbackConfig,
+    scheduler: schedule::Scheduler,
+    pool: pool::PoolConfig,
+    diagnostics: diagnostics::Diagnostics,
+    #[cfg(feature = "serde")]
+    persistence: persist::Persistence,
+    #[cfg(feature = "config")]
+    configs: config::Configs,
 }
 
 #[cfg(feature = "extension")]
@@ -269,8 +643,9 @@ impl ExtensionBuilder {
     #[inline]
     #[must_use]
     /// Sets the version of the extension.
+    /// Defaults to the version given to the [`arma`] attribute, which is the crate version unless set.
     pub fn version(mut self, version: String) -> Self {
-        self.version = version;
+        self.version = Some(version);
         self
     }
 
@@ -285,6 +660,22 @@ impl ExtensionBuilder {
         self
     }
 
+    #[inline]
+    #[must_use]
+    /// Add a version of the extension's API, called using `v[version]:[command]`, see [`Group::version`].
+    pub fn versioned(mut self, version: u32, group: Group) -> Self {
+        self.group = self.group.version(version, group);
+        self
+    }
+
+    #[inline]
+    #[must_use]
+    /// Sets the version used for paths that do not start with a version, see [`Group::default_version`].
+    pub fn default_version(mut self, version: u32) -> Self {
+        self.group = self.group.default_version(version);
+        self
+    }
+
     #[inline]
     #[must_use]
     /// Add a new state value to the extension if it has not be added already
@@ -299,8 +690,32 @@ impl ExtensionBuilder {
     #[inline]
     #[must_use]
     /// Freeze the extension's state, preventing the state from changing, allowing for faster reads
-    pub fn freeze_state(mut self) -> Self {
-        self.group = self.group.freeze_state();
+    pub const fn freeze_state(mut self) -> Self {
+        self.freeze_state = true;
+        self
+    }
+
+    #[cfg(feature = "serde")]
+    #[inline]
+    #[must_use]
+    /// Add a state value that is persisted to disk at `path`.
+    /// The value is loaded when the extension is built, falling back to `T::default()` if the file does not exist.
+    /// A file that cannot be read is moved to `<path>.corrupt` and `T::default()` is used instead.
+    /// It is saved periodically, when `::save_state` is called and when the extension shuts down.
+    pub fn persistent_state<T>(mut self, path: impl Into<std::path::PathBuf>) -> Self
+    where
+        T: serde::Serialize + serde::de::DeserializeOwned + Default + Send + Sync + 'static,
+    {
+        self.persistence.add::<T>(path.into());
+        self
+    }
+
+    #[cfg(feature = "serde")]
+    #[inline]
+    #[must_use]
+    /// Sets how often persistent state is saved, defaults to [`persist::DEFAULT_INTERVAL`].
+    pub const fn persist_interval(mut self, interval: std::time::Duration) -> Self {
+        self.persistence.set_interval(interval);
         self
     }
 
@@ -316,18 +731,251 @@ impl ExtensionBuilder {
         self
     }
 
+    #[inline]
+    #[must_use]
+    /// Match the names of commands and groups without regard to ASCII case,
+    /// so `"my_ext" callExtension "Group:Command"` calls `group:command`.
+    /// Commands registered at runtime are matched the same way.
+    /// # Panics
+    /// The extension panics when it is built if two names in a group only differ in case.
+    pub const fn case_insensitive(mut self) -> Self {
+        self.case_insensitive = true;
+        self
+    }
+
+    #[cfg(feature = "config")]
+    #[inline]
+    #[must_use]
+    /// Load a configuration file from next to the extension into the global state as [`config::Config<T>`].
+    /// The first of `<name>.toml` and `<name>.json` that exists is used, see [`module::module_name`].
+    /// If neither exists `T::default()` is used.
+    pub fn config<T>(mut self) -> Self
+    where
+        T: serde::de::DeserializeOwned + Default + Send + Sync + 'static,
+    {
+        self.configs.add::<T>(None);
+        self
+    }
+
+    #[cfg(feature = "config")]
+    #[inline]
+    #[must_use]
+    /// Load a configuration file from `path` into the global state as [`config::Config<T>`].
+    /// Files ending in `.json` are parsed as JSON, anything else as TOML.
+    pub fn config_file<T>(mut self, path: impl Into<std::path::PathBuf>) -> Self
+    where
+        T: serde::de::DeserializeOwned + Default + Send + Sync + 'static,
+    {
+        self.configs.add::<T>(Some(path.into()));
+        self
+    }
+
+    #[cfg(feature = "config")]
+    #[inline]
+    #[must_use]
+    /// Add a hook that is called when a reload changed the configuration.
+    pub fn on_config_change<F>(mut self, hook: F) -> Self
+    where
+        F: Fn(Context) + Send + Sync + 'static,
+    {
+        self.configs.on_change(hook);
+        self
+    }
+
+    #[inline]
+    #[must_use]
+    /// Install a logger for the [`log`] facade, writing to a rotating file and optionally forwarding to Arma.
+    /// Only the first logger installed in a process is used.
+    pub fn logging(mut self, config: logger::LogConfig) -> Self {
+        self.logging = Some(config);
+        self
+    }
+
+    #[inline]
+    #[must_use]
+    /// Configure the queue of callbacks waiting to be sent to Arma,
+    /// defaults to an unbounded queue that retries every callback until Arma accepts it.
+    pub fn callback_queue(mut self, config: callback::CallbackConfig) -> Self {
+        self.callback = config;
+        self
+    }
+
+    #[inline]
+    #[must_use]
+    /// Configure the worker pool used by [`Context::spawn`],
+    /// defaults to one thread per CPU and room for 256 queued tasks.
+    pub fn worker_pool(mut self, config: pool::PoolConfig) -> Self {
+        self.pool = config;
+        self
+    }
+
+    #[inline]
+    #[must_use]
+    /// Add a task that runs every `interval` on the scheduler thread.
+    /// The scheduler starts once Arma has registered the extension callback, the first run is one interval later.
+    /// # Panics
+    /// Panics if the interval is zero.
+    pub fn every<F>(mut self, interval: std::time::Duration, task: F) -> Self
+    where
+        F: FnMut(Context) + Send + 'static,
+    {
+        self.scheduler
+            .add(schedule::Schedule::Every(interval), task);
+        self
+    }
+
+    #[inline]
+    #[must_use]
+    /// Add a task that runs once, `delay` after Arma has registered the extension callback.
+    pub fn after<F>(mut self, delay: std::time::Duration, task: F) -> Self
+    where
+        F: FnOnce(Context) + Send + 'static,
+    {
+        let mut task = Some(task);
+        self.scheduler
+            .add(schedule::Schedule::After(delay), move |ctx| {
+                if let Some(task) = task.take() {
+                    task(ctx);
+                }
+            });
+        self
+    }
+
+    #[inline]
+    #[must_use]
+    /// Add a task that runs at the times matching a cron expression, see [`schedule::Cron`].
+    /// # Panics
+    /// Panics if the expression is not valid.
+    pub fn cron<F>(mut self, expression: &str, task: F) -> Self
+    where
+        F: FnMut(Context) + Send + 'static,
+    {
+        let cron = expression
+            .parse()
+            .unwrap_or_else(|e| panic!("invalid cron expression `{expression}`: {e}"));
+        self.scheduler.add(schedule::Schedule::Cron(cron), task);
+        self
+    }
+
+    #[inline]
+    #[must_use]
+    /// Add a hook that is called once the extension has been built and loaded.
+    pub fn on_load<F>(mut self, hook: F) -> Self
+    where
+        F: FnOnce(Context) + Send + 'static,
+    {
+        self.lifecycle.on_load(hook);
+        self
+    }
+
+    #[inline]
+    #[must_use]
+    /// Add a hook that is called once Arma has registered the extension callback.
+    /// Callbacks sent before this point are queued until the callback is registered.
+    pub fn on_callback_registered<F>(mut self, hook: F) -> Self
+    where
+        F: FnOnce(Context) + Send + 'static,
+    {
+        self.lifecycle.on_callback_registered(hook);
+        self
+    }
+
+    #[inline]
+    #[must_use]
+    /// Add a hook that is called when the extension shuts down, before queued callbacks are flushed.
+    /// See [`Extension::shutdown`].
+    pub fn on_shutdown<F>(mut self, hook: F) -> Self
+    where
+        F: FnOnce(Context) + Send + 'static,
+    {
+        self.lifecycle.on_shutdown(hook);
+        self
+    }
+
     #[inline]
     #[must_use]
     /// Add a command to the extension.
     pub fn command<S, F, I, R>(mut self, name: S, handler: F) -> Self
     where
         S: Into<String>,
-        F: Factory<I, R> + 'static,
+        F: Factory<I, R> + Send + Sync + 'static,
     {
         self.group = self.group.command(name, handler);
         self
     }
 
+    #[inline]
+    #[must_use]
+    /// Add a deprecated command to the extension, see [`Group::command_deprecated`].
+    pub fn command_deprecated<S, N, F, I, R>(mut self, name: S, note: N, handler: F) -> Self
+    where
+        S: Into<String>,
+        N: Into<String>,
+        F: Factory<I, R> + Send + Sync + 'static,
+    {
+        self.group = self.group.command_deprecated(name, note, handler);
+        self
+    }
+
+    #[inline]
+    #[must_use]
+    /// Add a command that needs the call context on every call, see [`Group::command_always_context`].
+    pub fn command_always_context<S, F, I, R>(mut self, name: S, handler: F) -> Self
+    where
+        S: Into<String>,
+        F: Factory<I, R> + Send + Sync + 'static,
+    {
+        self.group = self.group.command_always_context(name, handler);
+        self
+    }
+
+    #[inline]
+    #[must_use]
+    /// Add a diagnostic command, called from Arma using `::[name]`, see [`diagnostics`].
+    /// Access to the command is limited by [`ExtensionBuilder::diagnostic_access`].
+    /// # Panics
+    /// Panics if the name is empty, contains `:` or is used by a built-in `::` command
+    pub fn diagnostic<S, F, I, R>(mut self, name: S, handler: F) -> Self
+    where
+        S: Into<String>,
+        F: Factory<I, R> + Send + Sync + 'static,
+    {
+        self.diagnostics.add(name.into(), fn_handler(handler));
+        self
+    }
+
+    #[inline]
+    #[must_use]
+    /// Set who may call the diagnostic commands and the other `::` commands except `::reply`, every caller by default.
+    pub fn diagnostic_access(mut self, policy: diagnostics::AccessPolicy) -> Self {
+        self.diagnostics.set_policy(policy);
+        self
+    }
+
+    #[inline]
+    #[must_use]
+    /// Add another name for a command of the extension, see [`Group::alias`].
+    pub fn alias<S, T>(mut self, alias: S, command: T) -> Self
+    where
+        S: Into<String>,
+        T: Into<String>,
+    {
+        self.group = self.group.alias(alias, command);
+        self
+    }
+
+    #[inline]
+    #[must_use]
+    /// Handle calls to commands that do not exist, see [`Group::fallback`].
+    pub fn fallback<F, R>(mut self, handler: F) -> Self
+    where
+        F: Fn(Context, String, Vec<String>) -> R + Send + Sync + 'static,
+        R: IntoExtResult,
+    {
+        self.group = self.group.fallback(handler);
+        self
+    }
+
     #[inline]
     #[must_use]
     /// Builds the extension.
@@ -372,21 +1020,99 @@ impl ExtensionBuilder {
         #[cfg(debug_assertions)]
         let request_context = empty_request_context;
 
-        Extension {
-            version: self.version,
-            group: self.group.into(),
+        let metrics = std::sync::Arc::new(metrics::Metrics::default());
+        let mut bus = bus::Bus::default();
+        let mut group =
+            group::InternalGroup::new(self.group, &metrics, &mut bus, self.case_insensitive);
+        let jobs = job::JobManager::default();
+        group::set_state(&group.state, jobs.clone());
+        let registry = registry::Registry::new(
+            group.paths(),
+            metrics.clone(),
+            group
+                .feature_flags()
+                .union(self.diagnostics.feature_flags()),
+            self.case_insensitive,
+        );
+        group::set_state(&group.state, registry.clone());
+        #[cfg(feature = "serde")]
+        let persistence = std::sync::Arc::new(self.persistence);
+        #[cfg(feature = "serde")]
+        persistence.load(&group.state);
+        #[cfg(feature = "config")]
+        self.configs.load(&group.state);
+        if self.freeze_state {
+            std::sync::Arc::get_mut(&mut group.state)
+                .expect("state is not shared before the extension is built")
+                .freeze();
+        }
+        #[cfg(feature = "serde")]
+        let persist_saver = (!persistence.is_empty())
+            .then(|| persist::Saver::spawn(persistence.clone(), group.state.clone()));
+
+        let callback_channel = self.callback.channel();
+        let callback_queue = callback::CallbackQueue::new(self.callback, &callback_channel);
+        let logger = self.logging.map(|config| {
+            let logger = std::sync::Arc::new(logger::Logger::new(config, callback_queue.clone()));
+            logger.install();
+            logger
+        });
+
+        let version = self.version.map_or_else(
+            || module::attribute_version().unwrap_or("0.0.0").into(),
+            Into::into,
+        );
+        let extension = Extension {
+            version,
+            group,
             allow_no_args: self.allow_no_args,
-            callback: None,
-            callback_channel: unbounded(),
-            callback_thread: None,
-            context_manager: Rc::new(ArmaContextManager::new(request_context)),
+            callback: std::sync::Mutex::new(None),
+            callback_channel,
+            callback_queue,
+            bridge: std::sync::Arc::default(),
+            jobs,
+            scheduler: self.scheduler,
+            pool: std::sync::Arc::new(pool::WorkerPool::new(self.pool)),
+            bus: std::sync::Arc::new(bus),
+            registry,
+            callback_thread: std::sync::Mutex::new(None),
+            lifecycle: self.lifecycle,
+            logger,
+            metrics,
+            context_manager: std::sync::Arc::new(ArmaContextManager::new(request_context)),
             pre218_clear_context_override: pre218,
+            diagnostics: self.diagnostics,
+            started: std::time::Instant::now(),
+            #[cfg(feature = "serde")]
+            persistence,
+            #[cfg(feature = "serde")]
+            persist_saver: std::sync::Mutex::new(persist_saver),
+            #[cfg(feature = "config")]
+            configs: self.configs,
+        };
+        for hook in extension.lifecycle.take_on_load() {
+            hook(extension.context());
         }
+        extension
     }
 }
 
 const unsafe extern "system" fn empty_request_context() {}
 
+/// Read a C string provided by Arma, replacing invalid UTF-8 with `U+FFFD`.
+/// A null pointer is read as an empty string.
+///
+/// # Safety
+/// `ptr` must be null or point to a valid C string that outlives the returned value.
+#[cfg(feature = "extension")]
+pub(crate) unsafe fn read_cstr<'a>(ptr: *const libc::c_char) -> std::borrow::Cow<'a, str> {
+    if ptr.is_null() {
+        std::borrow::Cow::Borrowed("")
+    } else {
+        unsafe { std::ffi::CStr::from_ptr(ptr) }.to_string_lossy()
+    }
+}
+
 #[doc(hidden)]
 /// Called by generated code, do not call directly.
 ///
@@ -405,15 +1131,72 @@ pub unsafe fn write_cstr(
         return Some(0);
     }
 
-    let cstr = std::ffi::CString::new(string).ok()?;
-    let len_to_copy = cstr.as_bytes().len();
-    if len_to_copy >= buf_size {
+    let bytes = string.as_bytes();
+    if bytes.len() >= buf_size || bytes.contains(&0) {
         return None;
     }
 
-    unsafe { ptr.copy_from(cstr.as_ptr(), len_to_copy) };
-    unsafe { ptr.add(len_to_copy).write(0x00) };
-    Some(len_to_copy)
+    unsafe { ptr.copy_from(bytes.as_ptr().cast(), bytes.len()) };
+    unsafe { ptr.add(bytes.len()).write(0x00) };
+    Some(bytes.len())
+}
+
+/// Size checked writer into an output buffer provided by Arma
+#[cfg(feature = "extension")]
+struct OutputWriter {
+    ptr: *mut libc::c_char,
+    // Space left for the terminating zero byte
+    capacity: usize,
+    len: usize,
+}
+
+#[cfg(feature = "extension")]
+impl std::fmt::Write for OutputWriter {
+    fn write_str(&mut self, s: &str) -> std::fmt::Result {
+        let bytes = s.as_bytes();
+        if bytes.len() > self.capacity - self.len || bytes.contains(&0) {
+            return Err(std::fmt::Error);
+        }
+        unsafe {
+            self.ptr
+                .add(self.len)
+                .copy_from(bytes.as_ptr().cast(), bytes.len());
+        };
+        self.len += bytes.len();
+        Ok(())
+    }
+}
+
+/// Format a value directly into the output buffer, without allocating.
+///
+/// Returns `None` if the output does not fit into the buffer or contains a zero byte,
+/// the buffer then holds an empty string.
+///
+/// # Safety
+/// `ptr` must be valid for writes of `buf_size` bytes.
+#[cfg(feature = "extension")]
+pub(crate) unsafe fn write_display(
+    value: &(impl std::fmt::Display + ?Sized),
+    ptr: *mut libc::c_char,
+    buf_size: libc::size_t,
+) -> Option<libc::size_t> {
+    use std::fmt::Write;
+
+    let mut writer = OutputWriter {
+        ptr,
+        capacity: buf_size.saturating_sub(1),
+        len: 0,
+    };
+    if write!(writer, "{value}").is_err() {
+        if buf_size > 0 {
+            unsafe { ptr.write(0x00) };
+        }
+        return None;
+    }
+    if writer.len > 0 {
+        unsafe { ptr.add(writer.len).write(0x00) };
+    }
+    Some(writer.len)
 }
 
 #[cfg(all(test, feature = "extension"))]
@@ -500,6 +1283,41 @@ mod tests {
         assert_eq!(buf, [0; BUF_SIZE]);
     }
 
+    #[test]
+    fn write_display_value() {
+        const BUF_SIZE: libc::size_t = 16;
+        let mut buf = [0; BUF_SIZE];
+        let value = Value::Array(vec![
+            Value::Number(1.0),
+            Value::String(String::from("a\"b")),
+        ]);
+        let result = unsafe { write_display(&value, buf.as_mut_ptr(), BUF_SIZE) };
+
+        assert_eq!(result, Some(10));
+        assert_eq!(buf[..11], (b"[1,\"a\"\"b\"]\0").map(u8::cast_signed));
+    }
+
+    #[test]
+    fn write_display_overflow() {
+        const BUF_SIZE: libc::size_t = 7;
+        let mut buf = (b"zzzzzz\0").map(u8::cast_signed);
+        let value = Value::Array(vec![Value::Number(1.0), Value::Number(2.0), Value::Null]);
+        let result = unsafe { write_display(&value, buf.as_mut_ptr(), BUF_SIZE) };
+
+        assert_eq!(result, None);
+        assert_eq!(buf[0], 0);
+    }
+
+    #[test]
+    fn write_display_nul() {
+        const BUF_SIZE: libc::size_t = 7;
+        let mut buf = [0; BUF_SIZE];
+        let result = unsafe { write_display("a\0b", buf.as_mut_ptr(), BUF_SIZE) };
+        assert_eq!(result, None);
+        let result = unsafe { write_cstr(String::from("a\0b"), buf.as_mut_ptr(), BUF_SIZE) };
+        assert_eq!(result, None);
+    }
+
     #[test]
     fn write_overwrite() {
         const BUF_SIZE: libc::size_t = 7;
@@ -0,0 +1,57 @@
+use std::sync::Mutex;
+
+use crate::Context;
+
+pub(crate) type Hook = Box<dyn FnOnce(Context) + Send>;
+
+#[derive(Default)]
+/// Hooks called at points in the lifetime of the extension
+pub(crate) struct Lifecycle {
+    on_load: Mutex<Vec<Hook>>,
+    on_callback_registered: Mutex<Vec<Hook>>,
+    on_shutdown: Mutex<Vec<Hook>>,
+}
+
+/// Take the hooks out of `hooks`, so each hook runs at most once
+fn take(hooks: &Mutex<Vec<Hook>>) -> Vec<Hook> {
+    std::mem::take(
+        &mut *hooks
+            .lock()
+            .unwrap_or_else(std::sync::PoisonError::into_inner),
+    )
+}
+
+impl Lifecycle {
+    pub(crate) fn on_load(&mut self, hook: impl FnOnce(Context) + Send + 'static) {
+        self.on_load
+            .get_mut()
+            .unwrap_or_else(std::sync::PoisonError::into_inner)
+            .push(Box::new(hook));
+    }
+
+    pub(crate) fn on_callback_registered(&mut self, hook: impl FnOnce(Context) + Send + 'static) {
+        self.on_callback_registered
+            .get_mut()
+            .unwrap_or_else(std::sync::PoisonError::into_inner)
+            .push(Box::new(hook));
+    }
+
+    pub(crate) fn on_shutdown(&mut self, hook: impl FnOnce(Context) + Send + 'static) {
+        self.on_shutdown
+            .get_mut()
+            .unwrap_or_else(std::sync::PoisonError::into_inner)
+            .push(Box::new(hook));
+    }
+
+    pub(crate) fn take_on_load(&self) -> Vec<Hook> {
+        take(&self.on_load)
+    }
+
+    pub(crate) fn take_on_callback_registered(&self) -> Vec<Hook> {
+        take(&self.on_callback_registered)
+    }
+
+    pub(crate) fn take_on_shutdown(&self) -> Vec<Hook> {
+        take(&self.on_shutdown)
+    }
+}
@@ -0,0 +1,524 @@
+//! Logging backend for the [`log`] facade.
+
+use std::{
+    fs::{File, OpenOptions},
+    io::Write,
+    path::{Path, PathBuf},
+    sync::{
+        Arc, Mutex,
+        atomic::{AtomicUsize, Ordering},
+    },
+};
+
+use log::{LevelFilter, Log, Metadata, Record};
+
+use crate::{
+    CallbackMessage, Value,
+    callback::{CallbackQueue, on_delivery_thread},
+};
+
+/// Where the log file is written
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub enum LogLocation {
+    #[default]
+    /// Next to the extension library
+    Extension,
+    /// The directory Arma writes its RPT files to.
+    /// This is the `-profiles` directory if given, on Windows `%LOCALAPPDATA%\Arma 3` otherwise.
+    /// Falls back to [`LogLocation::Extension`] if the directory can not be determined.
+    Rpt,
+    /// A custom directory
+    Directory(PathBuf),
+}
+
+impl LogLocation {
+    fn directory(&self) -> Option<PathBuf> {
+        match self {
+            Self::Extension => crate::module::module_dir(),
+            Self::Rpt => rpt_directory().or_else(crate::module::module_dir),
+            Self::Directory(path) => Some(path.clone()),
+        }
+    }
+}
+
+fn rpt_directory() -> Option<PathBuf> {
+    if let Some(profiles) = std::env::args().find_map(|arg| {
+        arg.strip_prefix("-profiles=")
+            .map(|path| PathBuf::from(path.trim_matches('"')))
+    }) {
+        return Some(profiles);
+    }
+    if cfg!(windows) {
+        std::env::var_os("LOCALAPPDATA").map(|dir| PathBuf::from(dir).join("Arma 3"))
+    } else {
+        None
+    }
+}
+
+/// Configuration for the logger, see [`crate::ExtensionBuilder::logging`]
+#[derive(Debug, Clone)]
+pub struct LogConfig {
+    location: LogLocation,
+    file_name: Option<String>,
+    level: LevelFilter,
+    modules: Vec<(String, LevelFilter)>,
+    max_size: u64,
+    max_files: usize,
+    forward: LevelFilter,
+    callback_name: Option<String>,
+}
+
+impl Default for LogConfig {
+    fn default() -> Self {
+        Self {
+            location: LogLocation::default(),
+            file_name: None,
+            level: LevelFilter::Info,
+            modules: Vec::new(),
+            max_size: 10 * 1024 * 1024,
+            max_files: 5,
+            forward: LevelFilter::Off,
+            callback_name: None,
+        }
+    }
+}
+
+impl LogConfig {
+    #[must_use]
+    /// Create a new configuration, logging `info` and above to a file next to the extension
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    #[must_use]
+    /// Sets where the log file is written
+    pub fn location(mut self, location: LogLocation) -> Self {
+        self.location = location;
+        self
+    }
+
+    #[must_use]
+    /// Sets the name of the log file, defaults to `<name>.log`
+    pub fn file_name(mut self, file_name: impl Into<String>) -> Self {
+        self.file_name = Some(file_name.into());
+        self
+    }
+
+    #[must_use]
+    /// Sets the default level
+    pub const fn level(mut self, level: LevelFilter) -> Self {
+        self.level = level;
+        self
+    }
+
+    #[must_use]
+    /// Sets the level for a module and its children, overriding the default level
+    pub fn module(mut self, module: impl Into<String>, level: LevelFilter) -> Self {
+        self.modules.push((module.into(), level));
+        self
+    }
+
+    #[must_use]
+    /// Sets the size in bytes at which the log file is rotated
+    pub const fn max_size(mut self, max_size: u64) -> Self {
+        self.max_size = max_size;
+        self
+    }
+
+    #[must_use]
+    /// Sets how many rotated log files are kept
+    pub const fn max_files(mut self, max_files: usize) -> Self {
+        self.max_files = max_files;
+        self
+    }
+
+    #[must_use]
+    /// Forward records at or above `level` to Arma as callbacks.
+    /// The callback function is `log` and the data is `[level, target, message]`.
+    /// Records are dropped instead of waiting when the callback queue is full,
+    /// records logged by arma-rs itself are never forwarded.
+    pub const fn forward(mut self, level: LevelFilter) -> Self {
+        self.forward = level;
+        self
+    }
+
+    #[must_use]
+    /// Sets the name used for forwarded callbacks, defaults to the extension's name
+    pub fn callback_name(mut self, name: impl Into<String>) -> Self {
+        self.callback_name = Some(name.into());
+        self
+    }
+}
+
+struct LogFile {
+    path: PathBuf,
+    file: File,
+    size: u64,
+}
+
+impl LogFile {
+    fn open(path: PathBuf) -> std::io::Result<Self> {
+        if let Some(parent) = path.parent() {
+            std::fs::create_dir_all(parent)?;
+        }
+        let file = OpenOptions::new().create(true).append(true).open(&path)?;
+        let size = file.metadata()?.len();
+        Ok(Self { path, file, size })
+    }
+
+    fn write(&mut self, line: &str, max_size: u64, max_files: usize) -> std::io::Result<()> {
+        if self.size > 0 && self.size + line.len() as u64 > max_size {
+            self.rotate(max_files)?;
+        }
+        self.file.write_all(line.as_bytes())?;
+        self.size += line.len() as u64;
+        Ok(())
+    }
+
+    /// Shift `name.log.N` to `name.log.N+1`, dropping the oldest, and start a new file
+    fn rotate(&mut self, max_files: usize) -> std::io::Result<()> {
+        self.file.flush()?;
+        if max_files == 0 {
+            self.file = File::create(&self.path)?;
+        } else {
+            let _ = std::fs::remove_file(rotated(&self.path, max_files));
+            for i in (1..max_files).rev() {
+                let from = rotated(&self.path, i);
+                if from.exists() {
+                    std::fs::rename(from, rotated(&self.path, i + 1))?;
+                }
+            }
+            std::fs::rename(&self.path, rotated(&self.path, 1))?;
+            self.file = File::create(&self.path)?;
+        }
+        self.size = 0;
+        Ok(())
+    }
+}
+
+fn rotated(path: &Path, index: usize) -> PathBuf {
+    let mut name = path.as_os_str().to_owned();
+    name.push(format!(".{index}"));
+    PathBuf::from(name)
+}
+
+/// Logger installed by [`crate::ExtensionBuilder::logging`]
+pub struct Logger {
+    level: AtomicUsize,
+    modules: Vec<(String, LevelFilter)>,
+    max_size: u64,
+    max_files: usize,
+    file: Option<Mutex<LogFile>>,
+    forward: LevelFilter,
+    callback_name: String,
+    callback_queue: CallbackQueue,
+}
+
+impl Logger {
+    pub(crate) fn new(config: LogConfig, callback_queue: CallbackQueue) -> Self {
+        let name = crate::module::module_name().unwrap_or_else(|| String::from("arma_rs"));
+        let file = config.location.directory().and_then(|dir| {
+            let path = dir.join(config.file_name.unwrap_or_else(|| format!("{name}.log")));
+            LogFile::open(path)
+                .map_err(|e| eprintln!("arma-rs: failed to open log file: {e}"))
+                .ok()
+        });
+        let mut modules = config.modules;
+        // Most specific module first
+        modules.sort_by_key(|(module, _)| std::cmp::Reverse(module.len()));
+        Self {
+            level: AtomicUsize::new(config.level as usize),
+            modules,
+            max_size: config.max_size,
+            max_files: config.max_files,
+            file: file.map(Mutex::new),
+            forward: config.forward,
+            callback_name: config.callback_name.unwrap_or(name),
+            callback_queue,
+        }
+    }
+
+    /// Install the logger for the [`log`] facade.
+    /// Only the first logger installed in a process is used.
+    pub(crate) fn install(self: &Arc<Self>) {
+        struct Installed(Arc<Logger>);
+        impl Log for Installed {
+            fn enabled(&self, metadata: &Metadata) -> bool {
+                self.0.enabled(metadata)
+            }
+            fn log(&self, record: &Record) {
+                self.0.log(record);
+            }
+            fn flush(&self) {
+                self.0.flush();
+            }
+        }
+
+        if log::set_boxed_logger(Box::new(Installed(self.clone()))).is_ok() {
+            log::set_max_level(self.max_level());
+        } else {
+            warn!("a logger was already installed");
+        }
+    }
+
+    #[must_use]
+    /// The default level
+    pub fn level(&self) -> LevelFilter {
+        level_from_usize(self.level.load(Ordering::Relaxed))
+    }
+
+    /// Change the default level
+    pub fn set_level(&self, level: LevelFilter) {
+        self.level.store(level as usize, Ordering::Relaxed);
+        log::set_max_level(self.max_level());
+    }
+
+    fn max_level(&self) -> LevelFilter {
+        self.modules
+            .iter()
+            .map(|(_, level)| *level)
+            .chain([self.level(), self.forward])
+            .max()
+            .unwrap_or(LevelFilter::Off)
+    }
+
+    fn level_for(&self, target: &str) -> LevelFilter {
+        self.modules
+            .iter()
+            .find(|(module, _)| {
+                target
+                    .strip_prefix(module.as_str())
+                    .is_some_and(|rest| rest.is_empty() || rest.starts_with("::"))
+            })
+            .map_or_else(|| self.level(), |(_, level)| *level)
+    }
+}
+
+impl Log for Logger {
+    fn enabled(&self, metadata: &Metadata) -> bool {
+        metadata.level() <= self.level_for(metadata.target()) || metadata.level() <= self.forward
+    }
+
+    fn log(&self, record: &Record) {
+        if let Some(file) = &self.file
+            && record.level() <= self.level_for(record.target())
+        {
+            let line = format!(
+                "{} [{}] {}: {}\n",
+                timestamp(std::time::SystemTime::now()),
+                record.level(),
+                record.target(),
+                record.args()
+            );
+            let mut file = file
+                .lock()
+                .unwrap_or_else(std::sync::PoisonError::into_inner);
+            if let Err(e) = file.write(&line, self.max_size, self.max_files) {
+                eprintln!("arma-rs: failed to write log file: {e}");
+            }
+        }
+        // Records about callbacks would queue more callbacks, and a full queue must not stall the caller
+        if record.level() <= self.forward && !is_own(record.target()) && !on_delivery_thread() {
+            let _ = self.callback_queue.try_send(CallbackMessage::Call(
+                self.callback_name.clone(),
+                String::from("log"),
+                Some(Value::Array(vec![
+                    Value::String(record.level().to_string()),
+                    Value::String(record.target().to_string()),
+                    Value::String(record.args().to_string()),
+                ])),
+            ));
+        }
+    }
+
+    fn flush(&self) {
+        if let Some(file) = &self.file {
+            let _ = file
+                .lock()
+                .unwrap_or_else(std::sync::PoisonError::into_inner)
+                .file
+                .flush();
+        }
+    }
+}
+
+fn level_from_usize(level: usize) -> LevelFilter {
+    LevelFilter::iter()
+        .find(|l| *l as usize == level)
+        .unwrap_or(LevelFilter::Off)
+}
+
+/// Whether the record was logged by arma-rs itself
+fn is_own(target: &str) -> bool {
+    target == "arma_rs" || target.starts_with("arma_rs::")
+}
+
+/// Format a time as `YYYY-MM-DD HH:MM:SS.mmm` in UTC
+fn timestamp(time: std::time::SystemTime) -> String {
+    let since_epoch = time
+        .duration_since(std::time::UNIX_EPOCH)
+        .unwrap_or_default();
+    let secs = since_epoch.as_secs();
+    let (days, secs_of_day) = (secs / 86400, secs % 86400);
+    // Civil from days, http://howardhinnant.github.io/date_algorithms.html
+    let z = days.cast_signed() + 719_468;
+    let era = z.div_euclid(146_097);
+    let doe = z.rem_euclid(146_097);
+    let yoe = (doe - doe / 1460 + doe / 36524 - doe / 146_096) / 365;
+    let doy = doe - (365 * yoe + yoe / 4 - yoe / 100);
+    let mp = (5 * doy + 2) / 153;
+    let day = doy - (153 * mp + 2) / 5 + 1;
+    let month = if mp < 10 { mp + 3 } else { mp - 9 };
+    let year = yoe + era * 400 + i64::from(month <= 2);
+    format!(
+        "{year:04}-{month:02}-{day:02} {:02}:{:02}:{:02}.{:03}",
+        secs_of_day / 3600,
+        secs_of_day % 3600 / 60,
+        secs_of_day % 60,
+        since_epoch.subsec_millis()
+    )
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::callback::{CallbackConfig, OverflowPolicy};
+    use crossbeam_channel::unbounded;
+    use log::Level;
+
+    fn temp_dir(name: &str) -> PathBuf {
+        std::env::temp_dir().join(format!("arma-rs-logger-{}-{name}", std::process::id()))
+    }
+
+    fn log(logger: &Logger, level: Level, target: &str, message: &str) {
+        logger.log(
+            &Record::builder()
+                .level(level)
+                .target(target)
+                .args(format_args!("{message}"))
+                .build(),
+        );
+    }
+
+    #[test]
+    fn timestamp_format() {
+        let time = std::time::UNIX_EPOCH + std::time::Duration::from_millis(1_700_000_000_123);
+        assert_eq!(timestamp(time), "2023-11-14 22:13:20.123");
+        assert_eq!(timestamp(std::time::UNIX_EPOCH), "1970-01-01 00:00:00.000");
+    }
+
+    #[test]
+    fn module_levels() {
+        let (tx, _) = unbounded();
+        let logger = Logger::new(
+            LogConfig::new()
+                .location(LogLocation::Directory(temp_dir("levels")))
+                .level(LevelFilter::Warn)
+                .module("my_ext::db", LevelFilter::Trace)
+                .module("my_ext", LevelFilter::Info),
+            tx.into(),
+        );
+        assert_eq!(logger.level_for("other"), LevelFilter::Warn);
+        assert_eq!(logger.level_for("my_ext"), LevelFilter::Info);
+        assert_eq!(logger.level_for("my_ext::api"), LevelFilter::Info);
+        assert_eq!(logger.level_for("my_ext::db::pool"), LevelFilter::Trace);
+        assert_eq!(logger.level_for("my_ext_other"), LevelFilter::Warn);
+
+        logger.set_level(LevelFilter::Error);
+        assert_eq!(logger.level(), LevelFilter::Error);
+        assert_eq!(logger.level_for("other"), LevelFilter::Error);
+    }
+
+    #[test]
+    fn rotate() {
+        let dir = temp_dir("rotate");
+        let _ = std::fs::remove_dir_all(&dir);
+        let (tx, _) = unbounded();
+        let logger = Logger::new(
+            LogConfig::new()
+                .location(LogLocation::Directory(dir.clone()))
+                .file_name("test.log")
+                .max_size(100)
+                .max_files(2),
+            tx.into(),
+        );
+        for i in 0..10 {
+            log(&logger, Level::Info, "test", &format!("message {i}"));
+        }
+        log(&logger, Level::Debug, "test", "filtered");
+        logger.flush();
+
+        let current = std::fs::read_to_string(dir.join("test.log")).unwrap();
+        assert!(current.ends_with("[INFO] test: message 9\n"));
+        assert!(dir.join("test.log.1").exists());
+        assert!(dir.join("test.log.2").exists());
+        assert!(!dir.join("test.log.3").exists());
+        for file in ["test.log", "test.log.1", "test.log.2"] {
+            let contents = std::fs::read_to_string(dir.join(file)).unwrap();
+            assert!(contents.len() <= 100);
+            assert!(!contents.contains("filtered"));
+        }
+    }
+
+    #[test]
+    fn forward() {
+        let (tx, rx) = unbounded();
+        let logger = Logger::new(
+            LogConfig::new()
+                .location(LogLocation::Directory(temp_dir("forward")))
+                .forward(LevelFilter::Warn)
+                .callback_name("my_ext"),
+            tx.into(),
+        );
+        log(&logger, Level::Info, "test", "not forwarded");
+        log(&logger, Level::Error, "test", "forwarded");
+        let messages = rx.try_iter().collect::<Vec<_>>();
+        assert_eq!(messages.len(), 1);
+        let CallbackMessage::Call(name, func, data) = &messages[0] else {
+            panic!("expected a call");
+        };
+        assert_eq!(name, "my_ext");
+        assert_eq!(func, "log");
+        assert_eq!(
+            data.as_ref().unwrap().to_string(),
+            r#"["ERROR","test","forwarded"]"#
+        );
+    }
+
+    #[test]
+    fn forward_never_blocks() {
+        let config = CallbackConfig::new()
+            .capacity(1)
+            .policy(OverflowPolicy::Block);
+        let channel = config.channel();
+        let queue = CallbackQueue::new(config, &channel);
+        queue.set_running(true);
+        let logger = Logger::new(
+            LogConfig::new()
+                .location(LogLocation::Directory(temp_dir("forward_never_blocks")))
+                .forward(LevelFilter::Warn),
+            queue.clone(),
+        );
+        log(&logger, Level::Warn, "arma_rs", "not forwarded");
+        log(&logger, Level::Warn, "arma_rs::callback", "not forwarded");
+        log(&logger, Level::Warn, "test", "forwarded");
+        let start = std::time::Instant::now();
+        log(&logger, Level::Warn, "test", "dropped");
+        assert!(start.elapsed() < std::time::Duration::from_millis(50));
+        assert_eq!(queue.stats().dropped(), 1);
+        let messages = channel.1.try_iter().collect::<Vec<_>>();
+        assert_eq!(messages.len(), 1);
+        let CallbackMessage::Call(_, _, data) = &messages[0] else {
+            panic!("expected a call");
+        };
+        assert!(data.as_ref().unwrap().to_string().contains("forwarded"));
+
+        std::thread::spawn(move || {
+            crate::callback::mark_delivery_thread();
+            log(&logger, Level::Warn, "test", "not forwarded");
+        })
+        .join()
+        .unwrap();
+        assert!(channel.1.is_empty());
+    }
+}
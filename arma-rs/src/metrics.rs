@@ -0,0 +1,472 @@
+//! Per-command metrics collected by the dispatcher.
+
+use std::{
+    collections::BTreeMap,
+    sync::{
+        Arc, Mutex, OnceLock, RwLock,
+        atomic::{AtomicU64, Ordering},
+    },
+    time::Duration,
+};
+
+use crate::{IntoArma, Value, command::error_prefix};
+
+/// Upper bounds of the latency histogram buckets, a final bucket catches everything slower
+pub const LATENCY_BUCKETS: [Duration; 12] = [
+    Duration::from_micros(10),
+    Duration::from_micros(50),
+    Duration::from_micros(100),
+    Duration::from_micros(500),
+    Duration::from_millis(1),
+    Duration::from_millis(5),
+    Duration::from_millis(10),
+    Duration::from_millis(50),
+    Duration::from_millis(100),
+    Duration::from_millis(500),
+    Duration::from_secs(1),
+    Duration::from_secs(5),
+];
+
+#[derive(Default)]
+/// Counters for a single command, updated on every call
+pub(crate) struct CommandMetrics {
+    calls: AtomicU64,
+    errors: Mutex<BTreeMap<libc::c_int, u64>>,
+    parse_failures: AtomicU64,
+    overflows: AtomicU64,
+    latency: [AtomicU64; LATENCY_BUCKETS.len() + 1],
+    latency_sum_us: AtomicU64,
+    deprecated: OnceLock<String>,
+}
+
+impl CommandMetrics {
+    /// Mark the command as deprecated, reported in its statistics
+    pub(crate) fn deprecate(&self, note: String) {
+        let _ = self.deprecated.set(note);
+    }
+
+    /// Record the result code and duration of a call
+    pub(crate) fn record(&self, code: libc::c_int, elapsed: Duration) {
+        self.calls.fetch_add(1, Ordering::Relaxed);
+        let bucket = LATENCY_BUCKETS
+            .iter()
+            .position(|bound| elapsed <= *bound)
+            .unwrap_or(LATENCY_BUCKETS.len());
+        self.latency[bucket].fetch_add(1, Ordering::Relaxed);
+        self.latency_sum_us.fetch_add(
+            u64::try_from(elapsed.as_micros()).unwrap_or(u64::MAX),
+            Ordering::Relaxed,
+        );
+        if code == 0 {
+            return;
+        }
+        match code {
+            4 => {
+                self.overflows.fetch_add(1, Ordering::Relaxed);
+            }
+            _ if error_prefix(code) == 3 => {
+                self.parse_failures.fetch_add(1, Ordering::Relaxed);
+            }
+            _ => {}
+        }
+        *self
+            .errors
+            .lock()
+            .unwrap_or_else(std::sync::PoisonError::into_inner)
+            .entry(code)
+            .or_default() += 1;
+    }
+
+    fn snapshot(&self, name: &str) -> CommandStats {
+        let buckets = LATENCY_BUCKETS
+            .iter()
+            .copied()
+            .chain(std::iter::once(Duration::MAX))
+            .zip(&self.latency)
+            .map(|(bound, count)| (bound, count.load(Ordering::Relaxed)))
+            .collect();
+        CommandStats {
+            name: name.to_string(),
+            calls: self.calls.load(Ordering::Relaxed),
+            errors: self
+                .errors
+                .lock()
+                .unwrap_or_else(std::sync::PoisonError::into_inner)
+                .clone(),
+            parse_failures: self.parse_failures.load(Ordering::Relaxed),
+            overflows: self.overflows.load(Ordering::Relaxed),
+            latency: Histogram {
+                buckets,
+                sum: Duration::from_micros(self.latency_sum_us.load(Ordering::Relaxed)),
+            },
+            deprecated: self.deprecated.get().cloned(),
+        }
+    }
+}
+
+#[derive(Default)]
+/// Metrics for every command registered on the extension.
+/// Available through [`crate::Extension::metrics`] and the `::stats` command.
+pub struct Metrics {
+    commands: RwLock<BTreeMap<String, Arc<CommandMetrics>>>,
+    unknown: AtomicU64,
+}
+
+impl Metrics {
+    /// Get the counters for a command, creating them if needed
+    pub(crate) fn register(&self, name: String) -> Arc<CommandMetrics> {
+        self.commands
+            .write()
+            .unwrap_or_else(std::sync::PoisonError::into_inner)
+            .entry(name)
+            .or_default()
+            .clone()
+    }
+
+    pub(crate) fn record_unknown(&self) {
+        self.unknown.fetch_add(1, Ordering::Relaxed);
+    }
+
+    #[must_use]
+    /// Number of calls to commands that do not exist
+    pub fn unknown_commands(&self) -> u64 {
+        self.unknown.load(Ordering::Relaxed)
+    }
+
+    #[must_use]
+    /// Statistics of a single command, `name` is the full path such as `group:command`
+    pub fn command(&self, name: &str) -> Option<CommandStats> {
+        self.commands
+            .read()
+            .unwrap_or_else(std::sync::PoisonError::into_inner)
+            .get(name)
+            .map(|metrics| metrics.snapshot(name))
+    }
+
+    #[must_use]
+    /// Statistics of every command, sorted by name
+    pub fn commands(&self) -> Vec<CommandStats> {
+        self.commands
+            .read()
+            .unwrap_or_else(std::sync::PoisonError::into_inner)
+            .iter()
+            .map(|(name, metrics)| metrics.snapshot(name))
+            .collect()
+    }
+
+    #[cfg(feature = "prometheus")]
+    #[must_use]
+    /// Export the metrics in the Prometheus text format
+    pub fn to_prometheus(&self) -> String {
+        use std::fmt::Write;
+
+        let commands = self
+            .commands()
+            .into_iter()
+            .map(|stats| (escape_label(&stats.name), stats))
+            .collect::<Vec<_>>();
+        let mut out = String::new();
+        let _ = writeln!(
+            out,
+            "# HELP arma_rs_calls_total Calls per command.\n# TYPE arma_rs_calls_total counter"
+        );
+        for (name, stats) in &commands {
+            let _ = writeln!(
+                out,
+                "arma_rs_calls_total{{command=\"{name}\"}} {}",
+                stats.calls
+            );
+        }
+        let _ = writeln!(
+            out,
+            "# HELP arma_rs_errors_total Failed calls per command and result code.\n# TYPE arma_rs_errors_total counter"
+        );
+        for (name, stats) in &commands {
+            for (code, count) in &stats.errors {
+                let _ = writeln!(
+                    out,
+                    "arma_rs_errors_total{{command=\"{name}\",code=\"{code}\"}} {count}"
+                );
+            }
+        }
+        let _ = writeln!(
+            out,
+            "# HELP arma_rs_parse_failures_total Arguments that could not be parsed per command.\n# TYPE arma_rs_parse_failures_total counter"
+        );
+        for (name, stats) in &commands {
+            let _ = writeln!(
+                out,
+                "arma_rs_parse_failures_total{{command=\"{name}\"}} {}",
+                stats.parse_failures
+            );
+        }
+        let _ = writeln!(
+            out,
+            "# HELP arma_rs_overflows_total Outputs larger than the buffer per command.\n# TYPE arma_rs_overflows_total counter"
+        );
+        for (name, stats) in &commands {
+            let _ = writeln!(
+                out,
+                "arma_rs_overflows_total{{command=\"{name}\"}} {}",
+                stats.overflows
+            );
+        }
+        let _ = writeln!(
+            out,
+            "# HELP arma_rs_call_duration_seconds Duration of calls per command.\n# TYPE arma_rs_call_duration_seconds histogram"
+        );
+        for (name, stats) in &commands {
+            let mut cumulative = 0;
+            for (bound, count) in &stats.latency.buckets {
+                cumulative += count;
+                let le = if *bound == Duration::MAX {
+                    String::from("+Inf")
+                } else {
+                    bound.as_secs_f64().to_string()
+                };
+                let _ = writeln!(
+                    out,
+                    "arma_rs_call_duration_seconds_bucket{{command=\"{name}\",le=\"{le}\"}} {cumulative}"
+                );
+            }
+            let _ = writeln!(
+                out,
+                "arma_rs_call_duration_seconds_sum{{command=\"{name}\"}} {}\narma_rs_call_duration_seconds_count{{command=\"{name}\"}} {}",
+                stats.latency.sum.as_secs_f64(),
+                stats.latency.count()
+            );
+        }
+        let _ = writeln!(
+            out,
+            "# HELP arma_rs_unknown_commands_total Calls to commands that do not exist.\n# TYPE arma_rs_unknown_commands_total counter\narma_rs_unknown_commands_total {}",
+            self.unknown_commands()
+        );
+        out
+    }
+}
+
+#[cfg(feature = "prometheus")]
+/// Escape a label value for the Prometheus text format
+fn escape_label(value: &str) -> String {
+    value
+        .replace('\\', "\\\\")
+        .replace('"', "\\\"")
+        .replace('\n', "\\n")
+}
+
+impl IntoArma for Metrics {
+    fn to_arma(&self) -> Value {
+        Value::Array(vec![
+            self.commands().to_arma(),
+            Value::Number(self.unknown_commands() as f64),
+        ])
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+/// Snapshot of the statistics of a command
+pub struct CommandStats {
+    name: String,
+    calls: u64,
+    errors: BTreeMap<libc::c_int, u64>,
+    parse_failures: u64,
+    overflows: u64,
+    latency: Histogram,
+    deprecated: Option<String>,
+}
+
+impl CommandStats {
+    #[must_use]
+    /// Full name of the command
+    pub fn name(&self) -> &str {
+        &self.name
+    }
+
+    #[must_use]
+    /// Number of calls
+    pub const fn calls(&self) -> u64 {
+        self.calls
+    }
+
+    #[must_use]
+    /// Number of failed calls by result code, see the error codes in the README
+    pub const fn errors(&self) -> &BTreeMap<libc::c_int, u64> {
+        &self.errors
+    }
+
+    #[must_use]
+    /// Number of calls with an argument that could not be parsed
+    pub const fn parse_failures(&self) -> u64 {
+        self.parse_failures
+    }
+
+    #[must_use]
+    /// Number of calls where the output did not fit into the buffer
+    pub const fn overflows(&self) -> u64 {
+        self.overflows
+    }
+
+    #[must_use]
+    /// Latency of the calls
+    pub const fn latency(&self) -> &Histogram {
+        &self.latency
+    }
+
+    #[must_use]
+    /// Deprecation note of the command, `None` if it is not deprecated
+    pub fn deprecated(&self) -> Option<&str> {
+        self.deprecated.as_deref()
+    }
+}
+
+impl IntoArma for CommandStats {
+    fn to_arma(&self) -> Value {
+        Value::Array(vec![
+            Value::String(self.name.clone()),
+            Value::Number(self.calls as f64),
+            Value::Array(
+                self.errors
+                    .iter()
+                    .map(|(code, count)| {
+                        Value::Array(vec![
+                            Value::Number(f64::from(*code)),
+                            Value::Number(*count as f64),
+                        ])
+                    })
+                    .collect(),
+            ),
+            Value::Number(self.parse_failures as f64),
+            Value::Number(self.overflows as f64),
+            Value::Number(
+                self.latency
+                    .mean()
+                    .map_or(0.0, |mean| mean.as_micros() as f64),
+            ),
+            self.deprecated
+                .as_ref()
+                .map_or(Value::Null, |note| Value::String(note.clone())),
+        ])
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+/// Latency histogram of a command
+pub struct Histogram {
+    buckets: Vec<(Duration, u64)>,
+    sum: Duration,
+}
+
+impl Histogram {
+    #[must_use]
+    /// Number of calls per bucket, keyed by the upper bound of the bucket.
+    /// The last bucket has an upper bound of [`Duration::MAX`].
+    pub fn buckets(&self) -> &[(Duration, u64)] {
+        &self.buckets
+    }
+
+    #[must_use]
+    /// Total number of recorded calls
+    pub fn count(&self) -> u64 {
+        self.buckets.iter().map(|(_, count)| count).sum()
+    }
+
+    #[must_use]
+    /// Total time spent in the command
+    pub const fn sum(&self) -> Duration {
+        self.sum
+    }
+
+    #[must_use]
+    /// Average time spent in the command, `None` if it was never called
+    pub fn mean(&self) -> Option<Duration> {
+        let count = u32::try_from(self.count()).unwrap_or(u32::MAX);
+        (count > 0).then(|| self.sum / count)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::command::error_code;
+
+    #[test]
+    fn record() {
+        let metrics = Metrics::default();
+        let command = metrics.register(String::from("group:command"));
+        command.record(0, Duration::from_micros(5));
+        command.record(31, Duration::from_micros(20));
+        command.record(4, Duration::from_millis(2));
+        command.record(9, Duration::from_secs(10));
+        command.record(error_code(3, 12), Duration::from_micros(5));
+
+        let stats = metrics.command("group:command").unwrap();
+        assert_eq!(stats.calls(), 5);
+        assert_eq!(stats.parse_failures(), 2);
+        assert_eq!(stats.overflows(), 1);
+        assert_eq!(
+            stats.errors().iter().collect::<Vec<_>>(),
+            vec![(&4, &1), (&9, &1), (&31, &1), (&312, &1)]
+        );
+        let latency = stats.latency();
+        assert_eq!(latency.count(), 5);
+        assert_eq!(latency.buckets()[0], (Duration::from_micros(10), 2));
+        assert_eq!(latency.buckets()[1], (Duration::from_micros(50), 1));
+        assert_eq!(latency.buckets()[5], (Duration::from_millis(5), 1));
+        assert_eq!(latency.buckets()[12], (Duration::MAX, 1));
+    }
+
+    #[test]
+    fn register_existing() {
+        let metrics = Metrics::default();
+        metrics
+            .register(String::from("command"))
+            .record(0, Duration::ZERO);
+        metrics
+            .register(String::from("command"))
+            .record(0, Duration::ZERO);
+        assert_eq!(metrics.command("command").unwrap().calls(), 2);
+        assert_eq!(metrics.command("command").unwrap().deprecated(), None);
+        metrics
+            .register(String::from("command"))
+            .deprecate(String::from("use other"));
+        assert_eq!(
+            metrics.command("command").unwrap().deprecated(),
+            Some("use other")
+        );
+        assert_eq!(metrics.commands().len(), 1);
+        assert!(metrics.command("missing").is_none());
+    }
+
+    #[cfg(feature = "prometheus")]
+    #[test]
+    fn prometheus() {
+        let metrics = Metrics::default();
+        metrics
+            .register(String::from("command"))
+            .record(9, Duration::from_micros(30));
+        metrics.record_unknown();
+        let text = metrics.to_prometheus();
+        assert!(text.contains("arma_rs_calls_total{command=\"command\"} 1\n"));
+        assert!(text.contains("arma_rs_errors_total{command=\"command\",code=\"9\"} 1\n"));
+        assert!(text.contains(
+            "arma_rs_call_duration_seconds_bucket{command=\"command\",le=\"0.00005\"} 1\n"
+        ));
+        assert!(
+            text.contains(
+                "arma_rs_call_duration_seconds_bucket{command=\"command\",le=\"+Inf\"} 1\n"
+            )
+        );
+        assert!(text.contains("arma_rs_unknown_commands_total 1\n"));
+    }
+
+    #[cfg(feature = "prometheus")]
+    #[test]
+    fn prometheus_escape() {
+        let metrics = Metrics::default();
+        metrics
+            .register(String::from("a\"b\\c\nd"))
+            .record(0, Duration::ZERO);
+        let text = metrics.to_prometheus();
+        assert!(text.contains("arma_rs_calls_total{command=\"a\\\"b\\\\c\\nd\"} 1\n"));
+    }
+}
@@ -0,0 +1,116 @@
+//! Information about the loaded extension library.
+
+use std::{path::PathBuf, sync::OnceLock};
+
+/// Name and version given to the `#[arma]` attribute
+static ATTRIBUTES: OnceLock<(Option<&'static str>, &'static str)> = OnceLock::new();
+
+#[doc(hidden)]
+/// Called by generated code before the extension is built, do not call directly.
+pub fn set_attributes(name: Option<&'static str>, version: &'static str) {
+    let _ = ATTRIBUTES.set((name, version));
+}
+
+#[must_use]
+/// Version given to the `#[arma]` attribute, the crate version of the extension when not given.
+/// Used when the version is not set with [`crate::ExtensionBuilder::version`].
+pub fn attribute_version() -> Option<&'static str> {
+    ATTRIBUTES.get().map(|(_, version)| *version)
+}
+
+#[must_use]
+/// Path of the library the extension was loaded from.
+/// When running tests this is the path of the test executable.
+pub fn module_path() -> Option<PathBuf> {
+    platform_module_path()
+}
+
+#[must_use]
+/// Name of the extension, the library's file name without the extension and the `_x64` suffix.
+/// For example `my_ext` for `@my_mod/my_ext_x64.dll`.
+/// The `name` given to the `#[arma]` attribute takes precedence.
+pub fn module_name() -> Option<String> {
+    if let Some(name) = ATTRIBUTES.get().and_then(|(name, _)| *name) {
+        return Some(name.to_string());
+    }
+    let path = module_path()?;
+    let stem = path.file_stem()?.to_string_lossy();
+    Some(stem.strip_suffix("_x64").unwrap_or(&stem).to_string())
+}
+
+#[must_use]
+/// Directory containing the library the extension was loaded from.
+pub fn module_dir() -> Option<PathBuf> {
+    module_path().and_then(|path| path.parent().map(std::path::Path::to_path_buf))
+}
+
+#[cfg(not(windows))]
+fn platform_module_path() -> Option<PathBuf> {
+    use std::os::unix::ffi::OsStrExt;
+
+    let mut info: libc::Dl_info = unsafe { std::mem::zeroed() };
+    // Any address inside of this library resolves to the library itself
+    let address = platform_module_path as *const libc::c_void;
+    if unsafe { libc::dladdr(address, &raw mut info) } == 0 || info.dli_fname.is_null() {
+        return None;
+    }
+    let name = unsafe { std::ffi::CStr::from_ptr(info.dli_fname) };
+    Some(PathBuf::from(std::ffi::OsStr::from_bytes(name.to_bytes())))
+}
+
+#[cfg(windows)]
+fn platform_module_path() -> Option<PathBuf> {
+    use std::os::windows::ffi::OsStringExt;
+    use winapi::um::libloaderapi::{
+        GET_MODULE_HANDLE_EX_FLAG_FROM_ADDRESS, GET_MODULE_HANDLE_EX_FLAG_UNCHANGED_REFCOUNT,
+        GetModuleFileNameW, GetModuleHandleExW,
+    };
+
+    let mut handle = std::ptr::null_mut();
+    // Any address inside of this library resolves to the library itself
+    let address = platform_module_path as *const u16;
+    if unsafe {
+        GetModuleHandleExW(
+            GET_MODULE_HANDLE_EX_FLAG_FROM_ADDRESS | GET_MODULE_HANDLE_EX_FLAG_UNCHANGED_REFCOUNT,
+            address,
+            &raw mut handle,
+        )
+    } == 0
+    {
+        return None;
+    }
+    let mut buffer = vec![0u16; 32768];
+    let len = unsafe {
+        GetModuleFileNameW(
+            handle,
+            buffer.as_mut_ptr(),
+            u32::try_from(buffer.len()).unwrap_or(u32::MAX),
+        )
+    } as usize;
+    if len == 0 || len >= buffer.len() {
+        return None;
+    }
+    Some(PathBuf::from(std::ffi::OsString::from_wide(&buffer[..len])))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn current_executable() {
+        let path = module_path().unwrap();
+        assert_eq!(
+            path.canonicalize().unwrap(),
+            std::env::current_exe().unwrap().canonicalize().unwrap()
+        );
+    }
+
+    #[test]
+    fn dir_contains_module() {
+        assert_eq!(
+            module_dir(),
+            module_path().unwrap().parent().map(Into::into)
+        );
+    }
+}
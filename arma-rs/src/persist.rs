@@ -0,0 +1,409 @@
+//! Persistent state, saved to and restored from disk.
+
+use std::{
+    fs::File,
+    io::Write,
+    marker::PhantomData,
+    path::{Path, PathBuf},
+    sync::{Mutex, PoisonError, TryLockError},
+    time::Duration,
+};
+
+use crossbeam_channel::{RecvTimeoutError, Sender, bounded};
+use serde::{Deserialize, Serialize, de::DeserializeOwned};
+
+use crate::State;
+
+/// Version of the file format used for persistent state
+pub const FORMAT_VERSION: u32 = 1;
+
+/// Default interval between periodic saves of persistent state
+pub const DEFAULT_INTERVAL: Duration = Duration::from_secs(300);
+
+#[derive(Serialize)]
+struct Snapshot<'a, T> {
+    version: u32,
+    data: &'a T,
+}
+
+#[derive(Deserialize)]
+struct StoredSnapshot {
+    version: u32,
+    data: serde_json::Value,
+}
+
+/// Error that can occur when saving or loading persistent state
+#[derive(Debug)]
+pub enum PersistError {
+    /// Reading or writing the file failed
+    Io(std::io::Error),
+    /// The state could not be serialized or deserialized
+    Serde(serde_json::Error),
+    /// The file was written by an unsupported version of the format
+    UnsupportedVersion(u32),
+}
+
+impl std::fmt::Display for PersistError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Self::Io(e) => write!(f, "io error: {e}"),
+            Self::Serde(e) => write!(f, "serialization error: {e}"),
+            Self::UnsupportedVersion(v) => write!(f, "unsupported format version: {v}"),
+        }
+    }
+}
+
+impl std::error::Error for PersistError {}
+
+impl From<std::io::Error> for PersistError {
+    fn from(e: std::io::Error) -> Self {
+        Self::Io(e)
+    }
+}
+
+impl From<serde_json::Error> for PersistError {
+    fn from(e: serde_json::Error) -> Self {
+        Self::Serde(e)
+    }
+}
+
+trait Persist: Send + Sync {
+    fn path(&self) -> &Path;
+    fn load(&self, state: &State) -> Result<(), PersistError>;
+    fn save(&self, state: &State) -> Result<(), PersistError>;
+}
+
+struct Entry<T> {
+    path: PathBuf,
+    _marker: PhantomData<fn() -> T>,
+}
+
+impl<T> Entry<T>
+where
+    T: DeserializeOwned + Default,
+{
+    fn read(&self) -> Result<T, PersistError> {
+        if !self.path.exists() {
+            return Ok(T::default());
+        }
+        let snapshot: StoredSnapshot = serde_json::from_reader(File::open(&self.path)?)?;
+        if snapshot.version > FORMAT_VERSION {
+            return Err(PersistError::UnsupportedVersion(snapshot.version));
+        }
+        Ok(serde_json::from_value::<T>(snapshot.data)?)
+    }
+}
+
+impl<T> Persist for Entry<T>
+where
+    T: Serialize + DeserializeOwned + Default + Send + Sync + 'static,
+{
+    fn path(&self) -> &Path {
+        &self.path
+    }
+
+    fn load(&self, state: &State) -> Result<(), PersistError> {
+        let (value, result) = match self.read() {
+            Ok(value) => (value, Ok(())),
+            Err(e) => {
+                // Keep the unreadable file, the next save would overwrite it otherwise
+                if self.path.exists() {
+                    let backup = backup_path(&self.path);
+                    if let Err(e) = std::fs::rename(&self.path, &backup) {
+                        error!("failed to move {} aside: {e}", self.path.display());
+                    } else {
+                        warn!("moved {} to {}", self.path.display(), backup.display());
+                    }
+                }
+                (T::default(), Err(e))
+            }
+        };
+        if !crate::group::set_state(state, value) {
+            warn!(
+                "persistent state for {} was already set, the stored value is ignored",
+                self.path.display()
+            );
+        }
+        result
+    }
+
+    fn save(&self, state: &State) -> Result<(), PersistError> {
+        let Some(data) = state.try_get::<T>() else {
+            return Ok(());
+        };
+        let contents = serde_json::to_vec(&Snapshot {
+            version: FORMAT_VERSION,
+            data,
+        })?;
+        write_atomic(&self.path, &contents)
+    }
+}
+
+/// Path an unreadable file is moved to, `<path>.corrupt`
+fn backup_path(path: &Path) -> PathBuf {
+    let mut backup = path.as_os_str().to_owned();
+    backup.push(".corrupt");
+    PathBuf::from(backup)
+}
+
+/// Write to a temporary file next to `path`, then move it into place
+fn write_atomic(path: &Path, contents: &[u8]) -> Result<(), PersistError> {
+    if let Some(parent) = path.parent().filter(|p| !p.as_os_str().is_empty()) {
+        std::fs::create_dir_all(parent)?;
+    }
+    let mut tmp = path.as_os_str().to_owned();
+    tmp.push(".tmp");
+    let tmp = PathBuf::from(tmp);
+    {
+        let mut file = File::create(&tmp)?;
+        file.write_all(contents)?;
+        file.sync_all()?;
+    }
+    std::fs::rename(&tmp, path)?;
+    Ok(())
+}
+
+#[derive(Default)]
+/// Persistent state values registered on the extension
+pub(crate) struct Persistence {
+    entries: Vec<Box<dyn Persist>>,
+    interval: Option<Duration>,
+    // Held while saving, so the saver thread and `::save_state` never write the same file at once
+    saving: Mutex<()>,
+}
+
+impl Persistence {
+    pub(crate) fn add<T>(&mut self, path: PathBuf)
+    where
+        T: Serialize + DeserializeOwned + Default + Send + Sync + 'static,
+    {
+        self.entries.push(Box::new(Entry::<T> {
+            path,
+            _marker: PhantomData,
+        }));
+    }
+
+    pub(crate) const fn set_interval(&mut self, interval: Duration) {
+        self.interval = Some(interval);
+    }
+
+    pub(crate) fn is_empty(&self) -> bool {
+        self.entries.is_empty()
+    }
+
+    pub(crate) fn interval(&self) -> Duration {
+        self.interval.unwrap_or(DEFAULT_INTERVAL)
+    }
+
+    /// Load every registered value into the state, falling back to its default
+    pub(crate) fn load(&self, state: &State) {
+        for entry in &self.entries {
+            if let Err(e) = entry.load(state) {
+                error!(
+                    "failed to load persistent state from {}, using the default: {e}",
+                    entry.path().display()
+                );
+            }
+        }
+    }
+
+    /// Save every registered value, returning the first error encountered
+    pub(crate) fn save(&self, state: &State) -> Result<(), PersistError> {
+        let _saving = self.saving.lock().unwrap_or_else(PoisonError::into_inner);
+        self.save_entries(state)
+    }
+
+    /// Save every registered value unless a save is already running, which is skipped.
+    /// Used while the library is detached, the thread holding the lock may have been killed.
+    pub(crate) fn try_save(&self, state: &State) -> Result<(), PersistError> {
+        match self.saving.try_lock() {
+            Ok(_saving) => self.save_entries(state),
+            Err(TryLockError::Poisoned(e)) => {
+                let _saving = e.into_inner();
+                self.save_entries(state)
+            }
+            Err(TryLockError::WouldBlock) => Ok(()),
+        }
+    }
+
+    fn save_entries(&self, state: &State) -> Result<(), PersistError> {
+        let mut result = Ok(());
+        for entry in &self.entries {
+            if let Err(e) = entry.save(state) {
+                error!(
+                    "failed to save persistent state to {}: {e}",
+                    entry.path().display()
+                );
+                if result.is_ok() {
+                    result = Err(e);
+                }
+            }
+        }
+        result
+    }
+}
+
+/// Background thread periodically saving persistent state
+pub(crate) struct Saver {
+    stop: Sender<()>,
+    thread: std::thread::JoinHandle<()>,
+}
+
+impl Saver {
+    pub(crate) fn spawn(
+        persistence: std::sync::Arc<Persistence>,
+        state: std::sync::Arc<State>,
+    ) -> Self {
+        let (stop, rx) = bounded(0);
+        let interval = persistence.interval();
+        let thread = std::thread::spawn(move || {
+            while let Err(RecvTimeoutError::Timeout) = rx.recv_timeout(interval) {
+                let _ = persistence.save(&state);
+            }
+        });
+        Self { stop, thread }
+    }
+
+    /// Stop the thread without waiting for it, used while the library is detached
+    pub(crate) fn detach(self) {
+        drop(self.stop);
+    }
+
+    pub(crate) fn stop(self) {
+        drop(self.stop);
+        self.thread
+            .join()
+            .expect("Failed to join persistent state thread");
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// Directory for the files of one test, removed when the test ends
+    struct TempDir(PathBuf);
+
+    impl TempDir {
+        fn new(test: &str) -> Self {
+            Self(
+                std::env::temp_dir().join(format!("arma-rs-persist-{}-{test}", std::process::id())),
+            )
+        }
+
+        fn path(&self, name: &str) -> PathBuf {
+            self.0.join(name)
+        }
+    }
+
+    impl Drop for TempDir {
+        fn drop(&mut self) {
+            let _ = std::fs::remove_dir_all(&self.0);
+        }
+    }
+
+    #[test]
+    fn load_missing_uses_default() {
+        let dir = TempDir::new("missing");
+        let mut persistence = Persistence::default();
+        persistence.add::<Mutex<Vec<u32>>>(dir.path("missing.json"));
+        let state = State::default();
+        persistence.load(&state);
+        assert!(
+            state
+                .try_get::<Mutex<Vec<u32>>>()
+                .unwrap()
+                .lock()
+                .unwrap()
+                .is_empty()
+        );
+    }
+
+    #[test]
+    fn save_and_load() {
+        let dir = TempDir::new("roundtrip");
+        let path = dir.path("roundtrip.json");
+        let mut persistence = Persistence::default();
+        persistence.add::<Mutex<Vec<u32>>>(path.clone());
+
+        let state = State::default();
+        state.set(Mutex::new(vec![1_u32, 2, 3]));
+        persistence.save(&state).unwrap();
+        assert!(path.exists());
+        assert!(!path.with_extension("json.tmp").exists());
+
+        let state = State::default();
+        persistence.load(&state);
+        assert_eq!(
+            *state.try_get::<Mutex<Vec<u32>>>().unwrap().lock().unwrap(),
+            vec![1, 2, 3]
+        );
+    }
+
+    #[test]
+    fn concurrent_saves() {
+        let dir = TempDir::new("concurrent");
+        let path = dir.path("concurrent.json");
+        let mut persistence = Persistence::default();
+        persistence.add::<Vec<u32>>(path.clone());
+        let state = State::default();
+        state.set((0..10_000).collect::<Vec<u32>>());
+
+        std::thread::scope(|scope| {
+            for _ in 0..4 {
+                scope.spawn(|| {
+                    for _ in 0..10 {
+                        persistence.save(&state).unwrap();
+                    }
+                });
+            }
+        });
+        let state = State::default();
+        persistence.load(&state);
+        assert_eq!(state.try_get::<Vec<u32>>().unwrap().len(), 10_000);
+    }
+
+    #[test]
+    fn unsupported_version() {
+        let dir = TempDir::new("version");
+        let path = dir.path("version.json");
+        write_atomic(&path, br#"{"version":999,"data":[1]}"#).unwrap();
+        let entry = Entry::<Vec<u32>> {
+            path: path.clone(),
+            _marker: PhantomData,
+        };
+        let state = State::default();
+        assert!(matches!(
+            entry.load(&state),
+            Err(PersistError::UnsupportedVersion(999))
+        ));
+        assert!(state.try_get::<Vec<u32>>().unwrap().is_empty());
+        assert!(backup_path(&path).exists());
+    }
+
+    #[test]
+    fn load_corrupt_uses_default() {
+        let dir = TempDir::new("corrupt");
+        let path = dir.path("corrupt.json");
+        write_atomic(&path, b"{\"version\":1,\"da").unwrap();
+        let mut persistence = Persistence::default();
+        persistence.add::<Vec<u32>>(path.clone());
+
+        let state = State::default();
+        persistence.load(&state);
+        assert!(state.try_get::<Vec<u32>>().unwrap().is_empty());
+        assert!(!path.exists());
+        assert_eq!(
+            std::fs::read(backup_path(&path)).unwrap(),
+            b"{\"version\":1,\"da"
+        );
+
+        // Saving afterwards leaves the corrupt file untouched
+        persistence.save(&state).unwrap();
+        assert!(path.exists());
+        assert_eq!(
+            std::fs::read(backup_path(&path)).unwrap(),
+            b"{\"version\":1,\"da"
+        );
+    }
+}
@@ -0,0 +1,275 @@
+//! Shared pool of worker threads for blocking work, see [`crate::Context::spawn`].
+
+use std::sync::{
+    Mutex,
+    atomic::{AtomicBool, AtomicUsize, Ordering},
+};
+
+use crossbeam_channel::{Receiver, RecvTimeoutError, Sender, TrySendError, bounded};
+
+use crate::{IntoArma, Value};
+
+type Task = Box<dyn FnOnce() + Send>;
+
+/// Configuration for the worker pool, see [`crate::ExtensionBuilder::worker_pool`]
+#[derive(Debug, Clone)]
+pub struct PoolConfig {
+    threads: usize,
+    queue: usize,
+}
+
+impl Default for PoolConfig {
+    fn default() -> Self {
+        Self {
+            threads: std::thread::available_parallelism().map_or(4, std::num::NonZero::get),
+            queue: 256,
+        }
+    }
+}
+
+impl PoolConfig {
+    #[must_use]
+    /// Create a new configuration with one thread per CPU and room for 256 queued tasks
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    #[must_use]
+    /// Sets the number of worker threads, at least 1
+    pub fn threads(mut self, threads: usize) -> Self {
+        self.threads = threads.max(1);
+        self
+    }
+
+    #[must_use]
+    /// Sets the number of tasks that can wait for a free worker, at least 1
+    pub fn queue(mut self, depth: usize) -> Self {
+        self.queue = depth.max(1);
+        self
+    }
+}
+
+/// Error that can occur when spawning a task on the worker pool
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum SpawnError {
+    /// Every worker is busy and the queue is full, contains the queue depth
+    Saturated(usize),
+    /// The extension is shutting down
+    ShutDown,
+}
+
+impl std::fmt::Display for SpawnError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Self::Saturated(depth) => {
+                write!(f, "Worker pool saturated, {depth} tasks already queued")
+            }
+            Self::ShutDown => write!(f, "Worker pool shut down"),
+        }
+    }
+}
+
+impl std::error::Error for SpawnError {}
+
+impl IntoArma for SpawnError {
+    fn to_arma(&self) -> Value {
+        Value::String(self.to_string())
+    }
+}
+
+/// Snapshot of the worker pool, see [`crate::Extension::pool_stats`]
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct PoolStats {
+    threads: usize,
+    active: usize,
+    queued: usize,
+}
+
+impl PoolStats {
+    #[must_use]
+    /// Number of worker threads that have been started
+    pub const fn threads(&self) -> usize {
+        self.threads
+    }
+
+    #[must_use]
+    /// Number of tasks being run
+    pub const fn active(&self) -> usize {
+        self.active
+    }
+
+    #[must_use]
+    /// Number of tasks waiting for a free worker
+    pub const fn queued(&self) -> usize {
+        self.queued
+    }
+}
+
+struct Workers {
+    tx: Sender<Task>,
+    threads: Vec<std::thread::JoinHandle<()>>,
+    done: Receiver<()>,
+}
+
+#[derive(Default)]
+/// Worker threads owned by the extension, started on the first spawned task
+pub(crate) struct WorkerPool {
+    config: PoolConfig,
+    workers: Mutex<Option<Workers>>,
+    active: std::sync::Arc<AtomicUsize>,
+    closed: AtomicBool,
+}
+
+impl WorkerPool {
+    pub(crate) fn new(config: PoolConfig) -> Self {
+        Self {
+            config,
+            ..Self::default()
+        }
+    }
+
+    fn lock(&self) -> std::sync::MutexGuard<'_, Option<Workers>> {
+        self.workers
+            .lock()
+            .unwrap_or_else(std::sync::PoisonError::into_inner)
+    }
+
+    fn start(&self) -> Workers {
+        let (tx, rx) = bounded::<Task>(self.config.queue);
+        let (done_tx, done) = bounded::<()>(0);
+        let threads = (0..self.config.threads)
+            .map(|i| {
+                let rx = rx.clone();
+                let done = done_tx.clone();
+                let active = self.active.clone();
+                std::thread::Builder::new()
+                    .name(format!("arma-rs-worker-{i}"))
+                    .spawn(move || {
+                        let _done = done;
+                        while let Ok(task) = rx.recv() {
+                            active.fetch_add(1, Ordering::SeqCst);
+                            if std::panic::catch_unwind(std::panic::AssertUnwindSafe(task)).is_err()
+                            {
+                                error!("worker pool task panicked");
+                            }
+                            active.fetch_sub(1, Ordering::SeqCst);
+                        }
+                    })
+                    .expect("Failed to spawn worker thread")
+            })
+            .collect();
+        Workers { tx, threads, done }
+    }
+
+    pub(crate) fn spawn(&self, task: impl FnOnce() + Send + 'static) -> Result<(), SpawnError> {
+        let mut workers = self.lock();
+        if self.closed.load(Ordering::SeqCst) {
+            return Err(SpawnError::ShutDown);
+        }
+        let workers = workers.get_or_insert_with(|| self.start());
+        workers.tx.try_send(Box::new(task)).map_err(|e| match e {
+            TrySendError::Full(_) => SpawnError::Saturated(self.config.queue),
+            TrySendError::Disconnected(_) => SpawnError::ShutDown,
+        })
+    }
+
+    pub(crate) fn stats(&self) -> PoolStats {
+        let workers = self.lock();
+        PoolStats {
+            threads: workers.as_ref().map_or(0, |w| w.threads.len()),
+            active: self.active.load(Ordering::SeqCst),
+            queued: workers.as_ref().map_or(0, |w| w.tx.len()),
+        }
+    }
+
+    /// Reject new tasks, wait for the queued tasks to finish and join the threads
+    pub(crate) fn shutdown(&self, wait: bool) {
+        self.closed.store(true, Ordering::SeqCst);
+        let Some(Workers { tx, threads, done }) = self.lock().take() else {
+            return;
+        };
+        drop(tx);
+        if !wait {
+            return;
+        }
+        // The threads may have been killed by the OS, only join them once they have all signaled they finished
+        match done.recv_timeout(crate::SHUTDOWN_TIMEOUT) {
+            Err(RecvTimeoutError::Disconnected) => {
+                for thread in threads {
+                    thread.join().expect("Failed to join worker thread");
+                }
+            }
+            _ => warn!("worker threads did not finish before shutdown"),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::time::Duration;
+
+    use super::*;
+
+    #[test]
+    fn runs_tasks() {
+        let pool = WorkerPool::new(PoolConfig::new().threads(2));
+        assert_eq!(pool.stats().threads(), 0);
+        let (tx, rx) = bounded(10);
+        for i in 0..10 {
+            let tx = tx.clone();
+            pool.spawn(move || tx.send(i).unwrap()).unwrap();
+        }
+        assert_eq!(pool.stats().threads(), 2);
+        let mut received: Vec<i32> = (0..10)
+            .map(|_| rx.recv_timeout(Duration::from_secs(5)).unwrap())
+            .collect();
+        received.sort_unstable();
+        assert_eq!(received, (0..10).collect::<Vec<_>>());
+    }
+
+    #[test]
+    fn saturated() {
+        let pool = WorkerPool::new(PoolConfig::new().threads(1).queue(1));
+        let (release, wait) = bounded::<()>(0);
+        let (started_tx, started) = bounded::<()>(0);
+        pool.spawn(move || {
+            started_tx.send(()).unwrap();
+            let _ = wait.recv();
+        })
+        .unwrap();
+        started.recv_timeout(Duration::from_secs(5)).unwrap();
+        pool.spawn(|| {}).unwrap();
+        assert_eq!(pool.spawn(|| {}), Err(SpawnError::Saturated(1)));
+        let stats = pool.stats();
+        assert_eq!((stats.active(), stats.queued()), (1, 1));
+        drop(release);
+        pool.shutdown(true);
+        assert_eq!(pool.spawn(|| {}), Err(SpawnError::ShutDown));
+        assert_eq!(pool.stats().threads(), 0);
+    }
+
+    #[test]
+    fn survives_panic() {
+        let pool = WorkerPool::new(PoolConfig::new().threads(1));
+        pool.spawn(|| panic!("task panicked")).unwrap();
+        let (tx, rx) = bounded(1);
+        pool.spawn(move || tx.send(()).unwrap()).unwrap();
+        assert!(rx.recv_timeout(Duration::from_secs(5)).is_ok());
+    }
+
+    #[test]
+    fn shutdown_drains_queue() {
+        let pool = WorkerPool::new(PoolConfig::new().threads(1));
+        let count = std::sync::Arc::new(AtomicUsize::new(0));
+        for _ in 0..5 {
+            let count = count.clone();
+            pool.spawn(move || {
+                std::thread::sleep(Duration::from_millis(1));
+                count.fetch_add(1, Ordering::SeqCst);
+            })
+            .unwrap();
+        }
+        pool.shutdown(true);
+        assert_eq!(count.load(Ordering::SeqCst), 5);
+    }
+}
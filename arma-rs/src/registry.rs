@@ -0,0 +1,407 @@
+//! Commands registered and removed while the extension is running, see [`crate::Context::registry`].
+
+use std::{
+    borrow::Cow,
+    collections::{BTreeMap, HashSet},
+    sync::{Arc, RwLock},
+    time::Instant,
+};
+
+use crate::{
+    ArmaContextManager, Context, FeatureFlags, Group, State,
+    command::{Arguments, Factory, Handler, fn_handler},
+    context::GroupContext,
+    flags::Published,
+    group::{Deprecation, Flat, route_key},
+    metrics::{CommandMetrics, Metrics},
+};
+
+/// Error that can occur when registering a command
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum RegistryError {
+    /// The path is empty or contains an empty segment, like `group:` or `a::b`
+    InvalidPath(String),
+    /// A command already exists at the path
+    Exists(String),
+    /// An alias or default version of a group points to something missing
+    MissingTarget(String),
+    /// The group at the path has a fallback or subscribers, which can not be added while running
+    Unsupported(String),
+}
+
+impl std::fmt::Display for RegistryError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Self::InvalidPath(path) => write!(f, "invalid command path `{path}`"),
+            Self::Exists(path) => write!(f, "command `{path}` already exists"),
+            Self::MissingTarget(path) => write!(f, "`{path}` points to something missing"),
+            Self::Unsupported(path) => write!(
+                f,
+                "group `{path}` has a fallback or subscribers, which can not be added while running"
+            ),
+        }
+    }
+}
+
+impl std::error::Error for RegistryError {}
+
+struct Command {
+    handler: Handler,
+    metrics: Arc<CommandMetrics>,
+    // State of the group the command was added with, the global state otherwise
+    state: Option<Arc<State>>,
+    deprecation: Option<Deprecation>,
+}
+
+struct Inner {
+    commands: RwLock<BTreeMap<String, Arc<Command>>>,
+    // Paths of the commands and aliases added when the extension was built, they can not be replaced or removed
+    fixed: HashSet<String>,
+    // Feature flags of the commands added when the extension was built
+    fixed_flags: FeatureFlags,
+    published: Published,
+    metrics: Arc<Metrics>,
+    case_insensitive: bool,
+}
+
+#[derive(Clone)]
+/// Commands added after the extension was built.
+///
+/// Available in the global state and through [`crate::Context::registry`].
+/// Commands are called with their full path, like the commands of a [`crate::Group`],
+/// and receive a context with the global state as their group state.
+pub struct Registry(Arc<Inner>);
+
+impl Registry {
+    /// Create a registry for an extension, the paths in `fixed` can not be registered
+    pub(crate) fn new(
+        fixed: HashSet<String>,
+        metrics: Arc<Metrics>,
+        fixed_flags: FeatureFlags,
+        case_insensitive: bool,
+    ) -> Self {
+        Self(Arc::new(Inner {
+            commands: RwLock::default(),
+            fixed,
+            fixed_flags,
+            published: Published::new(fixed_flags),
+            metrics,
+            case_insensitive,
+        }))
+    }
+
+    fn key<'a>(&self, path: &'a str) -> Cow<'a, str> {
+        route_key(path, self.0.case_insensitive)
+    }
+
+    fn read(&self) -> std::sync::RwLockReadGuard<'_, BTreeMap<String, Arc<Command>>> {
+        self.0
+            .commands
+            .read()
+            .unwrap_or_else(std::sync::PoisonError::into_inner)
+    }
+
+    fn write(&self) -> std::sync::RwLockWriteGuard<'_, BTreeMap<String, Arc<Command>>> {
+        self.0
+            .commands
+            .write()
+            .unwrap_or_else(std::sync::PoisonError::into_inner)
+    }
+
+    /// Register a command at `path`, such as `ctf:score`.
+    /// # Errors
+    /// Returns an error if the path is invalid or a command already exists at the path.
+    pub fn command<F, I, R>(&self, path: &str, handler: F) -> Result<(), RegistryError>
+    where
+        F: Factory<I, R> + Send + Sync + 'static,
+    {
+        if path.split(':').any(str::is_empty) {
+            return Err(RegistryError::InvalidPath(path.to_string()));
+        }
+        let key = self.key(path);
+        let mut commands = self.write();
+        if self.0.fixed.contains(&*key) || commands.contains_key(&*key) {
+            return Err(RegistryError::Exists(path.to_string()));
+        }
+        commands.insert(
+            key.into_owned(),
+            Arc::new(Command {
+                handler: fn_handler(handler),
+                metrics: self.0.metrics.register(path.to_string()),
+                state: None,
+                deprecation: None,
+            }),
+        );
+        self.publish(&commands);
+        Ok(())
+    }
+
+    /// Register the commands of a group at `path`, so `ctf:score` calls the `score` command of a group registered at `ctf`.
+    /// Aliases, nested groups, versions and deprecations work as they do when the extension is built,
+    /// and commands receive a context with the state of their group.
+    ///
+    /// Either every command of the group is registered, or none is.
+    /// # Errors
+    /// Returns an error if the path is invalid, a command already exists at one of the paths of the group,
+    /// an alias or default version points to something missing, or a group has a fallback or subscribers.
+    pub fn group(&self, path: &str, group: Group) -> Result<(), RegistryError> {
+        if path.split(':').any(str::is_empty) {
+            return Err(RegistryError::InvalidPath(path.to_string()));
+        }
+        let entries = group.flatten(&format!("{path}:"))?;
+        let mut commands = self.write();
+        let mut keys = HashSet::new();
+        for entry in &entries {
+            let key = self.key(entry.path()).into_owned();
+            if self.0.fixed.contains(&key) || commands.contains_key(&key) || !keys.insert(key) {
+                return Err(RegistryError::Exists(entry.path().to_string()));
+            }
+        }
+        let mut added: BTreeMap<String, Arc<Command>> = BTreeMap::new();
+        for entry in entries {
+            let (path, command) = match entry {
+                Flat::Command {
+                    path,
+                    handler,
+                    deprecated,
+                    state,
+                } => {
+                    let metrics = self.0.metrics.register(path.clone());
+                    let deprecation = deprecated.map(|note| {
+                        metrics.deprecate(note.clone());
+                        Deprecation::new(path.clone(), note)
+                    });
+                    let command = Arc::new(Command {
+                        handler,
+                        metrics,
+                        state: Some(state),
+                        deprecation,
+                    });
+                    (path, command)
+                }
+                Flat::Alias { path, target } => {
+                    let command = added[&*self.key(&target)].clone();
+                    (path, command)
+                }
+            };
+            added.insert(self.key(&path).into_owned(), command);
+        }
+        commands.extend(added);
+        self.publish(&commands);
+        Ok(())
+    }
+
+    /// Remove the command at `path`, returns false if no command was registered at the path.
+    /// Commands added when the extension was built can not be removed.
+    pub fn remove_command(&self, path: &str) -> bool {
+        let mut commands = self.write();
+        let removed = commands.remove(&*self.key(path)).is_some();
+        self.publish(&commands);
+        removed
+    }
+
+    /// Remove every command registered under the group at `path`, returns the number of removed commands
+    pub fn remove_group(&self, path: &str) -> usize {
+        let prefix = format!("{}:", self.key(path));
+        let mut commands = self.write();
+        let before = commands.len();
+        commands.retain(|name, _| !name.starts_with(&prefix));
+        self.publish(&commands);
+        before - commands.len()
+    }
+
+    #[must_use]
+    /// Returns the feature flags needed by every command of the extension, including those added when it was built
+    pub fn feature_flags(&self) -> FeatureFlags {
+        Self::combine(self.0.fixed_flags, &self.read())
+    }
+
+    #[must_use]
+    /// Returns the feature flags of the command registered at `path`
+    pub fn command_flags(&self, path: &str) -> Option<FeatureFlags> {
+        self.read()
+            .get(&*self.key(path))
+            .map(|command| command.handler.flags)
+    }
+
+    fn combine(flags: FeatureFlags, commands: &BTreeMap<String, Arc<Command>>) -> FeatureFlags {
+        commands
+            .values()
+            .map(|command| command.handler.flags)
+            .fold(flags, FeatureFlags::union)
+    }
+
+    /// Publish the combined flags to Arma, called while holding the write lock so updates are not reordered
+    fn publish(&self, commands: &BTreeMap<String, Arc<Command>>) {
+        self.0
+            .published
+            .publish(Self::combine(self.0.fixed_flags, commands));
+    }
+
+    /// The flags Arma reads on each callExtension call
+    pub(crate) fn published(&self) -> FeatureFlags {
+        self.0.published.get()
+    }
+
+    /// Copy the published flags into `RVExtensionFeatureFlags`, now and whenever the registry changes
+    pub(crate) fn publish_global(&self) {
+        let _commands = self.write();
+        self.0.published.make_global();
+    }
+
+    #[must_use]
+    /// Returns the paths of every registered command, lowercase when routing is case insensitive
+    pub fn commands(&self) -> Vec<String> {
+        self.read().keys().cloned().collect()
+    }
+
+    #[must_use]
+    /// Returns true if a command is registered at `path`
+    pub fn contains(&self, path: &str) -> bool {
+        self.read().contains_key(&*self.key(path))
+    }
+
+    /// Call the command at `function`, gives the context back if no command is registered at the path.
+    ///
+    /// The lock is released before the command runs, so commands can register and remove commands.
+    pub(crate) fn handle(
+        &self,
+        context: Context,
+        acm: &ArmaContextManager,
+        function: &str,
+        output: *mut libc::c_char,
+        size: libc::size_t,
+        args: Arguments<'_>,
+    ) -> Result<libc::c_int, Context> {
+        let Some(command) = self.read().get(&*self.key(function)).cloned() else {
+            return Err(context);
+        };
+        if let Some(deprecation) = &command.deprecation {
+            deprecation.warn_call(acm);
+        }
+        let context = match &command.state {
+            Some(state) => context.with_group(GroupContext::new(state.clone())),
+            None => context,
+        };
+        let start = Instant::now();
+        let code = (command.handler.handler)(context, acm, output, size, args);
+        command.metrics.record(code, start.elapsed());
+        Ok(code)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn registry() -> Registry {
+        let fixed = HashSet::from([String::from("fixed"), String::from("stats:get")]);
+        Registry::new(
+            fixed,
+            Arc::new(Metrics::default()),
+            FeatureFlags::new(),
+            false,
+        )
+    }
+
+    #[test]
+    fn register_remove() {
+        let registry = registry();
+        registry.command("ctf:score", || 1).unwrap();
+        registry.command("ctf:flag:capture", || 2).unwrap();
+        registry.command("other", || 3).unwrap();
+        assert_eq!(
+            registry.commands(),
+            vec!["ctf:flag:capture", "ctf:score", "other"]
+        );
+        assert_eq!(
+            registry.command("ctf:score", || 1),
+            Err(RegistryError::Exists(String::from("ctf:score")))
+        );
+        assert!(registry.remove_command("other"));
+        assert!(!registry.remove_command("other"));
+        assert_eq!(registry.remove_group("ctf"), 2);
+        assert!(registry.commands().is_empty());
+    }
+
+    #[test]
+    fn invalid() {
+        let registry = registry();
+        assert_eq!(
+            registry.command("fixed", || 1),
+            Err(RegistryError::Exists(String::from("fixed")))
+        );
+        assert!(!registry.remove_command("fixed"));
+        for path in ["", "group:", ":command", "a::b"] {
+            assert_eq!(
+                registry.command(path, || 1),
+                Err(RegistryError::InvalidPath(path.to_string()))
+            );
+        }
+    }
+
+    #[test]
+    fn group() {
+        let registry = registry();
+        let group = Group::new()
+            .command("score", || 1)
+            .alias("points", "score")
+            .group("flag", Group::new().command("capture", || 2))
+            .version(
+                2,
+                Group::new().command("score", || 3).command("reset", || 4),
+            )
+            .default_version(2);
+        registry.group("ctf", group).unwrap();
+        assert_eq!(
+            registry.commands(),
+            vec![
+                "ctf:flag:capture",
+                "ctf:points",
+                "ctf:reset",
+                "ctf:score",
+                "ctf:v2:reset",
+                "ctf:v2:score"
+            ]
+        );
+        assert_eq!(
+            registry.group("ctf", Group::new().command("reset", || 5)),
+            Err(RegistryError::Exists(String::from("ctf:reset")))
+        );
+        assert_eq!(registry.remove_group("ctf"), 6);
+    }
+
+    #[test]
+    fn group_invalid() {
+        let registry = registry();
+        // Nothing is registered when one of the paths exists
+        assert_eq!(
+            registry.group(
+                "stats",
+                Group::new().command("set", || 1).command("get", || 2)
+            ),
+            Err(RegistryError::Exists(String::from("stats:get")))
+        );
+        assert!(registry.commands().is_empty());
+        assert_eq!(
+            registry.group("ctf", Group::new().alias("points", "score")),
+            Err(RegistryError::MissingTarget(String::from("ctf:points")))
+        );
+        assert_eq!(
+            registry.group("ctf", Group::new().default_version(2)),
+            Err(RegistryError::MissingTarget(String::from("ctf:v2")))
+        );
+        assert_eq!(
+            registry.group(
+                "ctf",
+                Group::new().group("flag", Group::new().fallback(|_, _, _| ()))
+            ),
+            Err(RegistryError::Unsupported(String::from("ctf:flag")))
+        );
+        assert_eq!(
+            registry.group("a::b", Group::new()),
+            Err(RegistryError::InvalidPath(String::from("a::b")))
+        );
+        assert!(registry.commands().is_empty());
+    }
+}
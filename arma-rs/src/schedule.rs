@@ -0,0 +1,525 @@
+//! Tasks run periodically or after a delay, see [`crate::ExtensionBuilder::every`].
+
+use std::{
+    str::FromStr,
+    sync::{Arc, Mutex},
+    time::{Duration, Instant, SystemTime},
+};
+
+use crossbeam_channel::{Receiver, RecvTimeoutError, Sender, bounded};
+
+use crate::{Context, IntoArma, Value};
+
+type Handler = Box<dyn FnMut(Context) + Send>;
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+/// When a scheduled task runs
+pub enum Schedule {
+    /// Repeatedly, waiting the duration between runs
+    Every(Duration),
+    /// Once, after the duration
+    After(Duration),
+    /// At the times matching a cron expression
+    Cron(Cron),
+}
+
+impl Schedule {
+    /// Next time the task runs after `now`, `None` if it never runs again
+    fn next(&self, now: Instant) -> Option<Instant> {
+        match self {
+            Self::Every(interval) | Self::After(interval) => Some(now + *interval),
+            Self::Cron(cron) => {
+                let at = cron.next_after(SystemTime::now())?;
+                Some(now + at.duration_since(SystemTime::now()).unwrap_or_default())
+            }
+        }
+    }
+}
+
+impl std::fmt::Display for Schedule {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Self::Every(interval) => write!(f, "every {interval:?}"),
+            Self::After(delay) => write!(f, "after {delay:?}"),
+            Self::Cron(cron) => write!(f, "cron {cron}"),
+        }
+    }
+}
+
+/// Error that can occur when parsing a cron expression
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum CronError {
+    /// The expression does not have 5 fields
+    FieldCount(usize),
+    /// A field contains an invalid value, range or step
+    InvalidField(&'static str, String),
+}
+
+impl std::fmt::Display for CronError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Self::FieldCount(count) => write!(f, "expected 5 fields, found {count}"),
+            Self::InvalidField(field, value) => write!(f, "invalid {field} field: {value}"),
+        }
+    }
+}
+
+impl std::error::Error for CronError {}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+/// A cron expression, `minute hour day-of-month month day-of-week`, evaluated in UTC.
+///
+/// Fields support `*`, values, ranges (`1-5`), lists (`1,15`) and steps (`*/10`).
+/// Day of week is `0-7`, both `0` and `7` are Sunday.
+/// When both day fields are restricted, a day matching either of them matches.
+pub struct Cron {
+    expression: String,
+    minutes: u64,
+    hours: u64,
+    days: u64,
+    months: u64,
+    weekdays: u64,
+    any_day: bool,
+    any_weekday: bool,
+}
+
+impl Cron {
+    /// Next time matching the expression, strictly after `time`
+    #[must_use]
+    pub fn next_after(&self, time: SystemTime) -> Option<SystemTime> {
+        let secs = time.duration_since(SystemTime::UNIX_EPOCH).ok()?.as_secs();
+        let mut minute = secs / 60 + 1;
+        // Bounded so an expression that never matches, like the 31st of February, gives up
+        for _ in 0..100_000 {
+            let days = minute / (24 * 60);
+            let (_, month, day) = civil_from_days(days);
+            if !bit(self.months, month) {
+                minute = (days + 1) * 24 * 60;
+                continue;
+            }
+            let weekday = (days + 4) % 7;
+            let day_matches = match (self.any_day, self.any_weekday) {
+                (true, true) => true,
+                (false, true) => bit(self.days, day),
+                (true, false) => bit(self.weekdays, weekday),
+                (false, false) => bit(self.days, day) || bit(self.weekdays, weekday),
+            };
+            if !day_matches {
+                minute = (days + 1) * 24 * 60;
+                continue;
+            }
+            if !bit(self.hours, minute / 60 % 24) {
+                minute = (minute / 60 + 1) * 60;
+                continue;
+            }
+            if !bit(self.minutes, minute % 60) {
+                minute += 1;
+                continue;
+            }
+            return Some(SystemTime::UNIX_EPOCH + Duration::from_secs(minute * 60));
+        }
+        None
+    }
+}
+
+impl FromStr for Cron {
+    type Err = CronError;
+
+    fn from_str(expression: &str) -> Result<Self, Self::Err> {
+        let fields: Vec<&str> = expression.split_whitespace().collect();
+        let [minutes, hours, days, months, weekdays] = fields[..] else {
+            return Err(CronError::FieldCount(fields.len()));
+        };
+        let mut weekdays_mask = parse_field("day of week", weekdays, 0, 7)?;
+        if bit(weekdays_mask, 7) {
+            weekdays_mask = (weekdays_mask | 1) & !(1 << 7);
+        }
+        Ok(Self {
+            expression: fields.join(" "),
+            minutes: parse_field("minute", minutes, 0, 59)?,
+            hours: parse_field("hour", hours, 0, 23)?,
+            days: parse_field("day of month", days, 1, 31)?,
+            months: parse_field("month", months, 1, 12)?,
+            weekdays: weekdays_mask,
+            any_day: days == "*",
+            any_weekday: weekdays == "*",
+        })
+    }
+}
+
+impl std::fmt::Display for Cron {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.write_str(&self.expression)
+    }
+}
+
+const fn bit(mask: u64, value: u64) -> bool {
+    mask & (1 << value) != 0
+}
+
+fn parse_field(name: &'static str, field: &str, min: u64, max: u64) -> Result<u64, CronError> {
+    let invalid = || CronError::InvalidField(name, field.to_string());
+    let mut mask = 0;
+    for part in field.split(',') {
+        let (range, step) = match part.split_once('/') {
+            Some((range, step)) => (range, step.parse().map_err(|_| invalid())?),
+            None => (part, 1),
+        };
+        let (start, end) = match range {
+            "*" => (min, max),
+            range => match range.split_once('-') {
+                Some((start, end)) => (
+                    start.parse().map_err(|_| invalid())?,
+                    end.parse().map_err(|_| invalid())?,
+                ),
+                // A single value with a step runs from the value to the end of the range
+                None if step > 1 => (range.parse().map_err(|_| invalid())?, max),
+                None => {
+                    let value = range.parse().map_err(|_| invalid())?;
+                    (value, value)
+                }
+            },
+        };
+        if step == 0 || start < min || end > max || start > end {
+            return Err(invalid());
+        }
+        for value in (start..=end).step_by(usize::try_from(step).map_err(|_| invalid())?) {
+            mask |= 1 << value;
+        }
+    }
+    Ok(mask)
+}
+
+/// Converts days since the unix epoch to `(year, month, day)`
+const fn civil_from_days(days: u64) -> (u64, u64, u64) {
+    let days = days + 719_468;
+    let era = days / 146_097;
+    let day_of_era = days % 146_097;
+    let year_of_era =
+        (day_of_era - day_of_era / 1460 + day_of_era / 36_524 - day_of_era / 146_096) / 365;
+    let day_of_year = day_of_era - (365 * year_of_era + year_of_era / 4 - year_of_era / 100);
+    let month_index = (5 * day_of_year + 2) / 153;
+    let day = day_of_year - (153 * month_index + 2) / 5 + 1;
+    let month = if month_index < 10 {
+        month_index + 3
+    } else {
+        month_index - 9
+    };
+    let year = year_of_era + era * 400 + if month <= 2 { 1 } else { 0 };
+    (year, month, day)
+}
+
+#[derive(Debug, Clone)]
+/// Information about a scheduled task
+pub struct TaskInfo {
+    id: usize,
+    schedule: Schedule,
+    paused: bool,
+    runs: u64,
+}
+
+impl TaskInfo {
+    #[must_use]
+    /// Id of the task, tasks are numbered in the order they were added
+    pub const fn id(&self) -> usize {
+        self.id
+    }
+
+    #[must_use]
+    /// When the task runs
+    pub const fn schedule(&self) -> &Schedule {
+        &self.schedule
+    }
+
+    #[must_use]
+    /// Returns true if the task is paused
+    pub const fn paused(&self) -> bool {
+        self.paused
+    }
+
+    #[must_use]
+    /// Number of times the task has run
+    pub const fn runs(&self) -> u64 {
+        self.runs
+    }
+}
+
+impl IntoArma for TaskInfo {
+    fn to_arma(&self) -> Value {
+        Value::Array(vec![
+            Value::Number(self.id as f64),
+            self.schedule.to_string().to_arma(),
+            self.paused.to_arma(),
+            Value::Number(self.runs as f64),
+        ])
+    }
+}
+
+struct Task {
+    info: TaskInfo,
+    next: Option<Instant>,
+    handler: Arc<Mutex<Handler>>,
+}
+
+/// The thread running the tasks
+struct Running {
+    wake: Sender<()>,
+    thread: std::thread::JoinHandle<()>,
+    // Disconnected once the thread has finished
+    done: Receiver<()>,
+}
+
+#[derive(Default)]
+/// Runs the scheduled tasks of an extension on a background thread.
+///
+/// Started once Arma registers the extension callback, tasks can be paused and resumed
+/// from Arma through the `::tasks`, `::task_pause` and `::task_resume` commands.
+pub struct Scheduler {
+    tasks: Arc<Mutex<Vec<Task>>>,
+    thread: Mutex<Option<Running>>,
+}
+
+impl Scheduler {
+    fn lock(&self) -> std::sync::MutexGuard<'_, Vec<Task>> {
+        self.tasks
+            .lock()
+            .unwrap_or_else(std::sync::PoisonError::into_inner)
+    }
+
+    /// Add a task
+    /// # Panics
+    /// Panics if the schedule is [`Schedule::Every`] with a zero interval, the task would never let the thread rest
+    pub(crate) fn add(
+        &mut self,
+        schedule: Schedule,
+        handler: impl FnMut(Context) + Send + 'static,
+    ) {
+        assert!(
+            schedule != Schedule::Every(Duration::ZERO),
+            "the interval of a periodic task must not be zero"
+        );
+        let mut tasks = self.lock();
+        let id = tasks.len();
+        tasks.push(Task {
+            info: TaskInfo {
+                id,
+                schedule,
+                paused: false,
+                runs: 0,
+            },
+            next: None,
+            handler: Arc::new(Mutex::new(Box::new(handler))),
+        });
+    }
+
+    fn thread(&self) -> std::sync::MutexGuard<'_, Option<Running>> {
+        self.thread
+            .lock()
+            .unwrap_or_else(std::sync::PoisonError::into_inner)
+    }
+
+    /// Start running the tasks, each run receives a new context from `context`
+    pub(crate) fn start(&self, context: impl Fn() -> Context + Send + 'static) {
+        let mut running = self.thread();
+        if running.is_some() || self.lock().is_empty() {
+            return;
+        }
+        let now = Instant::now();
+        for task in self.lock().iter_mut() {
+            task.next = task.info.schedule.next(now);
+        }
+        let (wake, rx) = bounded(1);
+        let (done_tx, done) = bounded::<()>(0);
+        let tasks = self.tasks.clone();
+        let thread = std::thread::spawn(move || {
+            let _done = done_tx;
+            loop {
+                let now = Instant::now();
+                let (due, next) = {
+                    let mut tasks = tasks
+                        .lock()
+                        .unwrap_or_else(std::sync::PoisonError::into_inner);
+                    let mut due = Vec::new();
+                    for task in tasks.iter_mut().filter(|task| !task.info.paused) {
+                        if task.next.is_some_and(|next| next <= now) {
+                            task.info.runs += 1;
+                            task.next = match task.info.schedule {
+                                Schedule::After(_) => None,
+                                _ => task.info.schedule.next(now),
+                            };
+                            due.push((task.info.id, task.handler.clone()));
+                        }
+                    }
+                    let next = tasks
+                        .iter()
+                        .filter(|task| !task.info.paused)
+                        .filter_map(|task| task.next)
+                        .min();
+                    (due, next)
+                };
+                for (id, handler) in due {
+                    let mut handler = handler
+                        .lock()
+                        .unwrap_or_else(std::sync::PoisonError::into_inner);
+                    let context = context();
+                    if std::panic::catch_unwind(std::panic::AssertUnwindSafe(|| handler(context)))
+                        .is_err()
+                    {
+                        error!("scheduled task {id} panicked");
+                    }
+                }
+                let woken = match next {
+                    Some(next) => rx.recv_timeout(next.saturating_duration_since(Instant::now())),
+                    None => rx.recv().map_err(|_| RecvTimeoutError::Disconnected),
+                };
+                if let Err(RecvTimeoutError::Disconnected) = woken {
+                    break;
+                }
+            }
+        });
+        *running = Some(Running { wake, thread, done });
+    }
+
+    /// Stop the scheduler.
+    /// With `wait`, waits up to [`crate::SHUTDOWN_TIMEOUT`] for a running task to finish,
+    /// the thread is left running if it does not finish in time.
+    pub(crate) fn stop(&self, wait: bool) {
+        // Release the lock before joining, a running task may pause or resume tasks
+        let running = self.thread().take();
+        let Some(Running { wake, thread, done }) = running else {
+            return;
+        };
+        drop(wake);
+        if !wait {
+            return;
+        }
+        match done.recv_timeout(crate::SHUTDOWN_TIMEOUT) {
+            Err(RecvTimeoutError::Disconnected) => {
+                thread.join().expect("Failed to join scheduler thread");
+            }
+            _ => warn!("scheduler thread did not finish before shutdown"),
+        }
+    }
+
+    fn wake(&self) {
+        if let Some(Running { wake, .. }) = &*self.thread() {
+            let _ = wake.try_send(());
+        }
+    }
+
+    #[must_use]
+    /// Returns every scheduled task
+    pub fn tasks(&self) -> Vec<TaskInfo> {
+        self.lock().iter().map(|task| task.info.clone()).collect()
+    }
+
+    /// Pause a task, returns false if the task does not exist
+    pub fn pause(&self, id: usize) -> bool {
+        let mut tasks = self.lock();
+        let Some(task) = tasks.get_mut(id) else {
+            return false;
+        };
+        task.info.paused = true;
+        true
+    }
+
+    /// Resume a paused task, returns false if the task does not exist.
+    /// Periodic tasks wait a full interval before running again.
+    pub fn resume(&self, id: usize) -> bool {
+        let started = self.thread().is_some();
+        let mut tasks = self.lock();
+        let Some(task) = tasks.get_mut(id) else {
+            return false;
+        };
+        if task.info.paused && started && !matches!(task.info.schedule, Schedule::After(_)) {
+            task.next = task.info.schedule.next(Instant::now());
+        }
+        task.info.paused = false;
+        drop(tasks);
+        self.wake();
+        true
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn at(year: u64, month: u64, day: u64, hour: u64, minute: u64) -> SystemTime {
+        let days = (0..)
+            .find(|days| civil_from_days(*days) == (year, month, day))
+            .unwrap();
+        SystemTime::UNIX_EPOCH + Duration::from_secs(((days * 24 + hour) * 60 + minute) * 60)
+    }
+
+    #[test]
+    fn civil() {
+        assert_eq!(civil_from_days(0), (1970, 1, 1));
+        assert_eq!(civil_from_days(11_016), (2000, 2, 29));
+        assert_eq!(civil_from_days(20_744), (2026, 10, 18));
+    }
+
+    #[test]
+    fn cron_parse() {
+        assert!("* * * * *".parse::<Cron>().is_ok());
+        assert!("*/15 0-6,12 1 */2 1-5".parse::<Cron>().is_ok());
+        assert_eq!("* * * *".parse::<Cron>(), Err(CronError::FieldCount(4)));
+        assert_eq!(
+            "60 * * * *".parse::<Cron>(),
+            Err(CronError::InvalidField("minute", "60".to_string()))
+        );
+        assert!("*/0 * * * *".parse::<Cron>().is_err());
+        assert!("* 5-2 * * *".parse::<Cron>().is_err());
+        assert!("* * 0 * *".parse::<Cron>().is_err());
+    }
+
+    #[test]
+    fn cron_next() {
+        let cron: Cron = "*/15 * * * *".parse().unwrap();
+        assert_eq!(
+            cron.next_after(at(2026, 10, 18, 12, 0)),
+            Some(at(2026, 10, 18, 12, 15))
+        );
+        let cron: Cron = "30 2 * * *".parse().unwrap();
+        assert_eq!(
+            cron.next_after(at(2026, 10, 18, 12, 0)),
+            Some(at(2026, 10, 19, 2, 30))
+        );
+        // 2026-10-18 is a Sunday
+        let cron: Cron = "0 9 * * 1-5".parse().unwrap();
+        assert_eq!(
+            cron.next_after(at(2026, 10, 17, 12, 0)),
+            Some(at(2026, 10, 19, 9, 0))
+        );
+        let cron: Cron = "0 0 * * 7".parse().unwrap();
+        assert_eq!(
+            cron.next_after(at(2026, 10, 17, 12, 0)),
+            Some(at(2026, 10, 18, 0, 0))
+        );
+        let cron: Cron = "0 0 29 2 *".parse().unwrap();
+        assert_eq!(
+            cron.next_after(at(2026, 10, 18, 0, 0)),
+            Some(at(2028, 2, 29, 0, 0))
+        );
+        let cron: Cron = "0 0 31 2 *".parse().unwrap();
+        assert_eq!(cron.next_after(at(2026, 10, 18, 0, 0)), None);
+    }
+
+    #[test]
+    fn pause_resume() {
+        let mut scheduler = Scheduler::default();
+        scheduler.add(Schedule::Every(Duration::from_secs(1)), |_| {});
+        assert!(scheduler.pause(0));
+        assert!(scheduler.tasks()[0].paused());
+        assert!(scheduler.resume(0));
+        assert!(!scheduler.tasks()[0].paused());
+        assert!(!scheduler.pause(1));
+        assert!(!scheduler.resume(1));
+    }
+
+    #[test]
+    #[should_panic(expected = "must not be zero")]
+    fn every_zero() {
+        Scheduler::default().add(Schedule::Every(Duration::ZERO), |_| {});
+    }
+}
@@ -2,7 +2,9 @@
 
 use std::time::Duration;
 
-use crate::{CallbackMessage, Context, State, Value};
+use crate::{
+    Arguments, ArmaEvent, CallbackMessage, Context, FromArma, FromArmaError, State, Value,
+};
 
 use crate::{ArmaCallContext, Caller, Mission, Server, Source};
 
@@ -65,6 +67,30 @@ impl Extension {
         &self.0.group.state
     }
 
+    #[must_use]
+    /// Get a reference to the metrics collected for every command
+    pub fn metrics(&self) -> &crate::metrics::Metrics {
+        self.0.metrics()
+    }
+
+    #[must_use]
+    /// Get the feature flags needed by every command, see [`crate::Extension::feature_flags`]
+    pub fn feature_flags(&self) -> crate::FeatureFlags {
+        self.0.feature_flags()
+    }
+
+    #[must_use]
+    /// Get the feature flags needed by the command at `path`, see [`crate::Extension::command_flags`]
+    pub fn command_flags(&self, path: &str) -> Option<crate::FeatureFlags> {
+        self.0.command_flags(path)
+    }
+
+    #[must_use]
+    /// Get a reference to the background jobs of the extension
+    pub fn jobs(&self) -> &crate::job::JobManager {
+        self.0.jobs()
+    }
+
     #[must_use]
     #[allow(clippy::too_many_arguments)]
     /// Call a function with Arma call context.
@@ -116,14 +142,12 @@ impl Extension {
                 .map(|s| std::ffi::CString::new(s).unwrap().into_raw())
                 .collect::<Vec<*mut i8>>()
         });
-        let res = self.0.group.handle(
+        let res = self.0.dispatch(
             self.context(),
-            &self.0.context_manager,
             function,
             output.as_mut_ptr(),
             BUFFER_SIZE,
-            args_pointer.as_mut().map(Vec::as_mut_ptr),
-            len,
+            unsafe { Arguments::from_raw(args_pointer.as_mut().map(Vec::as_mut_ptr), len) },
         );
         if let Some(args) = args_pointer {
             for arg in args {
@@ -131,7 +155,7 @@ impl Extension {
             }
         }
         (
-            std::ffi::CStr::from_ptr(output.as_ptr())
+            unsafe { std::ffi::CStr::from_ptr(output.as_ptr()) }
                 .to_str()
                 .unwrap()
                 .to_string(),
@@ -165,4 +189,27 @@ impl Extension {
             }
         }
     }
+
+    /// Wait for an event sent with [`Context::emit`] and decode its data with [`FromArma`]
+    ///
+    /// Other callbacks received in the meantime are ignored.
+    /// Returns `Result::Err` if the data could not be decoded,
+    /// or `Result::Timeout` if no matching event was received before the timeout.
+    pub fn expect_event<E>(&self, timeout: Duration) -> Result<E, FromArmaError>
+    where
+        E: ArmaEvent + FromArma,
+    {
+        self.callback_handler(
+            |name, func, data| {
+                if name != E::NAME || func != E::FUNC {
+                    return Result::Continue;
+                }
+                match E::from_arma(data.map_or_else(String::new, |data| data.to_string())) {
+                    Ok(event) => Result::Ok(event),
+                    Err(error) => Result::Err(error),
+                }
+            },
+            timeout,
+        )
+    }
 }
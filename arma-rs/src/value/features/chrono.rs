@@ -39,3 +39,5 @@ impl FromArma for NaiveDateTime {
         ))
     }
 }
+
+crate::value::from_arma::impl_from_arma_ref_owned!(NaiveDateTime);
@@ -34,6 +34,8 @@ impl FromArma for serde_json::Value {
     }
 }
 
+crate::value::from_arma::impl_from_arma_ref_owned!(serde_json::Value);
+
 impl Value {
     /// Convert a Value to a serde_json::Value
     pub fn to_json(&self) -> serde_json::Value {
@@ -15,3 +15,5 @@ impl FromArma for uuid::Uuid {
         Self::parse_str(s).map_err(FromArmaError::custom)
     }
 }
+
+crate::value::from_arma::impl_from_arma_ref_owned!(uuid::Uuid);
@@ -1,34 +1,43 @@
-use crate::Value;
+use std::borrow::Cow;
 
-fn split_array(s: &str) -> Vec<String> {
+/// Split the contents of an array on its top level commas, without copying
+fn split_array(s: &str) -> Vec<&str> {
     let mut nest = 0;
     let mut parts = Vec::new();
-    let mut part = String::new();
+    let mut start = 0;
     let mut in_string = false;
-    for c in s.chars() {
-        if c == '[' {
-            part.push(c);
-            nest += 1;
-        } else if c == ']' {
-            nest -= 1;
-            part.push(c);
-        } else if c == '"' {
-            in_string = !in_string;
-            part.push(c);
-        } else if c == ',' && nest == 0 && !in_string {
-            parts.push(part.trim().to_string());
-            part = String::new();
-        } else {
-            part.push(c);
+    for (i, c) in s.bytes().enumerate() {
+        match c {
+            b'[' => nest += 1,
+            b']' => nest -= 1,
+            b'"' => in_string = !in_string,
+            b',' if nest == 0 && !in_string => {
+                parts.push(s[start..i].trim());
+                start = i + 1;
+            }
+            _ => {}
         }
     }
-    let part = part.trim().to_string();
+    let part = s[start..].trim();
     if !part.is_empty() {
         parts.push(part);
     }
     parts
 }
 
+#[doc(hidden)]
+/// Elements of an array from Arma, borrowed from the input. Used by the derive macros.
+/// # Errors
+/// Will return an error if the input is not surrounded by brackets.
+pub fn array_elements(s: &str) -> Result<Vec<&str>, FromArmaError> {
+    let source = s
+        .strip_prefix('[')
+        .ok_or(FromArmaError::MissingBracket(true))?
+        .strip_suffix(']')
+        .ok_or(FromArmaError::MissingBracket(false))?;
+    Ok(split_array(source))
+}
+
 /// Error type for [`FromArma`]
 #[derive(Debug, PartialEq, Eq)]
 pub enum FromArmaError {
@@ -93,28 +102,94 @@ pub trait FromArma: Sized {
     /// # Errors
     /// Will return an error if the value cannot be converted.
     fn from_arma(s: String) -> Result<Self, FromArmaError>;
+
+    /// Converts a borrowed value from Arma to a Rust value.
+    ///
+    /// Collections use this for their elements, the default implementation copies the input into a `String`.
+    /// Types that can be parsed without an owned input should override it.
+    /// # Errors
+    /// Will return an error if the value cannot be converted.
+    fn from_arma_str(s: &str) -> Result<Self, FromArmaError> {
+        Self::from_arma(s.to_string())
+    }
+}
+
+/// A trait for converting a value from Arma to a Rust value that may borrow from the input.
+///
+/// It is implemented for `&str` and `Cow<str>`, which borrow the contents of a string instead of copying them,
+/// for tuples, arrays, `Vec` and `HashMap` of such types, and for the built in [`FromArma`] types.
+/// Deriving [`FromArma`] also implements this trait, types implementing [`FromArma`] by hand
+/// can implement it with [`FromArma::from_arma_str`].
+pub trait FromArmaRef<'a>: Sized {
+    /// Converts a value from Arma to a Rust value, borrowing from the input where possible.
+    /// # Errors
+    /// Will return an error if the value cannot be converted.
+    fn from_arma_ref(s: &'a str) -> Result<Self, FromArmaError>;
 }
 
+/// Implement [`FromArmaRef`] for owned types with [`FromArma::from_arma_str`]
+macro_rules! impl_from_arma_ref_owned {
+    ($($t:ty),*) => {
+        $(
+            impl<'a> $crate::FromArmaRef<'a> for $t {
+                fn from_arma_ref(s: &'a str) -> Result<Self, $crate::FromArmaError> {
+                    <Self as $crate::FromArma>::from_arma_str(s)
+                }
+            }
+        )*
+    };
+}
+pub(crate) use impl_from_arma_ref_owned;
+
+/// Contents of a string from Arma, still containing escaped quotes
 #[cfg(not(any(test, doc, debug_assertions)))]
-impl FromArma for String {
-    fn from_arma(s: String) -> Result<Self, FromArmaError> {
-        let Some(s) = s.strip_prefix('"').and_then(|s| s.strip_suffix('"')) else {
-            return Err(FromArmaError::InvalidPrimitive(String::from(
-                "missing '\"' at start or end of string",
-            )));
-        };
-        Ok(s.replace("\"\"", "\""))
-    }
+fn unquote(s: &str) -> Result<&str, FromArmaError> {
+    s.strip_prefix('"')
+        .and_then(|s| s.strip_suffix('"'))
+        .ok_or_else(|| {
+            FromArmaError::InvalidPrimitive(String::from("missing '\"' at start or end of string"))
+        })
 }
 
+/// Contents of a string from Arma, still containing escaped quotes
 #[cfg(any(test, doc, debug_assertions))]
+#[allow(clippy::unnecessary_wraps)]
+fn unquote(s: &str) -> Result<&str, FromArmaError> {
+    Ok(s.strip_prefix('"')
+        .and_then(|s| s.strip_suffix('"'))
+        .unwrap_or(s))
+}
+
 impl FromArma for String {
     fn from_arma(s: String) -> Result<Self, FromArmaError> {
-        let s = s
-            .strip_prefix('"')
-            .and_then(|s| s.strip_suffix('"'))
-            .unwrap_or(&s);
-        Ok(s.replace("\"\"", "\""))
+        Self::from_arma_str(&s)
+    }
+
+    fn from_arma_str(s: &str) -> Result<Self, FromArmaError> {
+        Ok(unquote(s)?.replace("\"\"", "\""))
+    }
+}
+
+impl<'a> FromArmaRef<'a> for Cow<'a, str> {
+    fn from_arma_ref(s: &'a str) -> Result<Self, FromArmaError> {
+        let s = unquote(s)?;
+        if s.contains("\"\"") {
+            Ok(Cow::Owned(s.replace("\"\"", "\"")))
+        } else {
+            Ok(Cow::Borrowed(s))
+        }
+    }
+}
+
+impl<'a> FromArmaRef<'a> for &'a str {
+    fn from_arma_ref(s: &'a str) -> Result<Self, FromArmaError> {
+        let s = unquote(s)?;
+        if s.contains("\"\"") {
+            return Err(FromArmaError::InvalidPrimitive(String::from(
+                "string contains escaped quotes, use Cow<str> or String",
+            )));
+        }
+        Ok(s)
     }
 }
 
@@ -123,7 +198,11 @@ macro_rules! impl_from_arma {
         $(
             impl FromArma for $t {
                 fn from_arma(s: String) -> Result<Self, FromArmaError> {
-                    let s = s.strip_suffix('"').and_then(|s| s.strip_prefix('"')).unwrap_or(&s);
+                    Self::from_arma_str(&s)
+                }
+
+                fn from_arma_str(s: &str) -> Result<Self, FromArmaError> {
+                    let s = s.strip_suffix('"').and_then(|s| s.strip_prefix('"')).unwrap_or(s);
                     s.parse::<Self>().map_err(|e| FromArmaError::InvalidPrimitive(e.to_string()))
                 }
             }
@@ -131,13 +210,18 @@ macro_rules! impl_from_arma {
     };
 }
 impl_from_arma!(f32, f64, bool, char);
+impl_from_arma_ref_owned!(String, char, bool, f32, f64);
 
 macro_rules! impl_from_arma_number {
     ($($t:ty),*) => {
         $(
             impl FromArma for $t {
                 fn from_arma(s: String) -> Result<Self, FromArmaError> {
-                    let s = s.strip_suffix('"').and_then(|s| s.strip_prefix('"')).unwrap_or(&s);
+                    Self::from_arma_str(&s)
+                }
+
+                fn from_arma_str(s: &str) -> Result<Self, FromArmaError> {
+                    let s = s.strip_suffix('"').and_then(|s| s.strip_prefix('"')).unwrap_or(s);
                     if s.contains("e") {
                         // parse exponential notation
                         let mut parts = s.split('e');
@@ -160,6 +244,9 @@ macro_rules! impl_from_arma_number {
 impl_from_arma_number!(
     i8, i16, i32, i64, i128, isize, u8, u16, u32, u64, u128, usize
 );
+impl_from_arma_ref_owned!(
+    i8, i16, i32, i64, i128, isize, u8, u16, u32, u64, u128, usize
+);
 
 macro_rules! impl_from_arma_tuple {
     { $c: expr, $($t:ident)* } => {
@@ -168,10 +255,39 @@ macro_rules! impl_from_arma_tuple {
             $($t: FromArma),*
         {
             fn from_arma(s: String) -> Result<Self, FromArmaError> {
-                let v: [Value; $c] = FromArma::from_arma(s)?;
-                let mut iter = v.iter();
+                Self::from_arma_str(&s)
+            }
+
+            fn from_arma_str(s: &str) -> Result<Self, FromArmaError> {
+                let elements = array_elements(s)?;
+                if elements.len() != $c {
+                    return Err(FromArmaError::InvalidLength {
+                        expected: $c,
+                        actual: elements.len(),
+                    });
+                }
+                let mut iter = elements.into_iter();
                 Ok((
-                    $($t::from_arma(iter.next().unwrap().to_string())?),*
+                    $($t::from_arma_str(iter.next().unwrap())?),*
+                ))
+            }
+        }
+
+        impl<'a, $($t),*> FromArmaRef<'a> for ($($t),*)
+        where
+            $($t: FromArmaRef<'a>),*
+        {
+            fn from_arma_ref(s: &'a str) -> Result<Self, FromArmaError> {
+                let elements = array_elements(s)?;
+                if elements.len() != $c {
+                    return Err(FromArmaError::InvalidLength {
+                        expected: $c,
+                        actual: elements.len(),
+                    });
+                }
+                let mut iter = elements.into_iter();
+                Ok((
+                    $($t::from_arma_ref(iter.next().unwrap())?),*
                 ))
             }
         }
@@ -209,16 +325,26 @@ where
     T: FromArma,
 {
     fn from_arma(s: String) -> Result<Self, FromArmaError> {
-        let source = s
-            .strip_prefix('[')
-            .ok_or(FromArmaError::MissingBracket(true))?
-            .strip_suffix(']')
-            .ok_or(FromArmaError::MissingBracket(false))?;
-        let parts = split_array(source);
-        parts.iter().try_fold(Self::new(), |mut acc, p| {
-            acc.push(T::from_arma(p.clone())?);
-            Ok(acc)
-        })
+        Self::from_arma_str(&s)
+    }
+
+    fn from_arma_str(s: &str) -> Result<Self, FromArmaError> {
+        array_elements(s)?
+            .into_iter()
+            .map(T::from_arma_str)
+            .collect()
+    }
+}
+
+impl<'a, T> FromArmaRef<'a> for Vec<T>
+where
+    T: FromArmaRef<'a>,
+{
+    fn from_arma_ref(s: &'a str) -> Result<Self, FromArmaError> {
+        array_elements(s)?
+            .into_iter()
+            .map(T::from_arma_ref)
+            .collect()
     }
 }
 
@@ -227,15 +353,31 @@ where
     T: FromArma,
 {
     fn from_arma(s: String) -> Result<Self, FromArmaError> {
-        let v: Vec<T> = FromArma::from_arma(s)?;
-        let len = v.len();
-        v.try_into().map_err(|_| FromArmaError::InvalidLength {
-            expected: N,
-            actual: len,
-        })
+        Self::from_arma_str(&s)
+    }
+
+    fn from_arma_str(s: &str) -> Result<Self, FromArmaError> {
+        array_from_vec(FromArma::from_arma_str(s)?)
     }
 }
 
+impl<'a, T, const N: usize> FromArmaRef<'a> for [T; N]
+where
+    T: FromArmaRef<'a>,
+{
+    fn from_arma_ref(s: &'a str) -> Result<Self, FromArmaError> {
+        array_from_vec(FromArmaRef::from_arma_ref(s)?)
+    }
+}
+
+fn array_from_vec<T, const N: usize>(v: Vec<T>) -> Result<[T; N], FromArmaError> {
+    let len = v.len();
+    v.try_into().map_err(|_| FromArmaError::InvalidLength {
+        expected: N,
+        actual: len,
+    })
+}
+
 impl<K, V, S> FromArma for std::collections::HashMap<K, V, S>
 where
     K: FromArma + Eq + std::hash::Hash,
@@ -243,12 +385,24 @@ where
     S: std::hash::BuildHasher + Default,
 {
     fn from_arma(s: String) -> Result<Self, FromArmaError> {
-        let data: Vec<(K, V)> = FromArma::from_arma(s)?;
-        let mut ret = Self::default();
-        for (k, v) in data {
-            ret.insert(k, v);
-        }
-        Ok(ret)
+        Self::from_arma_str(&s)
+    }
+
+    fn from_arma_str(s: &str) -> Result<Self, FromArmaError> {
+        let data: Vec<(K, V)> = FromArma::from_arma_str(s)?;
+        Ok(data.into_iter().collect())
+    }
+}
+
+impl<'a, K, V, S> FromArmaRef<'a> for std::collections::HashMap<K, V, S>
+where
+    K: FromArmaRef<'a> + Eq + std::hash::Hash,
+    V: FromArmaRef<'a>,
+    S: std::hash::BuildHasher + Default,
+{
+    fn from_arma_ref(s: &'a str) -> Result<Self, FromArmaError> {
+        let data: Vec<(K, V)> = FromArmaRef::from_arma_ref(s)?;
+        Ok(data.into_iter().collect())
     }
 }
 
@@ -383,6 +537,51 @@ mod tests {
         );
     }
 
+    #[test]
+    fn parse_borrowed() {
+        let input = r#"["alpha", "say ""hi""", "bravo"]"#;
+        let parsed = <Vec<Cow<str>>>::from_arma_ref(input).unwrap();
+        assert!(matches!(parsed[0], Cow::Borrowed("alpha")));
+        assert_eq!(parsed[1], r#"say "hi""#);
+        assert!(matches!(parsed[2], Cow::Borrowed("bravo")));
+        assert!(<Vec<&str>>::from_arma_ref(input).is_err());
+        let parsed = <Vec<&str>>::from_arma_ref(r#"["alpha", "bravo"]"#).unwrap();
+        assert_eq!(parsed, vec!["alpha", "bravo"]);
+        assert_eq!(<&str>::from_arma_ref(r#""""#), Ok(""));
+        assert_eq!(
+            <(u8, String)>::from_arma_ref(r#"[1, "a"]"#),
+            Ok((1, String::from("a")))
+        );
+    }
+
+    #[test]
+    fn parse_borrowed_collections() {
+        let input = r#"["alpha", 3]"#;
+        let (name, count) = <(&str, u32)>::from_arma_ref(input).unwrap();
+        assert_eq!((name, count), ("alpha", 3));
+        assert!(std::ptr::eq(name, &input[2..7]));
+
+        let parsed = <Vec<Vec<&str>>>::from_arma_ref(r#"[["a", "b"], [], ["c"]]"#).unwrap();
+        assert_eq!(parsed, vec![vec!["a", "b"], vec![], vec!["c"]]);
+        assert_eq!(
+            <[(&str, Cow<str>); 1]>::from_arma_ref(r#"[["key", "say ""hi"""]]"#),
+            Ok([("key", Cow::Owned(String::from(r#"say "hi""#)))])
+        );
+        let map = <std::collections::HashMap<&str, Vec<u8>>>::from_arma_ref(r#"[["a", [1, 2]]]"#)
+            .unwrap();
+        assert_eq!(map["a"], vec![1, 2]);
+    }
+
+    #[test]
+    fn parse_nested_arrays() {
+        let input = format!("[{}]", vec!["[1, [2, 3], \"a,b\"]"; 1000].join(", "));
+        let parsed = <Vec<(u8, [u8; 2], String)>>::from_arma_str(&input).unwrap();
+        assert_eq!(parsed.len(), 1000);
+        assert_eq!(parsed[999], (1, [2, 3], String::from("a,b")));
+        assert_eq!(array_elements("[]"), Ok(vec![]));
+        assert_eq!(array_elements("[1,,2]"), Ok(vec!["1", "", "2"]));
+    }
+
     #[test]
     fn parse_string() {
         assert_eq!(
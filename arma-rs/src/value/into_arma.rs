@@ -6,6 +6,7 @@ pub trait IntoArma {
     fn to_arma(&self) -> Value;
 }
 
+/// A value that is returned to Arma as is, see [`Value::direct`]
 pub struct DirectReturn(Value);
 impl Value {
     /// A workaround to return a value directly to Arma
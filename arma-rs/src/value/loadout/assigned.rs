@@ -97,6 +97,8 @@ impl FromArma for AssignedItems {
         )
     }
 }
+
+crate::value::from_arma::impl_from_arma_ref_owned!(AssignedItems);
 impl IntoArma for AssignedItems {
     fn to_arma(&self) -> Value {
         Value::Array(vec![
@@ -65,6 +65,8 @@ impl FromArma for Container {
         <(String, Vec<InventoryItem>)>::from_arma(s).map(|(name, items)| Self(Some((name, items))))
     }
 }
+
+crate::value::from_arma::impl_from_arma_ref_owned!(Container);
 impl IntoArma for Container {
     fn to_arma(&self) -> Value {
         self.0.as_ref().map_or_else(
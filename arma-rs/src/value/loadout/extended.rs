@@ -55,6 +55,8 @@ impl FromArma for CBAExtended {
     }
 }
 
+crate::value::from_arma::impl_from_arma_ref_owned!(CBAExtended);
+
 impl crate::IntoArma for CBAExtended {
     fn to_arma(&self) -> Value {
         match &self.0 {
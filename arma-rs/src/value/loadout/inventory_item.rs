@@ -91,6 +91,8 @@ impl FromArma for InventoryItem {
         }
     }
 }
+
+crate::value::from_arma::impl_from_arma_ref_owned!(InventoryItem);
 impl IntoArma for InventoryItem {
     fn to_arma(&self) -> Value {
         match self {
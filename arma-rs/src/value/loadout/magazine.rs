@@ -56,6 +56,8 @@ impl FromArma for Magazine {
         <(String, u32)>::from_arma(s).map(|(name, count)| Self(Some((name, count))))
     }
 }
+
+crate::value::from_arma::impl_from_arma_ref_owned!(Magazine);
 impl IntoArma for Magazine {
     fn to_arma(&self) -> Value {
         self.0.as_ref().map_or_else(
@@ -315,6 +315,8 @@ impl FromArma for Loadout {
         vanilla
     }
 }
+
+crate::value::from_arma::impl_from_arma_ref_owned!(Loadout);
 impl IntoArma for Loadout {
     fn to_arma(&self) -> Value {
         let core = vec![
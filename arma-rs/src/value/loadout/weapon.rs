@@ -222,6 +222,8 @@ impl FromArma for Weapon {
         )
     }
 }
+
+crate::value::from_arma::impl_from_arma_ref_owned!(Weapon);
 impl IntoArma for Weapon {
     fn to_arma(&self) -> Value {
         self.0.as_ref().map_or_else(
@@ -5,7 +5,7 @@ mod from_arma;
 mod into_arma;
 pub mod loadout;
 
-pub use from_arma::{FromArma, FromArmaError};
+pub use from_arma::{FromArma, FromArmaError, FromArmaRef, array_elements};
 pub use into_arma::{DirectReturn, IntoArma};
 
 #[derive(Debug, Clone, PartialEq, PartialOrd)]
@@ -40,16 +40,27 @@ impl Display for Value {
         match self {
             Self::Null => write!(f, "null"),
             Self::Number(n) => write!(f, "{n}"),
-            Self::Array(a) => write!(
-                f,
-                "[{}]",
-                a.iter()
-                    .map(ToString::to_string)
-                    .collect::<Vec<String>>()
-                    .join(",")
-            ),
+            Self::Array(a) => {
+                f.write_str("[")?;
+                for (i, value) in a.iter().enumerate() {
+                    if i > 0 {
+                        f.write_str(",")?;
+                    }
+                    value.fmt(f)?;
+                }
+                f.write_str("]")
+            }
             Self::Boolean(b) => write!(f, "{b}"),
-            Self::String(s) => write!(f, "\"{}\"", s.replace('\"', "\"\"")),
+            Self::String(s) => {
+                f.write_str("\"")?;
+                for (i, part) in s.split('"').enumerate() {
+                    if i > 0 {
+                        f.write_str("\"\"")?;
+                    }
+                    f.write_str(part)?;
+                }
+                f.write_str("\"")
+            }
             Self::Unknown(s) => write!(f, "Unknown({s})"),
         }
     }
@@ -57,17 +68,23 @@ impl Display for Value {
 
 impl FromArma for Value {
     fn from_arma(s: String) -> Result<Self, FromArmaError> {
+        Self::from_arma_str(&s)
+    }
+
+    fn from_arma_str(s: &str) -> Result<Self, FromArmaError> {
         match s.chars().next() {
             Some('n') => Ok(Self::Null),
-            Some('t' | 'f') => Ok(Self::Boolean(<bool>::from_arma(s)?)),
-            Some('0'..='9' | '-') => Ok(Self::Number(<f64>::from_arma(s)?)),
-            Some('[') => Ok(Self::Array(<Vec<Self>>::from_arma(s)?)),
-            Some('"') => Ok(Self::String(<String>::from_arma(s)?)),
-            _ => Ok(Self::Unknown(s)),
+            Some('t' | 'f') => Ok(Self::Boolean(<bool>::from_arma_str(s)?)),
+            Some('0'..='9' | '-') => Ok(Self::Number(<f64>::from_arma_str(s)?)),
+            Some('[') => Ok(Self::Array(<Vec<Self>>::from_arma_str(s)?)),
+            Some('"') => Ok(Self::String(<String>::from_arma_str(s)?)),
+            _ => Ok(Self::Unknown(s.to_string())),
         }
     }
 }
 
+crate::value::from_arma::impl_from_arma_ref_owned!(Value);
+
 #[cfg(test)]
 mod tests {
     use super::*;
@@ -0,0 +1,8 @@
+use arma_rs::arma;
+
+#[arma]
+fn init(version: String) -> arma_rs::Extension {
+    arma_rs::Extension::build().version(version).finish()
+}
+
+fn main() {}
@@ -0,0 +1,8 @@
+use arma_rs::arma;
+
+#[arma(name = "")]
+fn init() -> arma_rs::Extension {
+    arma_rs::Extension::build().finish()
+}
+
+fn main() {}
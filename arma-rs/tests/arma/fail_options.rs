@@ -0,0 +1,8 @@
+use arma_rs::arma;
+
+#[arma(verison = "1.0.0")]
+fn init() -> arma_rs::Extension {
+    arma_rs::Extension::build().finish()
+}
+
+fn main() {}
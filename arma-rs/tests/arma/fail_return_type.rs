@@ -0,0 +1,8 @@
+use arma_rs::arma;
+
+#[arma]
+fn init() -> Option<arma_rs::Extension> {
+    Some(arma_rs::Extension::build().finish())
+}
+
+fn main() {}
@@ -0,0 +1,13 @@
+use arma_rs::arma;
+
+#[arma]
+fn init() -> arma_rs::Extension {
+    arma_rs::Extension::build().finish()
+}
+
+fn main() {
+    let mut output = [0; 64];
+    unsafe { RVExtensionVersion(output.as_mut_ptr(), output.len()) };
+    let version = unsafe { std::ffi::CStr::from_ptr(output.as_ptr()) };
+    assert_eq!(version.to_str(), Ok(env!("CARGO_PKG_VERSION")));
+}
@@ -0,0 +1,14 @@
+use arma_rs::{Extension, arma};
+
+#[arma(version = "1.2.3", name = "my_ext", no_args = false)]
+fn init() -> Extension {
+    Extension::build().finish()
+}
+
+fn main() {
+    let mut output = [0; 64];
+    unsafe { RVExtensionVersion(output.as_mut_ptr(), output.len()) };
+    let version = unsafe { std::ffi::CStr::from_ptr(output.as_ptr()) };
+    assert_eq!(version.to_str(), Ok("1.2.3"));
+    assert_eq!(arma_rs::module::module_name().as_deref(), Some("my_ext"));
+}
@@ -610,4 +610,112 @@ mod derive {
             );
         }
     }
+
+    mod borrowed {
+        use std::borrow::Cow;
+
+        use arma_rs::FromArmaRef;
+
+        use super::*;
+
+        #[test]
+        fn map() {
+            #[derive(FromArmaRef, Debug, PartialEq)]
+            struct DeriveTest<'a> {
+                name: &'a str,
+                quote: Cow<'a, str>,
+                tags: Vec<&'a str>,
+                #[arma(default)]
+                count: u32,
+            }
+
+            let input = r#"[["name", "Alpha"], ["quote", "say ""hi"""], ["tags", ["a", "b"]]]"#;
+            let derived = DeriveTest::from_arma_ref(input).unwrap();
+            assert_eq!(
+                derived,
+                DeriveTest {
+                    name: "Alpha",
+                    quote: Cow::Owned(String::from(r#"say "hi""#)),
+                    tags: vec!["a", "b"],
+                    count: 0,
+                }
+            );
+            assert!(std::ptr::eq(derived.name, &input[11..16]));
+            assert_eq!(
+                DeriveTest::from_arma_ref(r#"[["name", "a"], ["name", "b"]]"#),
+                Err(FromArmaError::DuplicateField(String::from("name")))
+            );
+        }
+
+        #[test]
+        fn tuple_and_newtype() {
+            #[derive(FromArmaRef, Debug, PartialEq)]
+            struct Tuple<'a>(&'a str, u8);
+
+            #[derive(FromArmaRef, Debug, PartialEq)]
+            struct NewType<'a>(Cow<'a, str>);
+
+            #[derive(FromArmaRef, Debug, PartialEq)]
+            struct Owned(String);
+
+            assert_eq!(Tuple::from_arma_ref(r#"["a", 1]"#), Ok(Tuple("a", 1)));
+            assert_eq!(
+                Tuple::from_arma_ref(r#"["a"]"#),
+                Err(FromArmaError::InvalidLength {
+                    expected: 2,
+                    actual: 1
+                })
+            );
+            assert_eq!(
+                NewType::from_arma_ref(r#""b""#),
+                Ok(NewType(Cow::Borrowed("b")))
+            );
+            assert_eq!(Owned::from_arma_ref(r#""c""#), Ok(Owned(String::from("c"))));
+        }
+
+        #[test]
+        fn owned_fields() {
+            #[derive(FromArma, Debug, PartialEq)]
+            struct Position(f32, f32);
+
+            #[derive(FromArmaRef, Debug, PartialEq)]
+            struct Marker<'a>(&'a str, Vec<Position>);
+
+            assert_eq!(
+                Marker::from_arma_ref(r#"["base", [[1, 2], [3, 4]]]"#),
+                Ok(Marker("base", vec![Position(1.0, 2.0), Position(3.0, 4.0)]))
+            );
+        }
+    }
+
+    mod event {
+        use arma_rs::ArmaEvent;
+
+        use super::*;
+
+        #[test]
+        fn defaults() {
+            #[derive(IntoArma, ArmaEvent)]
+            struct PlayerJoined(String);
+
+            #[derive(IntoArma, ArmaEvent)]
+            struct HTTPRequest2Done(String);
+
+            assert_eq!(PlayerJoined::NAME, "arma-rs");
+            assert_eq!(PlayerJoined::FUNC, "player_joined");
+            assert_eq!(HTTPRequest2Done::FUNC, "http_request2_done");
+        }
+
+        #[test]
+        fn attributes() {
+            #[derive(IntoArma, ArmaEvent)]
+            #[arma_event(name = "my_ext", func = "joined")]
+            struct PlayerJoined {
+                uid: String,
+            }
+
+            assert_eq!(PlayerJoined::NAME, "my_ext");
+            assert_eq!(PlayerJoined::FUNC, "joined");
+        }
+    }
 }
@@ -0,0 +1,18 @@
+use arma_rs::{ArmaEvent, IntoArma};
+
+#[derive(IntoArma, ArmaEvent)]
+#[arma_event(unknown = "value")]
+struct Unknown(u32);
+
+#[derive(IntoArma, ArmaEvent)]
+#[arma_event(name = "one", name = "two")]
+struct Duplicate(u32);
+
+#[derive(IntoArma, ArmaEvent)]
+#[arma_event(func = "")]
+struct Empty(u32);
+
+#[derive(ArmaEvent)]
+struct NotIntoArma(u32);
+
+fn main() {}
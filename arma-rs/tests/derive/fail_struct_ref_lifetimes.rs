@@ -0,0 +1,9 @@
+use arma_rs::FromArmaRef;
+
+#[derive(FromArmaRef)]
+struct TwoLifetimes<'a, 'b> {
+    first: &'a str,
+    second: &'b str,
+}
+
+fn main() {}
@@ -46,7 +46,7 @@ mod extension {
 
         #[test]
         fn extension() {
-            let mut extension = Extension::build()
+            let extension = Extension::build()
                 .command("hello", || -> &'static str { "Hello" })
                 .command("welcome", |name: String| -> String {
                     format!("Welcome {name}")
@@ -116,6 +116,542 @@ mod extension {
         }
     }
 
+    #[test]
+    fn c_interface_lifecycle() {
+        let extension = Extension::build()
+            .on_callback_registered(|ctx| {
+                ctx.callback_null("lifecycle", "registered").unwrap();
+            })
+            .on_shutdown(|ctx| {
+                ctx.callback_null("lifecycle", "shutdown").unwrap();
+            })
+            .finish();
+        platform_extern!(
+            fn callback(name: *const i8, func: *const i8, data: *const i8) -> i32 {
+                callback_handler("c_interface_lifecycle".to_string(), name, func, data)
+            }
+        );
+        extension.register_callback(callback);
+        extension.run_callbacks();
+        extension.shutdown();
+
+        // Callbacks queued by the shutdown hook are sent before shutdown returns
+        let stack = get_callback_stack();
+        let stack = stack.read().unwrap();
+        let calls = stack.get("c_interface_lifecycle").unwrap();
+        assert_eq!(
+            calls
+                .iter()
+                .map(|(_, func, _)| func.as_str())
+                .collect::<Vec<_>>(),
+            vec!["registered", "shutdown"]
+        );
+    }
+
+    #[test]
+    fn c_interface_callback_retry() {
+        use std::{
+            sync::atomic::{AtomicUsize, Ordering},
+            time::Duration,
+        };
+
+        use arma_rs::callback::CallbackConfig;
+
+        static ATTEMPTS: AtomicUsize = AtomicUsize::new(0);
+
+        let extension = Extension::build()
+            .callback_queue(
+                CallbackConfig::new().backoff(Duration::from_millis(1), Duration::from_millis(4)),
+            )
+            .finish();
+        platform_extern!(
+            fn callback(name: *const i8, func: *const i8, data: *const i8) -> i32 {
+                // Arma's queue is full for the first two attempts
+                if ATTEMPTS.fetch_add(1, Ordering::SeqCst) < 2 {
+                    return -1;
+                }
+                callback_handler("c_interface_callback_retry".to_string(), name, func, data)
+            }
+        );
+        extension.register_callback(callback);
+        extension.run_callbacks();
+        extension.context().callback_null("retry", "fired").unwrap();
+        extension.shutdown();
+
+        let stack = get_callback_stack();
+        assert_eq!(
+            stack
+                .read()
+                .unwrap()
+                .get("c_interface_callback_retry")
+                .map(Vec::len),
+            Some(1)
+        );
+        assert_eq!(ATTEMPTS.load(Ordering::SeqCst), 3);
+        let stats = extension.callback_stats();
+        assert_eq!(stats.retried(), 1);
+        assert_eq!(stats.dropped(), 0);
+        assert_eq!(stats.queued(), 0);
+    }
+
+    #[test]
+    fn c_interface_callback_chunks() {
+        use arma_rs::{FromArma, Value, callback::CallbackConfig};
+
+        let extension = Extension::build()
+            .callback_queue(CallbackConfig::new().chunk_size(100))
+            .finish();
+        platform_extern!(
+            fn callback(name: *const i8, func: *const i8, data: *const i8) -> i32 {
+                callback_handler("c_interface_callback_chunks".to_string(), name, func, data)
+            }
+        );
+        extension.register_callback(callback);
+        extension.run_callbacks();
+        let context = extension.context();
+        assert_eq!(context.callback_len(), 100);
+        let data = "x".repeat(250);
+        context
+            .callback_data("chunks", "large", data.clone())
+            .unwrap();
+        context.callback_data("chunks", "small", "fits").unwrap();
+        extension.shutdown();
+
+        let stack = get_callback_stack();
+        let stack = stack.read().unwrap();
+        let calls = stack.get("c_interface_callback_chunks").unwrap();
+        let (last, fragments) = calls.split_last().unwrap();
+        assert_eq!(last, &("chunks".into(), "small".into(), "fits".into()));
+        assert!(fragments.len() > 2);
+        let mut joined = String::new();
+        for (name, func, fragment) in fragments {
+            assert_eq!(name, "chunks");
+            assert_eq!(func, arma_rs::callback::CHUNK_FUNCTION);
+            assert!(fragment.len() <= 100);
+            let Value::Array(parts) = Value::from_arma(fragment.clone()).unwrap() else {
+                panic!("fragment is not an array");
+            };
+            assert_eq!(parts[3], Value::String("large".into()));
+            let Value::String(piece) = &parts[4] else {
+                panic!("piece is not a string");
+            };
+            joined.push_str(piece);
+        }
+        assert_eq!(joined, data);
+    }
+
+    #[test]
+    fn c_interface_reply() {
+        use std::time::Duration;
+
+        use arma_rs::{FromArma, Value};
+
+        let extension = Extension::build().finish();
+        platform_extern!(
+            fn callback(name: *const i8, func: *const i8, data: *const i8) -> i32 {
+                callback_handler("c_interface_reply".to_string(), name, func, data)
+            }
+        );
+        extension.register_callback(callback);
+        extension.run_callbacks();
+        let request = extension
+            .context()
+            .request("bridge", "unit_position", "B Alpha 1-1:1")
+            .unwrap();
+        let id = request.id();
+        let waiter = std::thread::spawn(move || request.wait::<Vec<f64>>(Duration::from_secs(5)));
+
+        let data = loop {
+            let stack = get_callback_stack();
+            let stack = stack.read().unwrap();
+            if let Some((_, _, data)) = stack.get("c_interface_reply").and_then(|c| c.first()) {
+                break data.clone();
+            }
+            drop(stack);
+            std::thread::sleep(Duration::from_millis(5));
+        };
+        assert_eq!(
+            Value::from_arma(data).unwrap(),
+            Value::Array(vec![
+                Value::String(id.to_string()),
+                Value::String("B Alpha 1-1:1".into())
+            ])
+        );
+
+        let reply = |id: String| unsafe {
+            let mut output = [0i8; 1024];
+            let func = CString::new("::reply").unwrap().into_raw();
+            let mut args = [
+                CString::new(format!("\"{id}\"")).unwrap().into_raw(),
+                CString::new("[1,2,3]").unwrap().into_raw(),
+            ];
+            let code = extension.handle_call(
+                func,
+                output.as_mut_ptr(),
+                1024,
+                Some(args.as_mut_ptr()),
+                Some(2),
+                true,
+            );
+            let _ = CString::from_raw(func);
+            for arg in args {
+                let _ = CString::from_raw(arg);
+            }
+            code
+        };
+        assert_eq!(reply(id.to_string()), 0);
+        assert_eq!(waiter.join().unwrap().unwrap(), vec![1.0, 2.0, 3.0]);
+        assert_eq!(reply(id.to_string()), 9);
+        assert_eq!(reply("invalid".to_string()), 9);
+    }
+
+    #[test]
+    fn c_interface_jobs() {
+        use std::time::Duration;
+
+        use arma_rs::job::{CancellationToken, JOB_CALLBACK, JobId};
+
+        let extension = Extension::build()
+            .command("start", |ctx: Context| -> JobId {
+                ctx.spawn_job("wait", |token: CancellationToken| {
+                    while !token.is_cancelled() {
+                        std::thread::sleep(Duration::from_millis(1));
+                    }
+                    "stopped"
+                })
+            })
+            .finish();
+        platform_extern!(
+            fn callback(name: *const i8, func: *const i8, data: *const i8) -> i32 {
+                callback_handler("c_interface_jobs".to_string(), name, func, data)
+            }
+        );
+        extension.register_callback(callback);
+        extension.run_callbacks();
+
+        let call = |func: &str, arg: Option<&str>| unsafe {
+            let mut output = [0i8; 1024];
+            let func = CString::new(func).unwrap().into_raw();
+            let mut args = arg
+                .map(|arg| vec![CString::new(arg).unwrap().into_raw()])
+                .unwrap_or_default();
+            let code = extension.handle_call(
+                func,
+                output.as_mut_ptr(),
+                1024,
+                arg.map(|_| args.as_mut_ptr()),
+                arg.map(|_| 1),
+                true,
+            );
+            let _ = CString::from_raw(func);
+            for arg in args {
+                let _ = CString::from_raw(arg);
+            }
+            let output = CStr::from_ptr(output.as_ptr())
+                .to_str()
+                .unwrap()
+                .to_string();
+            (code, output)
+        };
+        assert_eq!(call("start", None), (0, "0".to_string()));
+        assert_eq!(
+            call("::jobs", None),
+            (0, r#"[[0,"wait","running",null]]"#.to_string())
+        );
+        assert_eq!(call("::job_status", None).0, 20);
+        assert_eq!(call("::job_status", Some("1")).0, 9);
+        assert_eq!(call("::job_cancel", Some("0")), (0, "true".to_string()));
+
+        let calls = loop {
+            let stack = get_callback_stack();
+            let stack = stack.read().unwrap();
+            if let Some(calls) = stack.get("c_interface_jobs") {
+                break calls.clone();
+            }
+            drop(stack);
+            std::thread::sleep(Duration::from_millis(5));
+        };
+        assert_eq!(
+            calls,
+            vec![(
+                JOB_CALLBACK.to_string(),
+                "cancelled".to_string(),
+                r#"[0,"wait","stopped"]"#.to_string()
+            )]
+        );
+        assert_eq!(
+            call("::job_status", Some("0")),
+            (0, r#"[0,"wait","cancelled","stopped"]"#.to_string())
+        );
+        assert_eq!(call("::job_cancel", Some("0")), (0, "false".to_string()));
+    }
+
+    #[test]
+    fn c_interface_scheduler() {
+        use std::time::Duration;
+
+        let extension = Extension::build()
+            .every(Duration::from_millis(5), |ctx: Context| {
+                ctx.callback_null("scheduler", "tick").unwrap();
+            })
+            .after(Duration::from_millis(5), |ctx: Context| {
+                ctx.callback_null("scheduler", "once").unwrap();
+            })
+            .cron("0 0 1 1 *", |_| {})
+            .finish();
+        platform_extern!(
+            fn callback(name: *const i8, func: *const i8, data: *const i8) -> i32 {
+                callback_handler("c_interface_scheduler".to_string(), name, func, data)
+            }
+        );
+        extension.register_callback(callback);
+        std::thread::sleep(Duration::from_millis(30));
+        assert!(extension.scheduler().tasks().iter().all(|t| t.runs() == 0));
+
+        extension.run_callbacks();
+        std::thread::sleep(Duration::from_millis(50));
+        let call = |func: &str, arg: Option<&str>| unsafe {
+            let mut output = [0i8; 1024];
+            let func = CString::new(func).unwrap().into_raw();
+            let mut args = arg
+                .map(|arg| vec![CString::new(arg).unwrap().into_raw()])
+                .unwrap_or_default();
+            let code = extension.handle_call(
+                func,
+                output.as_mut_ptr(),
+                1024,
+                arg.map(|_| args.as_mut_ptr()),
+                arg.map(|_| 1),
+                true,
+            );
+            let _ = CString::from_raw(func);
+            for arg in args {
+                let _ = CString::from_raw(arg);
+            }
+            let output = CStr::from_ptr(output.as_ptr())
+                .to_str()
+                .unwrap()
+                .to_string();
+            (code, output)
+        };
+        assert_eq!(call("::task_pause", Some("0")).0, 0);
+        assert_eq!(call("::task_pause", Some("3")).0, 9);
+        let (code, tasks) = call("::tasks", None);
+        assert_eq!(code, 0);
+        assert!(tasks.starts_with(r#"[[0,"every 5ms",true,"#));
+        assert!(tasks.ends_with(r#"[1,"after 5ms",false,1],[2,"cron 0 0 1 1 *",false,0]]"#));
+
+        let runs = extension.scheduler().tasks()[0].runs();
+        assert!(runs > 1);
+        std::thread::sleep(Duration::from_millis(30));
+        assert_eq!(extension.scheduler().tasks()[0].runs(), runs);
+        assert_eq!(call("::task_resume", Some("0")).0, 0);
+        std::thread::sleep(Duration::from_millis(30));
+        assert!(extension.scheduler().tasks()[0].runs() > runs);
+        extension.shutdown();
+
+        let stack = get_callback_stack();
+        let stack = stack.read().unwrap();
+        let calls = stack.get("c_interface_scheduler").unwrap();
+        assert_eq!(calls.iter().filter(|(_, f, _)| f == "once").count(), 1);
+        assert!(calls.iter().filter(|(_, f, _)| f == "tick").count() > 1);
+    }
+
+    #[test]
+    fn c_interface_scheduler_panic() {
+        use std::time::Duration;
+
+        let extension = Extension::build()
+            .after(Duration::ZERO, |_| panic!("task panic"))
+            .every(Duration::from_millis(5), |_| {})
+            .finish();
+        platform_extern!(
+            fn callback(_name: *const i8, _func: *const i8, _data: *const i8) -> i32 {
+                0
+            }
+        );
+        extension.register_callback(callback);
+        extension.run_callbacks();
+        std::thread::sleep(Duration::from_millis(50));
+
+        // The panic is contained, the other tasks keep running
+        let tasks = extension.scheduler().tasks();
+        assert_eq!(tasks[0].runs(), 1);
+        let runs = tasks[1].runs();
+        assert!(runs > 1);
+        std::thread::sleep(Duration::from_millis(30));
+        assert!(extension.scheduler().tasks()[1].runs() > runs);
+        extension.shutdown();
+    }
+
+    #[test]
+    fn c_interface_shutdown_detached() {
+        use std::time::{Duration, Instant};
+
+        let (tx, rx) = std::sync::mpsc::channel::<()>();
+        let rx = std::sync::Mutex::new(rx);
+        let extension = Extension::build()
+            .after(Duration::ZERO, move |_| {
+                let _ = rx.lock().unwrap().recv();
+            })
+            .finish();
+        platform_extern!(
+            fn callback(_name: *const i8, _func: *const i8, _data: *const i8) -> i32 {
+                0
+            }
+        );
+        extension.register_callback(callback);
+        extension.run_callbacks();
+        std::thread::sleep(Duration::from_millis(20));
+
+        // The task is still running, the detached shutdown must not wait for it
+        let start = Instant::now();
+        extension.shutdown_detached();
+        assert!(start.elapsed() < Duration::from_secs(1));
+        tx.send(()).unwrap();
+    }
+
+    #[test]
+    fn c_interface_stats() {
+        let extension = Extension::build()
+            .command("hello", || -> &'static str { "Hello" })
+            .finish();
+        unsafe {
+            for func in ["hello", "::stats"] {
+                let mut output = [0i8; 1024];
+                let ptr = CString::new(func).unwrap().into_raw();
+                let code = extension.handle_call(ptr, output.as_mut_ptr(), 1024, None, None, true);
+                assert_eq!(code, 0);
+                if func == "::stats" {
+                    let stats = CStr::from_ptr(output.as_ptr()).to_str().unwrap();
+                    assert!(stats.starts_with(r#"[[["hello",1,[],0,0,"#));
+                    assert!(stats.ends_with("]],0]"));
+                }
+                let _ = CString::from_raw(ptr);
+            }
+        }
+    }
+
+    mod c_interface_diagnostics {
+        use arma_rs::{CallContext, Caller, Group, diagnostics::AccessPolicy};
+
+        use super::*;
+
+        fn call(extension: &Extension, func: &str, arg: Option<&str>) -> (i32, String) {
+            unsafe {
+                let mut output = [0i8; 1024];
+                let func = CString::new(func).unwrap();
+                let arg = arg.map(|arg| CString::new(arg).unwrap());
+                let mut args = arg
+                    .iter()
+                    .map(|arg| arg.as_ptr().cast_mut())
+                    .collect::<Vec<_>>();
+                let code = extension.handle_call(
+                    func.as_ptr().cast_mut(),
+                    output.as_mut_ptr(),
+                    1024,
+                    arg.as_ref().map(|_| args.as_mut_ptr()),
+                    arg.as_ref().map(|_| 1),
+                    false,
+                );
+                let output = CStr::from_ptr(output.as_ptr())
+                    .to_str()
+                    .unwrap()
+                    .to_string();
+                (code, output)
+            }
+        }
+
+        #[test]
+        fn built_in() {
+            let extension = Extension::build()
+                .version("1.2.3".to_string())
+                .state(5_u32)
+                .group("group", Group::new().state(String::new()))
+                .diagnostic("ping", || "pong")
+                .finish();
+            assert_eq!(
+                call(&extension, "::version", None),
+                (0, "1.2.3".to_string())
+            );
+            assert_eq!(call(&extension, "::ping", None), (0, "pong".to_string()));
+            assert_eq!(
+                call(&extension, "::dump_state", None),
+                (
+                    0,
+                    r#"[["",["u32","arma_rs::job::JobManager","arma_rs::registry::Registry"]],["group",["alloc::string::String"]]]"#.to_string()
+                )
+            );
+
+            let (code, health) = call(&extension, "::health", None);
+            assert_eq!(code, 0);
+            assert!(health.ends_with(",false,0]"), "{health}");
+            assert!(!extension.health().callback_thread());
+
+            assert_eq!(call(&extension, "::log_level", None).0, 20);
+            assert_eq!(call(&extension, "::log_level", Some(r#""loud""#)).0, 9);
+            assert_eq!(call(&extension, "::log_level", Some(r#""trace""#)).0, 0);
+            assert_eq!(log::max_level(), log::LevelFilter::Trace);
+
+            assert_eq!(call(&extension, "::missing", None).0, 1);
+        }
+
+        #[test]
+        fn access_policy() {
+            let extension = Extension::build()
+                .diagnostic("ping", || "pong")
+                .diagnostic_access(AccessPolicy::Deny)
+                .finish();
+            assert_eq!(call(&extension, "::version", None), (1, String::new()));
+            assert_eq!(call(&extension, "::ping", None), (1, String::new()));
+            assert_eq!(call(&extension, "::stats", None), (1, String::new()));
+            assert_eq!(call(&extension, "::jobs", None), (1, String::new()));
+            // Replies are never denied, requests made by the extension would time out
+            assert_eq!(call(&extension, "::reply", None).0, 20);
+
+            let extension = Extension::build()
+                .diagnostic("whoami", |call: CallContext| {
+                    call.caller().as_u64().to_string()
+                })
+                .diagnostic_access(AccessPolicy::custom(|_, call| {
+                    call.caller() == &Caller::Steam(76_561_198_000_000_000)
+                }))
+                .finish();
+            assert_eq!(call(&extension, "::whoami", None).0, 1);
+            let source = CString::new("").unwrap();
+            let mut context = vec![
+                76_561_198_000_000_000_u64 as *mut i8,
+                source.as_ptr().cast_mut(),
+                source.as_ptr().cast_mut(),
+                source.as_ptr().cast_mut(),
+                std::ptr::null_mut(),
+            ];
+            unsafe { extension.handle_call_context(context.as_mut_ptr(), 5) };
+            assert_eq!(
+                call(&extension, "::whoami", None),
+                (0, "76561198000000000".to_string())
+            );
+        }
+
+        #[cfg(feature = "serde")]
+        #[test]
+        fn access_policy_save_state() {
+            let extension = Extension::build()
+                .diagnostic_access(AccessPolicy::Deny)
+                .finish();
+            assert_eq!(call(&extension, "::save_state", None), (1, String::new()));
+
+            let extension = Extension::build().finish();
+            assert_eq!(call(&extension, "::save_state", None).0, 0);
+        }
+
+        #[test]
+        #[should_panic(expected = "reserved")]
+        fn reserved_name() {
+            let _ = Extension::build().diagnostic("health", || "ok");
+        }
+    }
+
     #[test]
     fn c_interface_builder() {
         let extension = Extension::build().finish();
@@ -134,7 +670,7 @@ mod extension {
 
         #[test]
         fn extension() {
-            let mut extension = Extension::build()
+            let extension = Extension::build()
                 .command(
                     "callback_invalid_name",
                     |ctx: Context| -> Result<(), CallbackError> {
@@ -480,4 +1016,349 @@ mod extension {
             let _ = CString::from_raw(ptr_false);
         }
     }
+
+    mod c_interface_fuzz {
+        use arma_rs::{CallContext, CallContextStackTrace, Value};
+
+        use super::*;
+
+        /// Deterministic xorshift generator, so failures can be reproduced
+        struct Rng(u64);
+
+        impl Rng {
+            fn next(&mut self) -> u64 {
+                self.0 ^= self.0 << 13;
+                self.0 ^= self.0 >> 7;
+                self.0 ^= self.0 << 17;
+                self.0
+            }
+
+            /// Arbitrary bytes ending at the first zero byte, like a C string from Arma.
+            /// Half of the bytes are SQF syntax so the parsers see more than garbage.
+            fn cstring(&mut self) -> CString {
+                const SQF: &[u8] = b"[]\",-.0123456789eEtruefalsnil ";
+                let len = self.next() % 24;
+                let bytes = (0..len)
+                    .map(|_| {
+                        let n = self.next();
+                        if n.is_multiple_of(2) {
+                            SQF[(n >> 8) as usize % SQF.len()]
+                        } else {
+                            (n >> 8) as u8
+                        }
+                    })
+                    .take_while(|b| *b != 0)
+                    .collect::<Vec<u8>>();
+                CString::new(bytes).unwrap()
+            }
+        }
+
+        #[test]
+        fn handle_call() {
+            let extension = Extension::build()
+                .command("value", |value: Value| value)
+                .command("add", |a: i32, b: i32| a + b)
+                .command("strings", |a: Vec<String>| a.join(","))
+                .command("context", |call: CallContext| -> String {
+                    call.source().as_str().to_string()
+                })
+                .finish();
+            let functions = ["value", "add", "strings", "context", "::reply"];
+            let mut rng = Rng(0x2545_f491_4f6c_dd1d);
+            for _ in 0..5000 {
+                let function = if rng.next().is_multiple_of(4) {
+                    rng.cstring()
+                } else {
+                    CString::new(functions[(rng.next() % 5) as usize]).unwrap()
+                };
+                let args = (0..rng.next() % 4)
+                    .map(|_| rng.cstring())
+                    .collect::<Vec<_>>();
+                let mut pointers = args
+                    .iter()
+                    .map(|arg| arg.as_ptr().cast_mut())
+                    .collect::<Vec<_>>();
+                // Arma never passes null arguments, they must still not crash the extension
+                if !pointers.is_empty() && rng.next().is_multiple_of(8) {
+                    pointers[0] = std::ptr::null_mut();
+                }
+                let size = (rng.next() % 32) as usize + 1;
+                let mut output = vec![0i8; size];
+                let code = unsafe {
+                    extension.handle_call(
+                        function.as_ptr().cast_mut(),
+                        output.as_mut_ptr(),
+                        size,
+                        Some(pointers.as_mut_ptr()),
+                        Some(pointers.len() as i32),
+                        true,
+                    )
+                };
+                assert!(code >= 0, "{function:?} {args:?} returned {code}");
+                let written = unsafe { CStr::from_ptr(output.as_ptr()) };
+                assert!(written.to_bytes().len() < size);
+            }
+
+            unsafe {
+                let mut output = [0i8; 16];
+                let code = extension.handle_call(
+                    std::ptr::null_mut(),
+                    output.as_mut_ptr(),
+                    16,
+                    None,
+                    None,
+                    true,
+                );
+                assert_eq!(code, 1);
+                let add = CString::new("add").unwrap();
+                let code = extension.handle_call(
+                    add.as_ptr().cast_mut(),
+                    output.as_mut_ptr(),
+                    16,
+                    Some(std::ptr::null_mut()),
+                    Some(-3),
+                    true,
+                );
+                assert_eq!(code, 20);
+                let invalid = CString::new(b"add\xff".to_vec()).unwrap();
+                let code = extension.handle_call(
+                    invalid.as_ptr().cast_mut(),
+                    output.as_mut_ptr(),
+                    16,
+                    None,
+                    None,
+                    true,
+                );
+                assert_eq!(code, 1);
+            }
+        }
+
+        #[test]
+        fn handle_call_context() {
+            let extension = Extension::build()
+                .command("context", |call: CallContext| -> String {
+                    format!(
+                        "{}|{}|{}|{}",
+                        call.source().as_str(),
+                        call.mission().as_str(),
+                        call.server().as_str(),
+                        call.error().map(ToString::to_string).unwrap_or_default(),
+                    )
+                })
+                .command("stack", |call: CallContextStackTrace| {
+                    call.stack_trace().lines.len().to_string()
+                })
+                .finish();
+            let mut rng = Rng(0x9e37_79b9_7f4a_7c15);
+            for _ in 0..2000 {
+                let fields = [rng.cstring(), rng.cstring(), rng.cstring()];
+                let mut context = vec![
+                    rng.next() as *mut i8,
+                    fields[0].as_ptr().cast_mut(),
+                    fields[1].as_ptr().cast_mut(),
+                    fields[2].as_ptr().cast_mut(),
+                    (rng.next() % 8) as *mut i8,
+                    std::ptr::null_mut(),
+                ];
+                let count = (rng.next() % 7) as i32;
+                let expected = if count < 5 {
+                    format!("|||Call context has {count} values, expected at least 5")
+                } else {
+                    let lossy = fields
+                        .iter()
+                        .map(|field| String::from_utf8_lossy(field.as_bytes()))
+                        .collect::<Vec<_>>();
+                    let error = ["source", "mission", "server"]
+                        .iter()
+                        .zip(&fields)
+                        .find(|(_, field)| field.to_str().is_err())
+                        .map(|(name, _)| format!("Call context {name} is not valid UTF-8"))
+                        .unwrap_or_default();
+                    format!("{}|{}|{}|{error}", lossy[0], lossy[1], lossy[2])
+                };
+
+                for (function, expected) in [("context", expected.as_str()), ("stack", "0")] {
+                    unsafe {
+                        extension.handle_call_context(context.as_mut_ptr(), count);
+                        let mut output = [0i8; 1024];
+                        let function = CString::new(function).unwrap();
+                        let code = extension.handle_call(
+                            function.as_ptr().cast_mut(),
+                            output.as_mut_ptr(),
+                            1024,
+                            None,
+                            None,
+                            false,
+                        );
+                        assert_eq!(code, 0);
+                        assert_eq!(CStr::from_ptr(output.as_ptr()).to_string_lossy(), expected);
+                    }
+                }
+            }
+
+            unsafe {
+                extension.handle_call_context(std::ptr::null_mut(), 5);
+                let mut output = [0i8; 1024];
+                let function = CString::new("context").unwrap();
+                let code = extension.handle_call(
+                    function.as_ptr().cast_mut(),
+                    output.as_mut_ptr(),
+                    1024,
+                    None,
+                    None,
+                    false,
+                );
+                assert_eq!(code, 0);
+                assert_eq!(
+                    CStr::from_ptr(output.as_ptr()).to_str(),
+                    Ok("|||Call context context is a null pointer")
+                );
+            }
+        }
+    }
+
+    #[cfg(feature = "tracing")]
+    mod c_interface_tracing {
+        use std::sync::Mutex;
+
+        use arma_rs::CallContext;
+        use tracing::{
+            Event, Id, Subscriber,
+            field::{Field, Visit},
+            span::{Attributes, Record},
+        };
+        use tracing_subscriber::{
+            layer::{Context as LayerContext, SubscriberExt},
+            registry::LookupSpan,
+        };
+
+        use super::*;
+
+        type Fields = HashMap<String, String>;
+
+        #[derive(Clone, Default)]
+        struct Recorder {
+            spans: Arc<Mutex<Vec<Fields>>>,
+            events: Arc<Mutex<Vec<Fields>>>,
+        }
+
+        struct Collect<'a>(&'a mut Fields);
+
+        impl Visit for Collect<'_> {
+            fn record_str(&mut self, field: &Field, value: &str) {
+                self.0.insert(field.name().to_string(), value.to_string());
+            }
+
+            fn record_debug(&mut self, field: &Field, value: &dyn std::fmt::Debug) {
+                self.0
+                    .insert(field.name().to_string(), format!("{value:?}"));
+            }
+        }
+
+        impl<S> tracing_subscriber::Layer<S> for Recorder
+        where
+            S: Subscriber + for<'a> LookupSpan<'a>,
+        {
+            fn on_new_span(&self, attrs: &Attributes<'_>, id: &Id, ctx: LayerContext<'_, S>) {
+                let mut fields = Fields::new();
+                fields.insert("name".to_string(), attrs.metadata().name().to_string());
+                attrs.record(&mut Collect(&mut fields));
+                let mut spans = self.spans.lock().unwrap();
+                ctx.span(id).unwrap().extensions_mut().insert(spans.len());
+                spans.push(fields);
+            }
+
+            fn on_record(&self, id: &Id, values: &Record<'_>, ctx: LayerContext<'_, S>) {
+                let index = *ctx.span(id).unwrap().extensions().get::<usize>().unwrap();
+                values.record(&mut Collect(&mut self.spans.lock().unwrap()[index]));
+            }
+
+            fn on_event(&self, event: &Event<'_>, _: LayerContext<'_, S>) {
+                let mut fields = Fields::new();
+                event.record(&mut Collect(&mut fields));
+                self.events.lock().unwrap().push(fields);
+            }
+        }
+
+        #[test]
+        fn extension() {
+            let extension = Extension::build()
+                .command("hello", |call: CallContext| -> String {
+                    call.mission().as_str().to_string()
+                })
+                .command("callback", |ctx: Context| -> Result<(), CallbackError> {
+                    ctx.callback_null("tracing", "fired")
+                })
+                .finish();
+            platform_extern!(
+                fn callback(_name: *const i8, _func: *const i8, _data: *const i8) -> i32 {
+                    0
+                }
+            );
+            extension.register_callback(callback);
+            extension.run_callbacks();
+            // Callback events are recorded on the thread sending callbacks
+            let recorder = Recorder::default();
+            tracing::subscriber::set_global_default(
+                tracing_subscriber::registry().with(recorder.clone()),
+            )
+            .unwrap();
+            unsafe {
+                let source = CString::new("").unwrap();
+                let mission = CString::new("test.VR").unwrap();
+                let server = CString::new("").unwrap();
+                let mut context = vec![
+                    76_561_198_000_000_000_u64 as *mut i8,
+                    source.as_ptr().cast_mut(),
+                    mission.as_ptr().cast_mut(),
+                    server.as_ptr().cast_mut(),
+                    std::ptr::null_mut(),
+                ];
+                extension.handle_call_context(context.as_mut_ptr(), 5);
+
+                for func in ["hello", "callback"] {
+                    let mut output = [0i8; 1024];
+                    let ptr = CString::new(func).unwrap().into_raw();
+                    let code =
+                        extension.handle_call(ptr, output.as_mut_ptr(), 1024, None, None, false);
+                    assert_eq!(code, 0);
+                    let _ = CString::from_raw(ptr);
+                }
+            }
+            extension.shutdown();
+
+            // Other tests may record spans and events while the subscriber is installed
+            let spans = recorder.spans.lock().unwrap();
+            let find = |function: &str| {
+                spans
+                    .iter()
+                    .find(|span| {
+                        span.get("function").map(String::as_str) == Some(function)
+                            && span["name"] == "call"
+                    })
+                    .unwrap()
+            };
+            let hello = find("hello");
+            assert_eq!(hello["name"], "call");
+            assert_eq!(hello["function"], "hello");
+            assert_eq!(hello["args"], "0");
+            assert_eq!(hello["code"], "0");
+            assert!(hello.contains_key("duration_us"));
+            assert_eq!(hello["caller"], "76561198000000000");
+            assert_eq!(hello["mission"], "test.VR");
+            assert_eq!(hello["server"], "");
+
+            let callback = find("callback");
+            assert!(!callback.contains_key("caller"));
+
+            let events = recorder.events.lock().unwrap();
+            let events = events
+                .iter()
+                .filter(|event| event.get("name").map(String::as_str) == Some("tracing"))
+                .collect::<Vec<_>>();
+            assert_eq!(events.len(), 1);
+            assert_eq!(events[0]["func"], "fired");
+            assert_eq!(events[0]["delivered"], "true");
+        }
+    }
 }
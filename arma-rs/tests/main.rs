@@ -2,6 +2,14 @@
 mod extension {
     use arma_rs::{Context, ContextState, Extension, Group};
 
+    #[test]
+    #[cfg(not(miri))]
+    fn attribute() {
+        let tests = trybuild::TestCases::new();
+        tests.compile_fail("tests/arma/*fail*.rs");
+        tests.pass("tests/arma/*pass*.rs");
+    }
+
     #[test]
     fn root_command() {
         let extension = Extension::build()
@@ -332,6 +340,653 @@ mod extension {
         assert_eq!(value, 21);
     }
 
+    #[test]
+    fn lifecycle_on_load() {
+        let extension = Extension::build()
+            .on_load(|ctx| {
+                ctx.global().set(String::from("loaded"));
+            })
+            .finish()
+            .testing();
+        let value = extension.state().try_get::<String>();
+        assert_eq!(value, Some(&String::from("loaded")));
+    }
+
+    #[test]
+    fn lifecycle_on_shutdown() {
+        use std::sync::{
+            Arc,
+            atomic::{AtomicUsize, Ordering},
+        };
+
+        let calls = Arc::new(AtomicUsize::new(0));
+        let extension = Extension::build()
+            .on_shutdown({
+                let calls = calls.clone();
+                move |_| {
+                    calls.fetch_add(1, Ordering::SeqCst);
+                }
+            })
+            .finish();
+        assert_eq!(calls.load(Ordering::SeqCst), 0);
+        extension.shutdown();
+        assert_eq!(calls.load(Ordering::SeqCst), 1);
+        drop(extension);
+        assert_eq!(calls.load(Ordering::SeqCst), 1);
+    }
+
+    #[cfg(feature = "serde")]
+    #[test]
+    fn state_persistent() {
+        use std::sync::atomic::{AtomicUsize, Ordering};
+
+        let dir =
+            std::env::temp_dir().join(format!("arma-rs-{}-state_persistent", std::process::id()));
+        let path = dir.join("state_persistent.json");
+        let build = || {
+            Extension::build()
+                .persistent_state::<AtomicUsize>(&path)
+                .command("increment", |ctx: Context| {
+                    ctx.global()
+                        .get::<AtomicUsize>()
+                        .expect("state not found")
+                        .fetch_add(1, Ordering::Relaxed);
+                })
+                .finish()
+                .testing()
+        };
+
+        let extension = build();
+        assert_eq!(
+            extension
+                .state()
+                .get::<AtomicUsize>()
+                .load(Ordering::Relaxed),
+            0
+        );
+        let (_, _) = extension.call("increment", None);
+        let (_, _) = extension.call("increment", None);
+        drop(extension);
+
+        let extension = build();
+        assert_eq!(
+            extension
+                .state()
+                .get::<AtomicUsize>()
+                .load(Ordering::Relaxed),
+            2
+        );
+        drop(extension);
+        std::fs::remove_dir_all(dir).unwrap();
+    }
+
+    #[cfg(feature = "config")]
+    #[test]
+    fn config_reload() {
+        use std::sync::atomic::{AtomicUsize, Ordering};
+
+        use arma_rs::config::Config;
+
+        #[derive(Default, serde::Deserialize)]
+        struct Settings {
+            level: u32,
+        }
+
+        let path = std::env::temp_dir()
+            .join(format!("arma-rs-{}", std::process::id()))
+            .join("config_reload.toml");
+        std::fs::create_dir_all(path.parent().unwrap()).unwrap();
+        std::fs::write(&path, "level = 1").unwrap();
+
+        let extension = Extension::build()
+            .config_file::<Settings>(&path)
+            .state(AtomicUsize::new(0))
+            .on_config_change(|ctx| {
+                ctx.global()
+                    .get::<AtomicUsize>()
+                    .unwrap()
+                    .fetch_add(1, Ordering::SeqCst);
+            })
+            .finish();
+        let level = || {
+            extension
+                .context()
+                .global()
+                .get::<Config<Settings>>()
+                .unwrap()
+                .get()
+                .level
+        };
+        assert_eq!(level(), 1);
+
+        assert!(!extension.reload_config().unwrap());
+        std::fs::write(&path, "level = 2").unwrap();
+        assert!(extension.reload_config().unwrap());
+        assert_eq!(level(), 2);
+
+        std::fs::write(&path, "level = ").unwrap();
+        assert!(extension.reload_config().is_err());
+        assert_eq!(level(), 2);
+
+        let changes = extension
+            .context()
+            .global()
+            .get::<AtomicUsize>()
+            .unwrap()
+            .load(Ordering::SeqCst);
+        assert_eq!(changes, 1);
+        std::fs::remove_file(path).unwrap();
+    }
+
+    #[test]
+    fn event_emit() {
+        use std::time::Duration;
+
+        use arma_rs::{ArmaEvent, FromArma, IntoArma, testing};
+
+        #[derive(Debug, PartialEq, FromArma, IntoArma, ArmaEvent)]
+        #[arma_event(name = "my_ext")]
+        struct PlayerJoined {
+            uid: String,
+            score: u32,
+        }
+
+        #[derive(Debug, FromArma, IntoArma, ArmaEvent)]
+        #[arma_event(name = "my_ext", func = "player_joined")]
+        struct WrongShape(u32, u32, u32);
+
+        let extension = Extension::build()
+            .command("join", |ctx: Context, uid: String| {
+                ctx.callback_null("my_ext", "unrelated").unwrap();
+                ctx.emit(PlayerJoined { uid, score: 10 }).unwrap();
+            })
+            .finish()
+            .testing();
+        let (_, code) = extension.call("join", Some(vec![String::from("123")]));
+        assert_eq!(code, 0);
+        assert_eq!(
+            extension.expect_event::<PlayerJoined>(Duration::from_secs(1)),
+            testing::Result::Ok(PlayerJoined {
+                uid: String::from("123"),
+                score: 10
+            })
+        );
+        assert_eq!(
+            extension.expect_event::<PlayerJoined>(Duration::from_millis(10)),
+            testing::Result::Timeout
+        );
+
+        let (_, code) = extension.call("join", Some(vec![String::from("123")]));
+        assert_eq!(code, 0);
+        assert!(
+            extension
+                .expect_event::<WrongShape>(Duration::from_secs(1))
+                .is_err()
+        );
+    }
+
+    #[test]
+    fn event_bus() {
+        use std::sync::{
+            Arc,
+            atomic::{AtomicU32, Ordering},
+        };
+        use std::time::Duration;
+
+        use arma_rs::{ArmaEvent, IntoArma, Value, bus::Delivery, testing};
+
+        #[derive(IntoArma, ArmaEvent)]
+        #[arma_event(name = "my_ext")]
+        struct PlayerJoined {
+            uid: String,
+        }
+
+        let joined = Arc::new(AtomicU32::new(0));
+        let counted = joined.clone();
+        let extension = Extension::build()
+            .group(
+                "persistence",
+                Group::new().command("join", |ctx: Context, uid: String| {
+                    u32::try_from(ctx.publish(PlayerJoined { uid }).unwrap()).unwrap()
+                }),
+            )
+            .group(
+                "stats",
+                Group::new()
+                    .state(AtomicU32::new(0))
+                    .subscribe(move |ctx: Context, _: &PlayerJoined| {
+                        let count = ctx.group().get::<AtomicU32>().unwrap();
+                        counted.store(count.fetch_add(1, Ordering::SeqCst) + 1, Ordering::SeqCst);
+                    })
+                    .subscribe_with(Delivery::Pool, |ctx: Context, event: &PlayerJoined| {
+                        ctx.callback_data("stats", "pooled", event.uid.clone())
+                            .unwrap();
+                    }),
+            )
+            .group("relay", Group::new().forward::<PlayerJoined>())
+            .finish()
+            .testing();
+        for _ in 0..2 {
+            assert_eq!(
+                extension.call("persistence:join", Some(vec![String::from("123")])),
+                (String::from("3"), 0)
+            );
+        }
+        assert_eq!(joined.load(Ordering::SeqCst), 2);
+
+        let received = std::cell::RefCell::new(Vec::new());
+        let result = extension.callback_handler(
+            |name, func, data| {
+                received
+                    .borrow_mut()
+                    .push((name.to_string(), func.to_string(), data));
+                if received.borrow().len() == 4 {
+                    testing::Result::Ok(())
+                } else {
+                    testing::Result::<(), ()>::Continue
+                }
+            },
+            Duration::from_secs(2),
+        );
+        assert!(result.is_ok());
+        let mut received = received.into_inner();
+        received.sort_by(|a, b| a.0.cmp(&b.0));
+        let uid = Some(Value::String(String::from("123")));
+        let forwarded = Some(Value::Array(vec![Value::Array(vec![
+            Value::String(String::from("uid")),
+            Value::String(String::from("123")),
+        ])]));
+        assert_eq!(
+            received,
+            vec![
+                ("my_ext".into(), "player_joined".into(), forwarded.clone()),
+                ("my_ext".into(), "player_joined".into(), forwarded),
+                ("stats".into(), "pooled".into(), uid.clone()),
+                ("stats".into(), "pooled".into(), uid),
+            ]
+        );
+    }
+
+    #[test]
+    fn registry() {
+        use arma_rs::registry::RegistryError;
+
+        let extension = Extension::build()
+            .group(
+                "mission",
+                Group::new().command("start", |ctx: Context, mode: String| {
+                    if mode == "ctf" {
+                        ctx.registry()
+                            .command("ctf:score", |ctx: Context| {
+                                ctx.registry().remove_group("ctf");
+                                10
+                            })
+                            .unwrap();
+                    }
+                }),
+            )
+            .command("fixed", || "fixed")
+            .finish()
+            .testing();
+        assert_eq!(extension.call("ctf:score", None).1, 1);
+        let (_, code) = extension.call("mission:start", Some(vec![String::from("ctf")]));
+        assert_eq!(code, 0);
+        assert_eq!(extension.call("ctf:score", None), (String::from("10"), 0));
+        assert_eq!(extension.call("ctf:score", None).1, 1);
+
+        let registry = extension.context().registry().clone();
+        std::thread::spawn(move || {
+            registry.command("koth:hill", |hill: u8| hill * 2).unwrap();
+            assert_eq!(
+                registry.command("fixed", || 1),
+                Err(RegistryError::Exists(String::from("fixed")))
+            );
+        })
+        .join()
+        .unwrap();
+        assert_eq!(
+            extension.call("koth:hill", Some(vec![String::from("2")])),
+            (String::from("4"), 0)
+        );
+        assert_eq!(extension.call("koth:hill", None).1, 20);
+        assert_eq!(extension.metrics().command("koth:hill").unwrap().calls(), 2);
+        assert_eq!(extension.call("fixed", None), (String::from("fixed"), 0));
+    }
+
+    #[test]
+    fn registry_group() {
+        use arma_rs::registry::RegistryError;
+
+        let extension = Extension::build()
+            .group(
+                "stats",
+                Group::new().command("get", || "get").alias("fetch", "get"),
+            )
+            .group("v2", Group::new().command("reset", || "reset"))
+            .default_version(2)
+            .finish()
+            .testing();
+        let registry = extension.context().registry().clone();
+        // Built in aliases and default version paths can not be shadowed
+        for path in ["stats:fetch", "reset"] {
+            assert_eq!(
+                registry.command(path, || "shadowed"),
+                Err(RegistryError::Exists(path.to_string()))
+            );
+        }
+
+        registry
+            .group(
+                "ctf",
+                Group::new()
+                    .state(std::sync::atomic::AtomicU32::new(0))
+                    .command("score", |ctx: Context| {
+                        let score = ctx.group().get::<std::sync::atomic::AtomicU32>().unwrap();
+                        score.fetch_add(1, std::sync::atomic::Ordering::SeqCst) + 1
+                    })
+                    .alias("point", "score"),
+            )
+            .unwrap();
+        assert_eq!(extension.call("ctf:score", None), (String::from("1"), 0));
+        assert_eq!(extension.call("ctf:point", None), (String::from("2"), 0));
+        assert_eq!(registry.remove_group("ctf"), 2);
+        assert_eq!(extension.call("ctf:score", None).1, 1);
+    }
+
+    #[test]
+    fn feature_flags() {
+        use arma_rs::{CallContext, CallContextStackTrace, FeatureFlags};
+
+        let extension = Extension::build()
+            .command("plain", || {})
+            .command("context", |_: CallContext| {})
+            .group(
+                "debug",
+                Group::new().command("trace", |_: Context, _: CallContextStackTrace| {}),
+            )
+            .finish()
+            .testing();
+        let stack_trace = FeatureFlags::new().with_context_stack_trace(true);
+        assert_eq!(extension.command_flags("plain"), Some(FeatureFlags::new()));
+        assert_eq!(
+            extension.command_flags("context"),
+            Some(FeatureFlags::new())
+        );
+        assert_eq!(extension.command_flags("debug:trace"), Some(stack_trace));
+        assert_eq!(extension.command_flags("missing"), None);
+        assert_eq!(extension.feature_flags(), stack_trace);
+
+        let always = FeatureFlags::new().with_context_always(true);
+        let extension = Extension::build()
+            .command("plain", || {})
+            .command_always_context("audit", |ctx: CallContext| {
+                ctx.caller().as_u64().to_string()
+            })
+            .finish()
+            .testing();
+        assert_eq!(extension.command_flags("audit"), Some(always));
+        assert_eq!(extension.feature_flags(), always);
+
+        let registry = extension.context().registry().clone();
+        registry
+            .command("runtime:trace", |_: CallContextStackTrace| {})
+            .unwrap();
+        assert_eq!(extension.command_flags("runtime:trace"), Some(stack_trace));
+        assert_eq!(extension.feature_flags(), always.union(stack_trace));
+        assert!(registry.remove_command("runtime:trace"));
+        assert_eq!(extension.feature_flags(), always);
+    }
+
+    #[test]
+    fn fallback_and_alias() {
+        let extension = Extension::build()
+            .group(
+                "vehicles",
+                Group::new()
+                    .command("spawn", |class: String| format!("spawned {class}"))
+                    .alias("create", "spawn")
+                    .fallback(|_ctx: Context, path: String, args: Vec<String>| {
+                        format!("vehicles {path} {}", args.join(","))
+                    })
+                    .group("air", Group::new().command("land", || "landed")),
+            )
+            .group("empty", Group::new())
+            .fallback(|_ctx: Context, path: String, _args: Vec<String>| {
+                Err::<(), _>(format!("unknown command {path}"))
+            })
+            .finish()
+            .testing();
+        let heli = Some(vec![String::from("heli")]);
+        assert_eq!(
+            extension.call("vehicles:create", heli.clone()),
+            (String::from("spawned heli"), 0)
+        );
+        assert_eq!(
+            extension.call("vehicles:repair:all", Some(vec![String::from("1")])),
+            (String::from("vehicles repair:all 1"), 0)
+        );
+        assert_eq!(
+            extension.call("vehicles:air:fly", heli),
+            (String::from("vehicles air:fly heli"), 0)
+        );
+        assert_eq!(
+            extension.call("vehicles:air:land", None),
+            (String::from("landed"), 0)
+        );
+        assert_eq!(
+            extension.call("empty:command", None),
+            (String::from("unknown command empty:command"), 9)
+        );
+        assert_eq!(
+            extension
+                .metrics()
+                .command("vehicles:spawn")
+                .unwrap()
+                .calls(),
+            1
+        );
+    }
+
+    #[test]
+    fn versions() {
+        let extension = Extension::build()
+            .versioned(
+                1,
+                Group::new().group("stats", Group::new().command("get", || "v1")),
+            )
+            .versioned(
+                2,
+                Group::new()
+                    .group("stats", Group::new().command("get", || "v2"))
+                    .command("ping", || "pong"),
+            )
+            .command("ping", || "root")
+            .default_version(2)
+            .finish()
+            .testing();
+        assert_eq!(
+            extension.call("v1:stats:get", None),
+            (String::from("v1"), 0)
+        );
+        assert_eq!(
+            extension.call("v2:stats:get", None),
+            (String::from("v2"), 0)
+        );
+        assert_eq!(extension.call("stats:get", None), (String::from("v2"), 0));
+        assert_eq!(extension.call("ping", None), (String::from("root"), 0));
+        assert_eq!(extension.call("v2:ping", None), (String::from("pong"), 0));
+        assert_eq!(extension.call("v3:stats:get", None).1, 1);
+    }
+
+    #[test]
+    #[should_panic(expected = "default version `v3` does not exist")]
+    fn missing_default_version() {
+        let _ = Extension::build()
+            .versioned(2, Group::new())
+            .default_version(3)
+            .finish();
+    }
+
+    #[test]
+    fn deprecated() {
+        use arma_rs::{CallContext, Caller, Mission, Server, Source};
+
+        let extension = Extension::build()
+            .command_deprecated("player", "use v2:player", |call: CallContext| {
+                call.caller().as_str()
+            })
+            .command("current", || {})
+            .finish()
+            .testing();
+        for _ in 0..2 {
+            let (result, code) = extension.call_with_context(
+                "player",
+                None,
+                Caller::Steam(76_561_198_000_000_000),
+                Source::Pbo(String::from("fn_player.sqf")),
+                Mission::None,
+                Server::Singleplayer,
+                0,
+            );
+            assert_eq!((result.as_str(), code), ("76561198000000000", 0));
+        }
+        let metrics = extension.metrics();
+        assert_eq!(
+            metrics.command("player").unwrap().deprecated(),
+            Some("use v2:player")
+        );
+        assert_eq!(metrics.command("current").unwrap().deprecated(), None);
+    }
+
+    #[test]
+    fn case_insensitive() {
+        let extension = Extension::build()
+            .group("Mission", Group::new().command("Start", || "started"))
+            .case_insensitive()
+            .finish()
+            .testing();
+        assert_eq!(
+            extension.call("mission:START", None),
+            (String::from("started"), 0)
+        );
+        extension
+            .context()
+            .registry()
+            .command("CTF:Score", || 10)
+            .unwrap();
+        assert_eq!(extension.call("ctf:score", None), (String::from("10"), 0));
+        assert!(extension.context().registry().contains("Ctf:SCORE"));
+    }
+
+    #[test]
+    #[should_panic(expected = "must not be empty or contain `:`")]
+    fn command_name_with_separator() {
+        let _ = Group::new().command("mission:start", || {});
+    }
+
+    #[test]
+    #[should_panic(expected = "points to missing command")]
+    fn alias_missing_command() {
+        let _ = Extension::build().alias("old", "new").finish();
+    }
+
+    #[test]
+    #[should_panic(expected = "more than once when ignoring case")]
+    fn case_insensitive_collision() {
+        let _ = Extension::build()
+            .command("start", || {})
+            .command("Start", || {})
+            .case_insensitive()
+            .finish();
+    }
+
+    #[test]
+    fn metrics() {
+        let extension = Extension::build()
+            .command("add", |a: i32, b: i32| a + b)
+            .group(
+                "strings",
+                Group::new().command("long", |ctx: Context| "X".repeat(ctx.buffer_len() + 1)),
+            )
+            .finish()
+            .testing();
+        let _ = extension.call("add", Some(vec![String::from("1"), String::from("2")]));
+        let _ = extension.call("add", Some(vec![String::from("1"), String::from("a")]));
+        let _ = extension.call("add", None);
+        let _ = extension.call("strings:long", None);
+        let _ = extension.call("missing", None);
+        let _ = extension.call("missing:long", None);
+
+        let metrics = extension.metrics();
+        let add = metrics.command("add").unwrap();
+        assert_eq!(add.calls(), 3);
+        assert_eq!(add.parse_failures(), 1);
+        assert_eq!(add.errors().get(&31), Some(&1));
+        assert_eq!(add.errors().get(&20), Some(&1));
+        assert_eq!(add.latency().count(), 3);
+        let long = metrics.command("strings:long").unwrap();
+        assert_eq!(long.calls(), 1);
+        assert_eq!(long.overflows(), 1);
+        assert_eq!(metrics.unknown_commands(), 2);
+        assert_eq!(
+            metrics
+                .commands()
+                .iter()
+                .map(arma_rs::metrics::CommandStats::name)
+                .collect::<Vec<_>>(),
+            vec!["add", "strings:long"]
+        );
+    }
+
+    #[test]
+    fn worker_pool() {
+        use std::time::Duration;
+
+        use arma_rs::{
+            Value,
+            pool::{PoolConfig, SpawnError},
+        };
+
+        let extension = Extension::build()
+            .worker_pool(PoolConfig::new().threads(1).queue(1))
+            .command("work", |ctx: Context, id: u8| -> Result<(), SpawnError> {
+                ctx.spawn(move |ctx| {
+                    std::thread::sleep(Duration::from_millis(100));
+                    ctx.callback_data("pool", "done", id).unwrap();
+                })
+            })
+            .finish()
+            .testing();
+        assert_eq!(extension.call("work", Some(vec![String::from("1")])).1, 0);
+        std::thread::sleep(Duration::from_millis(50));
+        assert_eq!(extension.call("work", Some(vec![String::from("2")])).1, 0);
+        assert_eq!(
+            extension.call("work", Some(vec![String::from("3")])),
+            (
+                String::from("Worker pool saturated, 1 tasks already queued"),
+                9
+            )
+        );
+        let done = std::cell::RefCell::new(Vec::new());
+        let result = extension.callback_handler(
+            |name, func, data| {
+                assert_eq!((name, func), ("pool", "done"));
+                done.borrow_mut().push(data);
+                if done.borrow().len() == 2 {
+                    arma_rs::testing::Result::Ok(())
+                } else {
+                    arma_rs::testing::Result::<(), ()>::Continue
+                }
+            },
+            Duration::from_secs(2),
+        );
+        assert!(result.is_ok());
+        assert_eq!(
+            done.into_inner(),
+            vec![Some(Value::Number(1.0)), Some(Value::Number(2.0))]
+        );
+    }
+
     mod call_context {
         use arma_rs::{CallContext, Caller, Extension, Mission, Server, Source};
 
@@ -363,5 +1018,39 @@ mod extension {
                 "Steam(123),Pbo(\"pbo\"),Mission(\"mission\"),Multiplayer(\"server\")"
             );
         }
+
+        #[test]
+        fn concurrent_calls() {
+            let calls = std::sync::Arc::new(std::sync::atomic::AtomicUsize::new(0));
+            let counter = calls.clone();
+            let extension = Extension::build()
+                .command("caller", move |call_context: CallContext| {
+                    counter.fetch_add(1, std::sync::atomic::Ordering::Relaxed);
+                    std::thread::yield_now();
+                    call_context.caller().as_u64().to_string()
+                })
+                .finish()
+                .testing();
+            std::thread::scope(|scope| {
+                for caller in 0..8 {
+                    let extension = &extension;
+                    scope.spawn(move || {
+                        for _ in 0..50 {
+                            let (result, code) = extension.call_with_context(
+                                "caller",
+                                None,
+                                Caller::Steam(caller),
+                                Source::Console,
+                                Mission::default(),
+                                Server::default(),
+                                0,
+                            );
+                            assert_eq!((result, code), (caller.to_string(), 0));
+                        }
+                    });
+                }
+            });
+            assert_eq!(calls.load(std::sync::atomic::Ordering::Relaxed), 400);
+        }
     }
 }